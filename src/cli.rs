@@ -1,5 +1,6 @@
 use anyhow::Context;
 use clap::{Args, Parser, Subcommand};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "mepris")]
@@ -8,6 +9,20 @@ use clap::{Args, Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    #[arg(
+        long,
+        global = true,
+        help = "List installed packages again instead of using the cached lists"
+    )]
+    pub refresh: bool,
+    #[arg(
+        long,
+        global = true,
+        default_value = "10m",
+        value_parser = humantime::parse_duration,
+        help = "How long lists of installed packages are cached between runs, e.g. 30s or 1h; 0 disables the cache"
+    )]
+    pub cache_ttl: Duration,
 }
 
 #[derive(Subcommand)]
@@ -16,6 +31,18 @@ pub enum Commands {
     Run(RunArgs),
     #[command(about = "Resume failed run", long_about = None)]
     Resume(ResumeArgs),
+    #[command(
+        about = "Undo steps of the last run",
+        long_about = "Undo steps of the last run, or selected steps, in reverse dependency order.\n\
+        Each step runs its undo-script and then removes its installed packages."
+    )]
+    Rollback(RollbackArgs),
+    #[command(
+        about = "Upgrade packages declared by steps",
+        long_about = "Upgrade packages declared by the selected steps to their latest versions.\n\
+        Only installed packages are upgraded, the rest of the system is left untouched."
+    )]
+    Upgrade(UpgradeArgs),
     #[command(
         about = "Validate configuration and script syntax",
         long_about = "Validate configuration and script syntax.\n\
@@ -28,6 +55,8 @@ pub enum Commands {
     ListTags(ListTagsArgs),
     #[command(about = "Generate shell completion scripts")]
     Completion(CompletionArgs),
+    #[command(about = "Print JSON Schema for the configuration file")]
+    Schema(SchemaArgs),
 }
 
 #[derive(Args, Default)]
@@ -81,6 +110,24 @@ pub struct RunArgs {
         help = "Enable debug output (shows script execution time, exit codes, etc.)"
     )]
     pub debug: bool,
+    #[arg(
+        long,
+        help = "Continue with independent steps when a step fails, skipping its dependents"
+    )]
+    pub keep_going: bool,
+    #[arg(long, help = "Install the package versions recorded in mepris.lock")]
+    pub locked: bool,
+    #[arg(
+        long,
+        help = "Install packages of steps using the same package manager in one transaction",
+        conflicts_with = "interactive"
+    )]
+    pub batch_packages: bool,
+    #[arg(
+        long,
+        help = "Don't refresh package indexes before installing packages"
+    )]
+    pub no_refresh: bool,
 }
 
 #[derive(Args)]
@@ -108,6 +155,67 @@ pub struct ResumeArgs {
         help = "Enable debug output (shows script execution time, exit codes, etc.)"
     )]
     pub debug: bool,
+    #[arg(
+        long,
+        help = "Continue with independent steps when a step fails, skipping its dependents"
+    )]
+    pub keep_going: bool,
+}
+
+#[derive(Args)]
+pub struct RollbackArgs {
+    #[arg(
+        short,
+        long,
+        help = "Path to configuration YAML file (defaults to the file of the last run)"
+    )]
+    pub file: Option<String>,
+    #[arg(
+        short,
+        long = "step",
+        help = "Undo specific steps by their IDs instead of the steps of the last run"
+    )]
+    pub steps: Vec<String>,
+    #[arg(
+        short,
+        long,
+        help = "Enable dry-run mode (no scripts or packages executed)"
+    )]
+    pub dry_run: bool,
+    #[arg(
+        long,
+        help = "Enable debug output (shows script execution time, exit codes, etc.)"
+    )]
+    pub debug: bool,
+}
+
+#[derive(Args, Default)]
+pub struct UpgradeArgs {
+    #[arg(short, long, required = true, help = "Path to configuration YAML file")]
+    pub file: String,
+    #[arg(
+        short,
+        long = "tag",
+        help = "Filter steps by tags expression, e.g. !(tag1 || tag2) && tag3"
+    )]
+    pub tags_expr: Option<String>,
+    #[arg(
+        short,
+        long = "step",
+        help = "Upgrade packages of specific steps by their IDs"
+    )]
+    pub steps: Vec<String>,
+    #[arg(
+        short,
+        long,
+        help = "Enable dry-run mode (show packages that would be upgraded)"
+    )]
+    pub dry_run: bool,
+    #[arg(
+        long,
+        help = "Enable debug output (shows script execution time, exit codes, etc.)"
+    )]
+    pub debug: bool,
 }
 
 #[derive(Args)]
@@ -142,6 +250,12 @@ pub struct CompletionArgs {
     pub shell: Shell,
 }
 
+#[derive(Args)]
+pub struct SchemaArgs {
+    #[arg(long, help = "Print schema for pkg_aliases.yaml instead")]
+    pub aliases: bool,
+}
+
 #[derive(Debug, Clone, clap::ValueEnum)]
 pub enum Shell {
     Bash,
@@ -180,3 +294,13 @@ normalize_file!(RunArgs);
 normalize_file!(ValidateArgs);
 normalize_file!(ListStepsArgs);
 normalize_file!(ListTagsArgs);
+normalize_file!(UpgradeArgs);
+
+impl Normalize for RollbackArgs {
+    fn normalize(self) -> anyhow::Result<Self> {
+        Ok(Self {
+            file: self.file.as_deref().map(normalize_path).transpose()?,
+            ..self
+        })
+    }
+}
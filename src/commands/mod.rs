@@ -2,6 +2,9 @@ pub mod completion;
 pub mod list_steps;
 pub mod list_tags;
 pub mod resume;
+pub mod rollback;
 pub mod run;
+pub mod schema;
+pub mod upgrade;
 mod utils;
 pub mod validate;
@@ -21,17 +21,22 @@ pub fn handle(args: ResumeArgs, out: &mut impl Write) -> Result<()> {
 
     let interactive = args.interactive || state.interactive;
 
-    run::handle(
+    run::handle_resumed(
         RunArgs {
-            file: state.file,
-            tags_expr: state.tags_expr,
-            steps: state.steps,
-            start_step_id: state.last_step_id,
+            file: state.file.clone(),
+            tags_expr: state.tags_expr.clone(),
+            steps: state.steps.clone(),
+            start_step_id: state.last_step_id.clone(),
             interactive,
             dry_run: args.dry_run,
             show_skipped: args.show_skipped,
             debug: args.debug,
+            keep_going: args.keep_going,
+            locked: state.locked,
+            batch_packages: state.batch_packages && !interactive,
+            no_refresh: state.no_refresh,
         },
+        &state,
         out,
     )
 }
@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use colored::Colorize;
+use tracing::warn;
+
+use super::utils::filters::{FilterConfig, filter_steps};
+use super::utils::sort::toposort_steps;
+use super::utils::{RunInfo, check_env, load_env};
+use crate::cli::RollbackArgs;
+use crate::config::{StepSelectionReason, ValidationMode};
+use crate::runner::rollback::{self, StepUndo};
+use crate::runner::script_checker::DefaultScriptChecker;
+use crate::runner::state;
+use crate::system::os_info::OS_INFO;
+use crate::{config, utils};
+
+pub fn handle(args: RollbackArgs, out: &mut impl Write) -> Result<()> {
+    let last_run: Option<RunInfo> = state::get().ok();
+
+    let file = match (&args.file, &last_run) {
+        (Some(file), _) => utils::file::get_absolute_path(file, None)?
+            .to_str()
+            .unwrap()
+            .to_string(),
+        (None, Some(last_run)) => last_run.file.clone(),
+        (None, None) => {
+            writeln!(
+                out,
+                "Nothing to roll back. Did you use the run command first?"
+            )?;
+            return Ok(());
+        }
+    };
+
+    let step_ids = if !args.steps.is_empty() {
+        args.steps.clone()
+    } else {
+        match &last_run {
+            Some(last_run) if last_run.file == file => last_run.executed_steps.clone(),
+            Some(_) => {
+                bail!("The last run used another file, select steps to roll back with --step")
+            }
+            None => {
+                writeln!(
+                    out,
+                    "Nothing to roll back. Did you use the run command first?"
+                )?;
+                return Ok(());
+            }
+        }
+    };
+    if step_ids.is_empty() {
+        writeln!(
+            out,
+            "Nothing to roll back: no steps of the last run left to undo"
+        )?;
+        return Ok(());
+    }
+
+    let steps = config::load_steps(&file, ValidationMode::Full)?;
+    let filter_result = filter_steps(
+        &steps,
+        &FilterConfig::new()
+            .apply_os(OS_INFO.clone())
+            .apply_ids(step_ids),
+    )?;
+
+    load_env(&file)?;
+    check_env(&filter_result.filtered_steps)?;
+
+    // dependencies are only used for ordering: steps are undone before the steps they require
+    let mut steps = toposort_steps(&filter_result, &OS_INFO)?
+        .into_iter()
+        .filter(|s| s.selection_reason == Some(StepSelectionReason::MatchedFilter))
+        .collect::<Vec<config::Step>>();
+    steps.reverse();
+
+    let installed_packages = match &last_run {
+        Some(last_run) if last_run.file == file => last_run.installed_packages.clone(),
+        _ => BTreeMap::new(),
+    };
+    let mut undone = Vec::new();
+    let result = rollback::rollback(
+        &steps,
+        Path::new(&file),
+        &installed_packages,
+        args.dry_run,
+        &mut undone,
+        &mut DefaultScriptChecker::new(),
+        out,
+    );
+
+    // steps undone before a failure must not be undone again by the next rollback
+    if let Some(mut last_run) = last_run
+        && last_run.file == file
+        && !undone.is_empty()
+    {
+        let undone = undone.iter().collect::<HashSet<&String>>();
+        last_run.executed_steps.retain(|id| !undone.contains(id));
+        last_run
+            .installed_packages
+            .retain(|id, _| !undone.contains(id));
+        if state::save(&last_run).is_err() {
+            warn!("failed to save run state");
+        }
+    }
+
+    if let Some(plan) = result? {
+        return print_plan(&plan, out);
+    }
+    Ok(())
+}
+
+fn print_plan(plan: &[StepUndo], out: &mut impl Write) -> Result<()> {
+    let mut previous_source_file = "";
+
+    for undo in plan {
+        let cur_source_file = Path::new(&undo.source_file)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap();
+        if cur_source_file != previous_source_file {
+            if !previous_source_file.is_empty() {
+                writeln!(out)?;
+            }
+            writeln!(out, "From {}:", cur_source_file)?;
+            previous_source_file = cur_source_file;
+        }
+
+        if undo.is_empty() {
+            writeln!(out, "  ➖ Step {}: nothing to undo", undo.id.cyan())?;
+            continue;
+        }
+
+        writeln!(out, "  ↩️ Would undo step {}", undo.id.cyan())?;
+        if undo.has_undo_script {
+            writeln!(out, "  ⚙️ {} undo-script", "Would run".yellow())?;
+        }
+        if !undo.packages_to_remove.is_empty() {
+            writeln!(
+                out,
+                "  🗑️ {} {} ({})",
+                "Would remove packages".yellow(),
+                undo.packages_to_remove.join(", "),
+                undo.package_manager
+            )?;
+        }
+    }
+
+    if plan.iter().all(StepUndo::is_empty) {
+        writeln!(out, "❌ No steps would be undone")?;
+    }
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use super::utils::{RunStateSaver, check_env, load_env};
+use super::utils::{RunInfo, RunStateSaver, check_env, load_env};
 use crate::commands::utils::filters::StepFilter::{ByIds, ByOs, ByStartId, ByTags, ByWhenScript};
 use crate::commands::utils::filters::{
     ExcludedStep, ExcludedStepVecExt, FilterConfig, StepFilter, filter_steps,
@@ -6,8 +6,9 @@ use crate::commands::utils::filters::{
 use crate::commands::utils::sort::toposort_steps;
 use crate::config::{StepSelectionReason, ValidationMode};
 use crate::runner::dry::StepRun;
+use crate::runner::lock::{self, Lock};
 use crate::runner::script_checker::DefaultScriptChecker;
-use crate::runner::{CliInteractor, Interactor, StepCompletedResult};
+use crate::runner::{CliInteractor, Interactor, RunOutcome, StepCompletedResult};
 use crate::{
     cli::RunArgs,
     config,
@@ -18,10 +19,10 @@ use crate::{
 };
 use anyhow::{Result, bail};
 use colored::Colorize;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::io::{BufReader, Write, stdin};
 use std::path::Path;
-use tracing::info;
+use tracing::{info, warn};
 
 impl From<&RunArgs> for FilterConfig {
     fn from(args: &RunArgs) -> Self {
@@ -40,6 +41,15 @@ impl From<&RunArgs> for FilterConfig {
 }
 
 pub fn handle(args: RunArgs, out: &mut impl Write) -> Result<()> {
+    run(args, None, out)
+}
+
+/// Continues the run saved in `previous`, its executed steps and installed packages are kept for rollback.
+pub fn handle_resumed(args: RunArgs, previous: &RunInfo, out: &mut impl Write) -> Result<()> {
+    run(args, Some(previous), out)
+}
+
+fn run(args: RunArgs, previous: Option<&RunInfo>, out: &mut impl Write) -> Result<()> {
     let state_saver = RunStateSaver {
         file: utils::file::get_absolute_path(&args.file, None)?
             .to_str()
@@ -47,6 +57,11 @@ pub fn handle(args: RunArgs, out: &mut impl Write) -> Result<()> {
             .to_string(),
         tags_expr: args.tags_expr.clone(),
         steps: args.steps.clone(),
+        locked: args.locked,
+        batch_packages: args.batch_packages,
+        no_refresh: args.no_refresh,
+        executed_steps: previous.map_or(Vec::new(), |p| p.executed_steps.clone()),
+        installed_packages: previous.map_or(BTreeMap::new(), |p| p.installed_packages.clone()),
     };
     let mut script_checker = DefaultScriptChecker::new();
     let interactor: Option<&mut dyn Interactor> = if args.interactive {
@@ -70,14 +85,22 @@ pub fn handle(args: RunArgs, out: &mut impl Write) -> Result<()> {
     load_env(&args.file)?;
     check_env(&filter_result.filtered_steps)?;
 
-    let steps = toposort_steps(&filter_result, &OS_INFO)?;
+    let mut steps = toposort_steps(&filter_result, &OS_INFO)?;
 
     let params = runner::RunParameters {
         source_file_path: state_saver.file.clone().into(),
         dry_run: args.dry_run,
+        keep_going: args.keep_going,
+        batch_packages: args.batch_packages,
+        refresh_index: !args.no_refresh,
     };
 
-    let dry_run_plan = runner::run(
+    if args.locked {
+        let lock = Lock::load(&lock::lock_path(&params.source_file_path))?;
+        lock::apply_lock(&mut steps, &lock, &params.source_file_path)?;
+    }
+
+    let outcome = runner::run(
         &steps[..],
         &params,
         &state_saver,
@@ -86,21 +109,32 @@ pub fn handle(args: RunArgs, out: &mut impl Write) -> Result<()> {
         out,
     )?;
 
-    if args.dry_run
-        && let Some(dry_run_plan) = dry_run_plan
-    {
-        // excluded steps may be pulled in as dependencies after toposort and should not be shown as skipped
-        let steps_to_run_ids = steps
-            .iter()
-            .map(|s| s.id.clone())
-            .collect::<HashSet<String>>();
-        let excluded_steps = filter_result
-            .excluded_steps()
-            .iter()
-            .filter(|s| !steps_to_run_ids.contains(&s.step.id))
-            .cloned()
-            .collect::<Vec<&ExcludedStep>>();
-        print_info(&excluded_steps, &dry_run_plan, args.show_skipped, out)?;
+    match outcome {
+        RunOutcome::Planned(dry_run_plan) => {
+            // excluded steps may be pulled in as dependencies after toposort and should not be shown as skipped
+            let steps_to_run_ids = steps
+                .iter()
+                .map(|s| s.id.clone())
+                .collect::<HashSet<String>>();
+            let excluded_steps = filter_result
+                .excluded_steps()
+                .iter()
+                .filter(|s| !steps_to_run_ids.contains(&s.step.id))
+                .cloned()
+                .collect::<Vec<&ExcludedStep>>();
+            print_info(&excluded_steps, &dry_run_plan, args.show_skipped, out)?;
+        }
+        RunOutcome::Completed(completed_steps) => {
+            // skipped steps keep their locked versions
+            let completed_steps = steps
+                .into_iter()
+                .filter(|s| completed_steps.contains(&s.id))
+                .collect::<Vec<config::Step>>();
+            if let Err(err) = lock::update_lock(&completed_steps, &params.source_file_path) {
+                warn!("failed to update lock file: {err:#}");
+            }
+        }
+        RunOutcome::Aborted => {}
     }
     Ok(())
 }
@@ -184,7 +218,15 @@ fn print_info(
             step_extra_info,
         )?;
 
+        print_repositories_info(step, out)?;
+        print_refresh_info(step, out)?;
         print_packages_info(step, out)?;
+        print_absent_packages_info(step, out)?;
+        print_downloads_info(step, out)?;
+        print_git_info(step, out)?;
+        print_links_info(step, out)?;
+        print_files_info(step, out)?;
+        print_edits_info(step, out)?;
         print_shells_info(step, out)?;
     }
 
@@ -199,6 +241,30 @@ fn print_info(
     Ok(())
 }
 
+fn print_repositories_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
+    for repo in step.repositories.iter().filter(|r| !r.added) {
+        writeln!(
+            out,
+            "  📚 {} {}",
+            "Would add repository".yellow(),
+            repo.name
+        )?;
+    }
+    Ok(())
+}
+
+fn print_refresh_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
+    if let Some(manager) = step.package_manager.as_ref().filter(|_| step.refresh_index) {
+        writeln!(
+            out,
+            "  🔄 {} ({})",
+            "Would refresh package index".yellow(),
+            manager.name
+        )?;
+    }
+    Ok(())
+}
+
 fn print_packages_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
     if step.packages_to_install.is_empty() {
         return Ok(());
@@ -250,6 +316,98 @@ fn print_packages_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
     Ok(())
 }
 
+fn print_absent_packages_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
+    let installed_packages = step
+        .packages_to_remove
+        .iter()
+        .filter(|p| p.installed)
+        .map(|p| p.to_string())
+        .collect::<Vec<String>>();
+    if installed_packages.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "  🗑️ {} {} ({})",
+        "Would remove packages".yellow(),
+        installed_packages.join(", "),
+        step.package_manager.as_ref().unwrap().name
+    )?;
+    Ok(())
+}
+
+fn print_downloads_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
+    for download in step.downloads.iter().filter(|d| !d.downloaded) {
+        writeln!(
+            out,
+            "  📥 {} {} -> {}",
+            "Would download".yellow(),
+            download.url,
+            download.dest
+        )?;
+    }
+    Ok(())
+}
+
+fn print_git_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
+    for repo in step.git.iter().filter(|r| !r.synced) {
+        let rev = repo
+            .rev
+            .as_ref()
+            .map(|rev| format!(" ({rev})"))
+            .unwrap_or_default();
+        if repo.cloned {
+            writeln!(out, "  🌿 {} {}{rev}", "Would update".yellow(), repo.dest)?;
+        } else {
+            writeln!(
+                out,
+                "  🌿 {} {} -> {}{rev}",
+                "Would clone".yellow(),
+                repo.repo,
+                repo.dest
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn print_links_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
+    for link in step.links.iter().filter(|l| !l.created) {
+        writeln!(
+            out,
+            "  🔗 {} {} -> {}",
+            "Would link".yellow(),
+            link.dest,
+            link.src
+        )?;
+    }
+    Ok(())
+}
+
+fn print_files_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
+    for file in step.files.iter().filter(|f| !f.rendered) {
+        writeln!(out, "  📝 {} {}", "Would render".yellow(), file.dest)?;
+        for line in file.diff.lines() {
+            writeln!(out, "    {line}")?;
+        }
+    }
+    Ok(())
+}
+
+fn print_edits_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
+    for edit in step.edits.iter().filter(|e| !e.applied) {
+        writeln!(
+            out,
+            "  ✏️ {} {}: {}",
+            "Would edit".yellow(),
+            edit.path,
+            edit.description
+        )?;
+    }
+    Ok(())
+}
+
 fn print_shells_info(step: &StepRun, out: &mut impl Write) -> Result<()> {
     if !step.missing_shells.is_empty() {
         let shells = step.missing_shells.join(", ");
@@ -360,7 +518,10 @@ mod tests {
     use crate::config::Script;
     use crate::config::expr::Expr;
     use crate::runner::StepCompletedResult;
-    use crate::runner::dry::{PackageInfo, PackageManagerInfo, RunPlan, StepRun};
+    use crate::runner::dry::{
+        DownloadInfo, FileEditInfo, FileInfo, GitRepoInfo, LinkInfo, PackageInfo,
+        PackageManagerInfo, RunPlan, StepRun,
+    };
     use crate::system::os_info::{OsInfo, Platform};
     use std::io::Cursor;
 
@@ -489,6 +650,7 @@ mod tests {
                 when_script: Some(Script {
                     shell: None,
                     code: "exit 1".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/test.yaml".to_string(),
                 ..Default::default()
@@ -498,6 +660,7 @@ mod tests {
                 when_script: Some(Script {
                     shell: None,
                     code: "exit 1".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/test.yaml".to_string(),
                 ..Default::default()
@@ -655,6 +818,7 @@ mod tests {
                 when_script: Some(Script {
                     shell: None,
                     code: "exit 1".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/test.yaml".to_string(),
                 ..Default::default()
@@ -731,6 +895,7 @@ mod tests {
                 when_script: Some(Script {
                     shell: None,
                     code: "exit 1".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/test.yaml".to_string(),
                 ..Default::default()
@@ -1082,6 +1247,225 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_print_info_with_pending_links() {
+        let mut output = Cursor::new(Vec::new());
+        let excluded_steps_ref: Vec<&ExcludedStep> = vec![];
+        let mut step_run = create_step_run(
+            "dotfiles",
+            StepCompletedResult::PendingLinks(vec!["/home/user/.bashrc".to_string()]),
+            StepSelectionReason::MatchedFilter,
+        );
+        step_run.links = vec![
+            LinkInfo {
+                src: "/dotfiles/vimrc".to_string(),
+                dest: "/home/user/.vimrc".to_string(),
+                created: true,
+            },
+            LinkInfo {
+                src: "/dotfiles/bashrc".to_string(),
+                dest: "/home/user/.bashrc".to_string(),
+                created: false,
+            },
+        ];
+        let dry_run_plan = RunPlan {
+            steps_to_run: vec![step_run],
+        };
+
+        print_info(&excluded_steps_ref, &dry_run_plan, true, &mut output).unwrap();
+
+        let clean = strip_ansi_escapes::strip(output.into_inner());
+        let output_str = String::from_utf8(clean).unwrap();
+        assert!(
+            output_str.contains("Would link /home/user/.bashrc -> /dotfiles/bashrc"),
+            "unexpected output: \n{}",
+            output_str
+        );
+        assert!(
+            !output_str.contains(".vimrc"),
+            "unexpected output: \n{}",
+            output_str
+        );
+    }
+
+    #[test]
+    fn test_print_info_with_installed_absent_packages() {
+        let mut output = Cursor::new(Vec::new());
+        let excluded_steps_ref: Vec<&ExcludedStep> = vec![];
+        let mut step_run = create_step_run(
+            "editors",
+            StepCompletedResult::InstalledAbsentPackages(vec!["nano".to_string()]),
+            StepSelectionReason::MatchedFilter,
+        );
+        step_run.package_manager = Some(PackageManagerInfo {
+            name: "apt".to_string(),
+            installed: true,
+        });
+        step_run.packages_to_remove = vec![
+            PackageInfo {
+                name: "nano".to_string(),
+                use_alias: false,
+                installed: true,
+            },
+            PackageInfo {
+                name: "firefox".to_string(),
+                use_alias: false,
+                installed: false,
+            },
+        ];
+        let dry_run_plan = RunPlan {
+            steps_to_run: vec![step_run],
+        };
+
+        print_info(&excluded_steps_ref, &dry_run_plan, true, &mut output).unwrap();
+
+        let clean = strip_ansi_escapes::strip(output.into_inner());
+        let output_str = String::from_utf8(clean).unwrap();
+        assert!(
+            output_str.contains("Would remove packages nano (apt)"),
+            "unexpected output: \n{}",
+            output_str
+        );
+        assert!(!output_str.contains("firefox"), "{output_str}");
+    }
+
+    #[test]
+    fn test_print_info_with_pending_downloads() {
+        let mut output = Cursor::new(Vec::new());
+        let excluded_steps_ref: Vec<&ExcludedStep> = vec![];
+        let mut step_run = create_step_run(
+            "ripgrep",
+            StepCompletedResult::PendingDownloads(vec!["/opt/rg".to_string()]),
+            StepSelectionReason::MatchedFilter,
+        );
+        step_run.downloads = vec![DownloadInfo {
+            url: "https://example.com/rg.tar.gz".to_string(),
+            dest: "/opt/rg".to_string(),
+            downloaded: false,
+        }];
+        let dry_run_plan = RunPlan {
+            steps_to_run: vec![step_run],
+        };
+
+        print_info(&excluded_steps_ref, &dry_run_plan, true, &mut output).unwrap();
+
+        let clean = strip_ansi_escapes::strip(output.into_inner());
+        let output_str = String::from_utf8(clean).unwrap();
+        assert!(
+            output_str.contains("Would download https://example.com/rg.tar.gz -> /opt/rg"),
+            "unexpected output: \n{}",
+            output_str
+        );
+    }
+
+    #[test]
+    fn test_print_info_with_pending_git_repos() {
+        let mut output = Cursor::new(Vec::new());
+        let excluded_steps_ref: Vec<&ExcludedStep> = vec![];
+        let mut step_run = create_step_run(
+            "plugins",
+            StepCompletedResult::PendingGitRepos(vec!["/home/user/.tmux/plugins/tpm".to_string()]),
+            StepSelectionReason::MatchedFilter,
+        );
+        step_run.git = vec![
+            GitRepoInfo {
+                repo: "https://github.com/tmux-plugins/tpm".to_string(),
+                dest: "/home/user/.tmux/plugins/tpm".to_string(),
+                rev: Some("v3.1.0".to_string()),
+                cloned: false,
+                synced: false,
+            },
+            GitRepoInfo {
+                repo: "https://example.com/dotfiles.git".to_string(),
+                dest: "/home/user/dotfiles".to_string(),
+                rev: None,
+                cloned: true,
+                synced: false,
+            },
+        ];
+        let dry_run_plan = RunPlan {
+            steps_to_run: vec![step_run],
+        };
+
+        print_info(&excluded_steps_ref, &dry_run_plan, true, &mut output).unwrap();
+
+        let clean = strip_ansi_escapes::strip(output.into_inner());
+        let output_str = String::from_utf8(clean).unwrap();
+        assert!(
+            output_str.contains(
+                "Would clone https://github.com/tmux-plugins/tpm -> /home/user/.tmux/plugins/tpm (v3.1.0)"
+            ),
+            "unexpected output: \n{}",
+            output_str
+        );
+        assert!(
+            output_str.contains("Would update /home/user/dotfiles\n"),
+            "unexpected output: \n{}",
+            output_str
+        );
+    }
+
+    #[test]
+    fn test_print_info_with_pending_edits() {
+        let mut output = Cursor::new(Vec::new());
+        let excluded_steps_ref: Vec<&ExcludedStep> = vec![];
+        let mut step_run = create_step_run(
+            "path",
+            StepCompletedResult::PendingFileEdits(vec!["/home/user/.bashrc".to_string()]),
+            StepSelectionReason::MatchedFilter,
+        );
+        step_run.edits = vec![FileEditInfo {
+            path: "/home/user/.bashrc".to_string(),
+            description: "ensure line 'export PATH=$HOME/bin:$PATH'".to_string(),
+            applied: false,
+        }];
+        let dry_run_plan = RunPlan {
+            steps_to_run: vec![step_run],
+        };
+
+        print_info(&excluded_steps_ref, &dry_run_plan, true, &mut output).unwrap();
+
+        let clean = strip_ansi_escapes::strip(output.into_inner());
+        let output_str = String::from_utf8(clean).unwrap();
+        assert!(
+            output_str.contains(
+                "Would edit /home/user/.bashrc: ensure line 'export PATH=$HOME/bin:$PATH'"
+            ),
+            "unexpected output: \n{}",
+            output_str
+        );
+    }
+
+    #[test]
+    fn test_print_info_with_pending_files() {
+        let mut output = Cursor::new(Vec::new());
+        let excluded_steps_ref: Vec<&ExcludedStep> = vec![];
+        let mut step_run = create_step_run(
+            "gitconfig",
+            StepCompletedResult::PendingFiles(vec!["/home/user/.gitconfig".to_string()]),
+            StepSelectionReason::MatchedFilter,
+        );
+        step_run.files = vec![FileInfo {
+            dest: "/home/user/.gitconfig".to_string(),
+            rendered: false,
+            diff: "-name = bob\n+name = alice\n".to_string(),
+        }];
+        let dry_run_plan = RunPlan {
+            steps_to_run: vec![step_run],
+        };
+
+        print_info(&excluded_steps_ref, &dry_run_plan, true, &mut output).unwrap();
+
+        let clean = strip_ansi_escapes::strip(output.into_inner());
+        let output_str = String::from_utf8(clean).unwrap();
+        assert!(
+            output_str
+                .contains("Would render /home/user/.gitconfig\n    -name = bob\n    +name = alice"),
+            "unexpected output: \n{}",
+            output_str
+        );
+    }
+
     #[test]
     fn test_print_info_with_missing_shells() {
         let mut output = Cursor::new(Vec::new());
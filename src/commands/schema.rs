@@ -0,0 +1,96 @@
+use std::io::Write;
+
+use anyhow::Result;
+use schemars::schema_for;
+
+use crate::cli::SchemaArgs;
+use crate::config::Config;
+use crate::config::aliases::PackageAliases;
+
+pub fn handle(args: SchemaArgs, out: &mut impl Write) -> Result<()> {
+    let schema = if args.aliases {
+        schema_for!(PackageAliases)
+    } else {
+        schema_for!(Config)
+    };
+    writeln!(out, "{}", serde_json::to_string_pretty(&schema)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::pkg::PackageManager;
+    use crate::system::shell::Shell;
+    use serde_json::Value;
+    use strum::IntoEnumIterator;
+
+    fn generate(aliases: bool) -> Value {
+        let mut out = Vec::new();
+        handle(SchemaArgs { aliases }, &mut out).unwrap();
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    fn enum_values(schema: &Value, name: &str) -> Vec<String> {
+        let mut values: Vec<String> = schema["$defs"][name]["enum"]
+            .as_array()
+            .unwrap_or_else(|| panic!("no enum values for {name}"))
+            .iter()
+            .map(|v| v.as_str().unwrap().to_string())
+            .collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn test_config_schema() {
+        let schema = generate(false);
+
+        let config_props = schema["properties"].as_object().unwrap();
+        for field in ["includes", "defaults", "vars", "templates", "steps"] {
+            assert!(
+                config_props.contains_key(field),
+                "missing config field {field}"
+            );
+        }
+
+        let step_props = schema["$defs"]["Step"]["properties"].as_object().unwrap();
+        assert!(!step_props.contains_key("source_file"));
+        assert!(!step_props.contains_key("dependencies"));
+
+        let script = serde_json::to_string(&schema["$defs"]["Script"]).unwrap();
+        assert!(script.contains("\"run\""));
+    }
+
+    #[test]
+    fn test_config_schema_snapshot() {
+        let snapshot: Value =
+            serde_json::from_str(include_str!("../../tests/fixtures/schema.json")).unwrap();
+        assert!(
+            generate(false) == snapshot,
+            "schema changed, update it with `cargo run -- schema > tests/fixtures/schema.json`"
+        );
+    }
+
+    #[test]
+    fn test_config_schema_enums() {
+        let schema = generate(false);
+
+        let mut managers: Vec<String> = PackageManager::iter()
+            .map(|pm| pm.to_string().to_lowercase())
+            .collect();
+        managers.sort();
+        assert_eq!(enum_values(&schema, "PackageManager"), managers);
+
+        let mut shells: Vec<String> = Shell::iter().map(|s| s.get_command().to_string()).collect();
+        shells.sort();
+        assert_eq!(enum_values(&schema, "Shell"), shells);
+    }
+
+    #[test]
+    fn test_aliases_schema() {
+        let schema = generate(true);
+        assert_eq!(schema["type"], "object");
+        assert!(schema["additionalProperties"].is_object());
+    }
+}
@@ -0,0 +1,90 @@
+use std::io::Write;
+
+use anyhow::{Result, bail};
+use colored::Colorize;
+use tracing::info;
+
+use super::utils::filters::{FilterConfig, filter_steps};
+use super::utils::{check_env, load_env};
+use crate::cli::UpgradeArgs;
+use crate::config::{Step, ValidationMode};
+use crate::runner::upgrade::{self, PackageUpgrade};
+use crate::system::os_info::OS_INFO;
+use crate::{config, utils};
+
+impl From<&UpgradeArgs> for FilterConfig {
+    fn from(args: &UpgradeArgs) -> Self {
+        let mut conf = FilterConfig::new()
+            .apply_when()
+            .apply_os(OS_INFO.clone())
+            .apply_ids(args.steps.clone());
+        if let Some(tags_expr) = args.tags_expr.as_ref() {
+            conf = conf.apply_tags(tags_expr.clone());
+        }
+        conf
+    }
+}
+
+pub fn handle(args: UpgradeArgs, out: &mut impl Write) -> Result<()> {
+    let steps = config::load_steps(&args.file, ValidationMode::Full)?;
+    if steps.is_empty() {
+        bail!("The file doesn't contain any steps");
+    }
+
+    let filter_result = filter_steps(&steps, &FilterConfig::from(&args))?;
+
+    load_env(&args.file)?;
+    check_env(&filter_result.filtered_steps)?;
+
+    let steps = filter_result
+        .filtered_steps
+        .iter()
+        .copied()
+        .cloned()
+        .collect::<Vec<Step>>();
+    let plan = upgrade::plan_upgrade(&steps, &utils::file::get_absolute_path(&args.file, None)?)?;
+
+    if args.dry_run {
+        return print_plan(&plan, out);
+    }
+    if plan.iter().all(|u| u.installed.is_empty()) {
+        info!("ℹ️ No installed packages to upgrade");
+        return Ok(());
+    }
+    upgrade::upgrade(&plan)
+}
+
+fn print_plan(plan: &[PackageUpgrade], out: &mut impl Write) -> Result<()> {
+    for upgrade in plan {
+        if !upgrade.installed.is_empty() {
+            writeln!(
+                out,
+                "⬆️ {} {} ({})",
+                "Would upgrade packages".yellow(),
+                upgrade.installed.join(", "),
+                upgrade.manager
+            )?;
+        }
+        if !upgrade.not_installed.is_empty() {
+            writeln!(
+                out,
+                "➖ Not installed, skipped: {} ({})",
+                upgrade.not_installed.join(", "),
+                upgrade.manager
+            )?;
+        }
+        if !upgrade.pinned.is_empty() {
+            writeln!(
+                out,
+                "📌 Pinned, skipped: {} ({})",
+                upgrade.pinned.join(", "),
+                upgrade.manager
+            )?;
+        }
+    }
+
+    if plan.iter().all(|u| u.installed.is_empty()) {
+        writeln!(out, "❌ No packages would be upgraded")?;
+    }
+    Ok(())
+}
@@ -11,7 +11,7 @@ use crate::system::os_info::OsInfo;
 use anyhow::{Context, bail};
 use std::collections::HashMap;
 use std::path::Path;
-use tracing::{debug, debug_span};
+use tracing::{debug, debug_span, warn};
 
 #[derive(Default)]
 pub struct FilterConfig {
@@ -255,6 +255,14 @@ pub fn filter_by_when_script<'a>(steps: &[&'a Step]) -> anyhow::Result<FilterRes
         match run.status {
             ScriptStatus::Success => matching.push(*s),
             ScriptStatus::Failed(_) => not_matching.push(*s),
+            ScriptStatus::TimedOut(timeout) => {
+                warn!(
+                    "when-script of step '{}' timed out after {}",
+                    s.id,
+                    humantime::format_duration(timeout)
+                );
+                not_matching.push(*s)
+            }
         }
     }
 
@@ -591,6 +599,7 @@ mod tests {
                 when_script: Some(Script {
                     shell: None,
                     code: format!("echo step1 >> {}", log_file.display()),
+                    ..Default::default()
                 }),
                 source_file: "/file.yaml".to_string(),
                 provides: vec!["p1".into()],
@@ -602,6 +611,7 @@ mod tests {
                 when_script: Some(Script {
                     shell: None,
                     code: format!("echo step2 >> {}", log_file.display()),
+                    ..Default::default()
                 }),
                 source_file: "/file.yaml".to_string(),
                 provides: vec!["p2".into()],
@@ -612,6 +622,7 @@ mod tests {
                 when_script: Some(Script {
                     shell: None,
                     code: format!("echo step3 >> {}", log_file.display()),
+                    ..Default::default()
                 }),
                 source_file: "/file.yaml".to_string(),
                 provides: vec!["dep".into()],
@@ -622,6 +633,7 @@ mod tests {
                 when_script: Some(Script {
                     shell: None,
                     code: format!("echo step4 >> {}", log_file.display()),
+                    ..Default::default()
                 }),
                 source_file: "/file.yaml".to_string(),
                 ..Default::default()
@@ -632,6 +644,7 @@ mod tests {
                 when_script: Some(Script {
                     shell: None,
                     code: format!("echo step5 >> {}", log_file.display()),
+                    ..Default::default()
                 }),
                 source_file: "/file.yaml".to_string(),
                 provides: vec!["dep2".into()],
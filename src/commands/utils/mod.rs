@@ -8,7 +8,7 @@ use crate::{
 };
 use anyhow::{Context, Result, bail};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     path::Path,
 };
 
@@ -16,16 +16,44 @@ pub struct RunStateSaver {
     pub file: String,
     pub tags_expr: Option<String>,
     pub steps: Vec<String>,
+    pub locked: bool,
+    pub batch_packages: bool,
+    pub no_refresh: bool,
+    /// Steps executed by the run that is resumed, kept for rollback.
+    pub executed_steps: Vec<String>,
+    /// Packages installed by the run that is resumed, by step id.
+    pub installed_packages: BTreeMap<String, Vec<String>>,
 }
 
 impl runner::StateSaver for RunStateSaver {
     fn save(&self, state: &RunState) -> anyhow::Result<()> {
+        let mut executed_steps = self.executed_steps.clone();
+        for id in &state.executed_steps {
+            if !executed_steps.contains(id) {
+                executed_steps.push(id.clone());
+            }
+        }
+        let mut installed_packages = self.installed_packages.clone();
+        for (id, packages) in &state.installed_packages {
+            let recorded = installed_packages.entry(id.clone()).or_default();
+            for pkg in packages {
+                if !recorded.contains(pkg) {
+                    recorded.push(pkg.clone());
+                }
+            }
+        }
+
         state::save(&RunInfo {
             file: self.file.clone(),
             tags_expr: self.tags_expr.clone(),
             steps: self.steps.clone(),
             interactive: state.interactive,
             last_step_id: state.last_step_id.clone(),
+            executed_steps,
+            installed_packages,
+            locked: self.locked,
+            batch_packages: self.batch_packages,
+            no_refresh: self.no_refresh,
         })
     }
 }
@@ -37,6 +65,17 @@ pub struct RunInfo {
     pub steps: Vec<String>,
     pub interactive: bool,
     pub last_step_id: Option<String>,
+    #[serde(default)]
+    pub executed_steps: Vec<String>,
+    /// Packages installed by mepris, by step id. Rollback only removes these.
+    #[serde(default)]
+    pub installed_packages: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub batch_packages: bool,
+    #[serde(default)]
+    pub no_refresh: bool,
 }
 
 pub fn load_env(config_file_path: &str) -> Result<()> {
@@ -203,7 +203,7 @@ fn filter_requires(steps: &mut [Step], os_info: &OsInfo) -> anyhow::Result<()> {
 
                 match run.status {
                     ScriptStatus::Success => {}
-                    ScriptStatus::Failed(_) => continue,
+                    ScriptStatus::Failed(_) | ScriptStatus::TimedOut(_) => continue,
                 }
             }
 
@@ -573,6 +573,7 @@ mod tests {
                 when_script: Some(Script {
                     shell: None,
                     code: "exit 1".to_string(),
+                    ..Default::default()
                 }),
             }],
             provides: vec![],
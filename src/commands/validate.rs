@@ -87,6 +87,14 @@ fn check_scripts(steps: &[Step], script_checker: &mut dyn ScriptChecker) -> Resu
             &mut unavailable_shell_msgs,
             script_checker,
         )?;
+        check_step_script(
+            step,
+            "undo-script",
+            &step.undo_script,
+            &mut checked_count,
+            &mut unavailable_shell_msgs,
+            script_checker,
+        )?;
         for require in &step.requires {
             check_step_script(
                 step,
@@ -136,10 +144,7 @@ fn check_step_script(
 
     let mut checked = false;
     for shell in resolved_shells {
-        let runner_script = runner::Script {
-            shell,
-            code: script.code.clone(),
-        };
+        let runner_script = runner::Script::new(shell, script.code.clone());
 
         if !is_shell_available(&runner_script.shell)
             && !unavailable_shell_msgs.contains(&runner_script.shell)
@@ -204,6 +209,7 @@ mod tests {
             script: Some(Script {
                 shell: Some(Shell::Bash),
                 code: "echo hi".into(),
+                ..Default::default()
             }),
             ..Default::default()
         };
@@ -237,6 +243,7 @@ mod tests {
             script: Some(Script {
                 shell: Some(Shell::Bash),
                 code: "echo hi".into(),
+                ..Default::default()
             }),
             ..Default::default()
         };
@@ -271,12 +278,15 @@ mod tests {
             script: Some(Script {
                 shell: None,
                 code: "echo hi".into(),
+                ..Default::default()
             }),
             defaults: Some(Defaults {
                 windows_package_manager: None,
                 windows_shell: Some(Shell::PowerShellCore),
                 linux_shell: None,
                 macos_shell: None,
+                refresh_index: None,
+                linux_privilege: None,
             }),
             ..Default::default()
         };
@@ -1,6 +1,7 @@
 use anyhow::Context;
 use anyhow::Result;
 use directories::ProjectDirs;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::fs;
@@ -12,7 +13,7 @@ use crate::config::PackageManager;
 use crate::config::PackageSource;
 use crate::config::Repository;
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, JsonSchema, Debug, Default)]
 pub struct PackageAliases(HashMap<String, HashMap<PackageSource, String>>);
 
 impl PackageAliases {
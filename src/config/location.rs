@@ -0,0 +1,258 @@
+use std::fmt::Write;
+use std::fs;
+
+use crate::config::Step;
+
+/// Position of an element in a config file, both line and column are 1-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Formats an error as `file:line:col: message` followed by a snippet of the offending line.
+pub fn format_error(file: &str, content: &str, span: Span, message: &str) -> String {
+    let mut result = format!("{file}:{}:{}: {message}", span.line, span.column);
+    if let Some(snippet) = snippet(content, span) {
+        result.push('\n');
+        result.push_str(&snippet);
+    }
+    result
+}
+
+/// Same as [`format_error`], but reads the content of the file itself.
+/// Falls back to the location without a snippet if the file can't be read.
+pub fn format_file_error(file: &str, span: Span, message: &str) -> String {
+    let content = fs::read_to_string(file).unwrap_or_default();
+    format_error(file, &content, span, message)
+}
+
+/// Describes an error related to the step, pointing at its location if it's known.
+pub fn format_step_error(step: &Step, message: &str) -> String {
+    match step.span {
+        Some(span) => format_file_error(&step.source_file, span, message),
+        None if step.source_file.is_empty() => message.to_string(),
+        None => format!("{}: {message}", step.source_file),
+    }
+}
+
+fn snippet(content: &str, span: Span) -> Option<String> {
+    let line = content.lines().nth(span.line.checked_sub(1)?)?;
+    let gutter = span.line.to_string().len();
+    let marker_offset = line
+        .chars()
+        .take(span.column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+
+    let mut result = String::new();
+    let _ = writeln!(result, "{:gutter$} |", "");
+    let _ = writeln!(result, "{} | {line}", span.line);
+    let _ = write!(result, "{:gutter$} | {marker_offset}^", "");
+    Some(result)
+}
+
+/// Converts a YAML error into a message with its location and snippet, if it has one.
+pub fn yaml_error(file: &str, content: &str, err: &serde_yaml::Error) -> String {
+    let Some(location) = err.location() else {
+        return err.to_string();
+    };
+
+    let span = Span {
+        line: location.line(),
+        column: location.column(),
+    };
+    let position = format!(" at line {} column {}", span.line, span.column);
+    let message = err.to_string().replacen(&position, "", 1);
+
+    // Custom errors of a field (e.g. an invalid OS expression) point at the start
+    // of the mapping containing it, so try to narrow them down to the quoted value.
+    let span = quoted_value(&message)
+        .and_then(|value| find_in_mapping(content, span, value))
+        .unwrap_or(span);
+
+    format_error(file, content, span, &message)
+}
+
+fn quoted_value(message: &str) -> Option<&str> {
+    let (_, rest) = message.split_once('\'')?;
+    let (value, _) = rest.split_once('\'')?;
+    Some(value).filter(|v| !v.is_empty())
+}
+
+fn find_in_mapping(content: &str, start: Span, value: &str) -> Option<Span> {
+    content
+        .lines()
+        .enumerate()
+        .skip(start.line.checked_sub(1)?)
+        .take_while(|(idx, line)| {
+            let trimmed = line.trim_start();
+            *idx + 1 == start.line
+                || trimmed.is_empty()
+                || line.len() - trimmed.len() >= start.column - 1
+        })
+        .find_map(|(idx, line)| {
+            line.find(value).map(|pos| Span {
+                line: idx + 1,
+                column: line[..pos].chars().count() + 1,
+            })
+        })
+}
+
+/// Finds the spans of the items of the top-level `steps` sequence, in order.
+///
+/// Only block sequences in a single document are recognized. Returns `None` for other layouts
+/// (flow sequences like `steps: [...]`, aliases, several documents), the steps then have no span.
+pub fn step_spans(content: &str) -> Option<Vec<Span>> {
+    let mut spans = vec![];
+    let mut root_indent = None;
+    let mut item_indent = None;
+    let mut in_steps = false;
+    let mut steps_done = false;
+
+    for (idx, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') || line == "..." {
+            continue;
+        }
+        if line == "---" || line.starts_with("--- ") {
+            // a start marker after the first document starts another one
+            if root_indent.is_some() {
+                return None;
+            }
+            continue;
+        }
+
+        let indent = line.len() - trimmed.len();
+        let root = *root_indent.get_or_insert(indent);
+        if steps_done {
+            continue;
+        }
+
+        if !in_steps {
+            if let Some(rest) = trimmed.strip_prefix("steps:").filter(|_| indent == root) {
+                // only an anchor may follow the key of a block sequence
+                let value = rest.split(" #").next().unwrap_or_default().trim();
+                let is_anchor = value.starts_with('&') && !value.contains(' ');
+                if !value.is_empty() && !is_anchor {
+                    return None;
+                }
+                in_steps = true;
+            }
+            continue;
+        }
+
+        let is_item = trimmed == "-" || trimmed.starts_with("- ");
+        if indent < root || (indent == root && !is_item) {
+            steps_done = true;
+            continue;
+        }
+        if item_indent.is_none() && !is_item {
+            // e.g. a flow sequence on the line after the key
+            return None;
+        }
+
+        if is_item && *item_indent.get_or_insert(indent) == indent {
+            spans.push(Span {
+                line: idx + 1,
+                column: indent + 1,
+            });
+        }
+    }
+
+    Some(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_spans() {
+        let content = "\
+includes:
+  - child.yaml
+steps:
+  - id: step1
+    requires:
+      - id: step0
+    script: |
+      - not a step
+  # comment
+  - id: step2
+defaults: {}
+";
+        assert_eq!(
+            step_spans(content).unwrap(),
+            vec![
+                Span { line: 4, column: 3 },
+                Span {
+                    line: 10,
+                    column: 3
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_step_spans_indented_document() {
+        let content = "
+            steps:
+            - id: step1
+            - id: step2
+            ";
+        assert_eq!(
+            step_spans(content).unwrap(),
+            vec![
+                Span {
+                    line: 3,
+                    column: 13
+                },
+                Span {
+                    line: 4,
+                    column: 13
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_step_spans_anchors() {
+        let content = "\
+---
+steps: &all # anchored
+  - &base
+    id: step1
+  - <<: *base
+    id: step2
+";
+        assert_eq!(
+            step_spans(content).unwrap(),
+            vec![Span { line: 3, column: 3 }, Span { line: 5, column: 3 }]
+        );
+    }
+
+    #[test]
+    fn test_step_spans_unsupported_layouts() {
+        let flow = "steps: [{id: step1}, {id: step2}]\n";
+        let flow_on_next_line = "steps:\n  [{id: step1}]\n";
+        let alias = "vars: &steps\n  - id: step1\nsteps: *steps\n";
+        let documents = "steps:\n  - id: step1\n---\nsteps:\n  - id: step2\n";
+
+        assert_eq!(step_spans(flow), None);
+        assert_eq!(step_spans(flow_on_next_line), None);
+        assert_eq!(step_spans(alias), None);
+        assert_eq!(step_spans(documents), None);
+    }
+
+    #[test]
+    fn test_format_error() {
+        let content = "steps:\n  - id: step1\n    os: linux &&\n";
+        let message = format_error("config.yaml", content, Span { line: 3, column: 9 }, "oops");
+
+        assert_eq!(
+            message,
+            "config.yaml:3:9: oops\n  |\n3 |     os: linux &&\n  |         ^"
+        );
+    }
+}
@@ -2,10 +2,13 @@ use crate::system::pkg::{PackageManager, PackageSource, Repository};
 
 pub mod aliases;
 pub mod expr;
+pub mod location;
 mod parser;
 
 mod steps;
+pub mod templates;
 mod validate;
+pub mod vars;
 
 pub use crate::config::validate::ValidationMode;
 pub use steps::*;
@@ -1,15 +1,34 @@
-use std::{collections::HashSet, fs, path::Path};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    config::{Config, Defaults, Step},
+    config::{
+        Config, Defaults, Step, expr::Expr, location, templates, templates::Templates, vars,
+        vars::Vars,
+    },
+    system::pkg::version::parse_package_spec,
     utils,
 };
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use indexmap::IndexSet;
+use tracing::{debug, warn};
+
+/// Settings passed down from a file to the files it includes.
+#[derive(Default, Clone)]
+struct Inherited {
+    defaults: Option<Defaults>,
+    vars: Vars,
+    templates: Templates,
+    os: Option<Expr>,
+    tags: Vec<String>,
+}
 
 pub fn parse(file: &str) -> Result<Vec<Step>> {
     let mut visited_files = HashSet::new();
-    let mut steps = parse_recursive(file, &mut visited_files, None, None)?;
+    let mut steps = parse_recursive(file, &mut visited_files, None, &Inherited::default())?;
     normalize_steps(&mut steps);
     Ok(steps)
 }
@@ -18,7 +37,7 @@ fn parse_recursive(
     file: &str,
     visited_files: &mut HashSet<String>,
     base_dir: Option<&Path>,
-    inherited_defaults: Option<Defaults>,
+    inherited: &Inherited,
 ) -> Result<Vec<Step>> {
     let abs_path = utils::file::get_absolute_path(file, base_dir)
         .with_context(|| format!("Failed to resolve absolute path for '{file}'"))?;
@@ -36,30 +55,78 @@ fn parse_recursive(
     let content = fs::read_to_string(&abs_path)
         .with_context(|| format!("Failed to read file '{abs_path_str}'"))?;
     let config: Config = serde_yaml::from_str(&content)
+        .map_err(|err| anyhow!(location::yaml_error(&abs_path_str, &content, &err)))
         .with_context(|| format!("YAML parse error in file '{abs_path_str}'"))?;
 
-    let config_defaults = Defaults::merge(&inherited_defaults, &config.defaults);
+    let config_defaults = Defaults::merge(&inherited.defaults, &config.defaults);
+    let config_vars = vars::merge(&inherited.vars, &config.vars);
+    let config_templates = templates::merge(&inherited.templates, &config.templates);
 
     let mut steps = vec![];
 
     if let Some(includes) = config.includes {
         for include in includes {
+            let path = vars::interpolate(&include.path, &config_vars).with_context(|| {
+                format!(
+                    "Failed to resolve include '{}' in file '{abs_path_str}'",
+                    include.path
+                )
+            })?;
+            let nested_inherited = Inherited {
+                defaults: Some(config_defaults.clone()),
+                vars: config_vars.clone(),
+                templates: config_templates.clone(),
+                os: and_os_exprs(&inherited.os, &include.os),
+                tags: templates::combine(&inherited.tags, &include.tags),
+            };
+
             let nested_dir = abs_path.parent().unwrap_or(Path::new("../.."));
-            let nested = parse_recursive(
-                &include,
-                visited_files,
-                Some(nested_dir),
-                Some(config_defaults.clone()),
-            )
-            .with_context(|| format!("Failed to parse included file '{include}'"))?;
-            steps.extend(nested);
+            for nested_file in expand_include(&path, nested_dir)? {
+                let nested = parse_recursive(
+                    &nested_file,
+                    visited_files,
+                    Some(nested_dir),
+                    &nested_inherited,
+                )
+                .with_context(|| format!("Failed to parse included file '{nested_file}'"))?;
+                steps.extend(nested);
+            }
         }
     }
 
     if let Some(mut own_steps) = config.steps {
+        match location::step_spans(&content) {
+            Some(spans) if spans.len() == own_steps.len() => {
+                for (own_step, span) in own_steps.iter_mut().zip(spans) {
+                    own_step.span = Some(span);
+                }
+            }
+            _ => debug!("Steps of '{abs_path_str}' can't be located, errors only name the file"),
+        }
+
         for own_step in &mut own_steps {
             own_step.source_file.clone_from(&abs_path_str);
             own_step.defaults = Some(config_defaults.clone());
+
+            // only formatted on failure, the snippet of the step is costly
+            let (id, span) = (own_step.id.clone(), own_step.span);
+            let step_error = |message: String| match span {
+                Some(span) => location::format_error(&abs_path_str, &content, span, &message),
+                None => format!("{message} in file '{abs_path_str}'"),
+            };
+
+            templates::apply(own_step, &config_templates)
+                .with_context(|| step_error(format!("Failed to expand step '{id}'")))?;
+            vars::interpolate_step(own_step, &config_vars).with_context(|| {
+                step_error(format!("Failed to resolve variables of step '{id}'"))
+            })?;
+            if let Some(timeout) = own_step.script_timeout {
+                for script in own_step.scripts_mut() {
+                    script.timeout.get_or_insert(timeout);
+                }
+            }
+            own_step.os = and_os_exprs(&inherited.os, &own_step.os);
+            own_step.tags = templates::combine(&inherited.tags, &own_step.tags);
         }
         steps.extend(own_steps);
     }
@@ -67,22 +134,104 @@ fn parse_recursive(
     Ok(steps)
 }
 
+fn and_os_exprs(outer: &Option<Expr>, inner: &Option<Expr>) -> Option<Expr> {
+    match (outer, inner) {
+        (Some(outer), Some(inner)) => {
+            Some(Expr::And(Box::new(outer.clone()), Box::new(inner.clone())))
+        }
+        (Some(expr), None) | (None, Some(expr)) => Some(expr.clone()),
+        (None, None) => None,
+    }
+}
+
+/// Expands an include entry into the list of files to parse.
+///
+/// Directories include all their `.yaml`/`.yml` files (non-recursively), glob patterns include
+/// all matching files. In both cases files are sorted by path to keep the order deterministic.
+fn expand_include(include: &str, base_dir: &Path) -> Result<Vec<String>> {
+    let path = base_dir.join(include);
+
+    let mut files: Vec<PathBuf> = if path.is_dir() {
+        fs::read_dir(&path)
+            .with_context(|| format!("Failed to read included directory '{include}'"))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()
+            .with_context(|| format!("Failed to read included directory '{include}'"))?
+            .into_iter()
+            .filter(|p| p.is_file() && is_yaml_file(p))
+            .collect()
+    } else if is_glob_pattern(include) {
+        let pattern = path
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("Invalid UTF-8 in path: {:?}", path))?;
+        let matches = glob::glob(pattern)
+            .with_context(|| format!("Invalid include pattern '{include}'"))?
+            .collect::<Result<Vec<PathBuf>, _>>()
+            .with_context(|| format!("Failed to expand include pattern '{include}'"))?
+            .into_iter()
+            .filter(|p| p.is_file())
+            .collect::<Vec<PathBuf>>();
+
+        if matches.is_empty() {
+            warn!("No files match include pattern '{include}'");
+        }
+        matches
+    } else {
+        return Ok(vec![include.to_string()]);
+    };
+
+    files.sort();
+    files
+        .into_iter()
+        .map(|p| {
+            p.to_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("Invalid UTF-8 in path: {:?}", p))
+        })
+        .collect()
+}
+
+fn is_glob_pattern(include: &str) -> bool {
+    include.contains(['*', '?', '['])
+}
+
+fn is_yaml_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "yaml" || ext == "yml")
+}
+
 fn normalize_steps(steps: &mut Vec<Step>) {
     for step in steps {
-        if step.provides.is_empty() && !step.packages.is_empty() {
-            let unique_packages = step.packages.iter().cloned().collect::<IndexSet<String>>();
-            step.provides = unique_packages.iter().cloned().collect();
+        if step.provides.is_empty() && (!step.packages.is_empty() || !step.repositories.is_empty())
+        {
+            let unique_provides = step
+                .packages
+                .iter()
+                .map(|pkg| package_name(pkg))
+                .chain(step.repositories.iter().map(|repo| repo.name()))
+                .map(str::to_string)
+                .collect::<IndexSet<String>>();
+            step.provides = unique_provides.into_iter().collect();
         }
     }
 }
 
+fn package_name(spec: &str) -> &str {
+    parse_package_spec(spec).map_or(spec, |(name, _)| name)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::config::PackageManager;
+    use crate::config::{
+        FileEditAction, ManagedBlock, PackageManager, PackageRepo, PackageSource, Repository,
+    };
 
     use super::*;
+    use crate::config::Script;
+    use crate::system::privilege::Privilege;
     use crate::system::shell::Shell;
     use std::fs;
+    use std::time::Duration;
     use tempfile::tempdir;
 
     #[test]
@@ -327,4 +476,544 @@ mod tests {
         assert_eq!(steps[0].provides[2], "pkg3");
         assert_eq!(steps[0].provides[3], "pkg4");
     }
+
+    #[test]
+    fn test_parse_versioned_packages() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let parent_path = dir.path().join("file.yaml");
+
+        fs::write(
+            &parent_path,
+            r#"
+            steps:
+              - id: "step1"
+                packages:
+                  - ripgrep@14.1.0
+                  - name: node
+                    version: ">=20"
+                  - "@angular/cli"
+            "#,
+        )
+        .expect("Failed to write file.yaml");
+
+        let steps = parse(parent_path.to_str().unwrap()).expect("Failed to parse YAML");
+
+        assert_eq!(
+            steps[0].packages,
+            vec!["ripgrep@14.1.0", "node@>=20", "@angular/cli"]
+        );
+        assert_eq!(steps[0].provides, vec!["ripgrep", "node", "@angular/cli"]);
+    }
+
+    #[test]
+    fn test_parse_repositories() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let parent_path = dir.path().join("file.yaml");
+
+        fs::write(
+            &parent_path,
+            r#"
+            vars:
+              codename: noble
+            steps:
+              - id: "step1"
+                repositories:
+                  - type: apt
+                    name: docker
+                    uri: https://download.docker.com/linux/ubuntu
+                    suite: "{{ codename }}"
+                    components: [stable]
+                    key: https://download.docker.com/linux/ubuntu/gpg
+                  - type: copr
+                    name: atim/lazygit
+                packages: [docker-ce]
+            "#,
+        )
+        .expect("Failed to write file.yaml");
+
+        let steps = parse(parent_path.to_str().unwrap()).expect("Failed to parse YAML");
+
+        assert_eq!(
+            steps[0].repositories[0],
+            PackageRepo::Apt {
+                name: "docker".to_string(),
+                uri: "https://download.docker.com/linux/ubuntu".to_string(),
+                suite: "noble".to_string(),
+                components: vec!["stable".to_string()],
+                key: "https://download.docker.com/linux/ubuntu/gpg".to_string(),
+            }
+        );
+        assert_eq!(
+            steps[0].repositories[1],
+            PackageRepo::Copr {
+                name: "atim/lazygit".to_string()
+            }
+        );
+        assert_eq!(
+            steps[0].provides,
+            vec!["docker-ce", "docker", "atim/lazygit"]
+        );
+    }
+
+    #[test]
+    fn test_parse_vars_interpolation() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        fs::create_dir(dir.path().join("hosts")).expect("Failed to create child dir");
+        let parent_path = dir.path().join("parent.yaml");
+
+        fs::write(
+            &parent_path,
+            r#"
+            vars:
+              host: laptop
+              editor: neovim
+            includes:
+              - hosts/{{ host }}.yaml
+            steps:
+              - id: "step1"
+                packages: ["{{ editor }}", "git"]
+                env: ["{{ host }}_TOKEN"]
+                script: echo {{ editor }}
+            "#,
+        )
+        .expect("Failed to write parent.yaml");
+
+        fs::write(
+            dir.path().join("hosts/laptop.yaml"),
+            r#"
+            vars:
+              editor: helix
+            steps:
+              - id: "step2"
+                check: which {{editor}}
+            "#,
+        )
+        .expect("Failed to write laptop.yaml");
+
+        let steps = parse(parent_path.to_str().unwrap()).expect("Failed to parse YAML");
+
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].id, "step2");
+        assert_eq!(steps[0].check_script.as_ref().unwrap().code, "which helix");
+        assert_eq!(steps[0].vars.get("host").unwrap(), "laptop");
+
+        assert_eq!(steps[1].packages, vec!["neovim", "git"]);
+        assert_eq!(steps[1].provides, vec!["neovim", "git"]);
+        assert_eq!(steps[1].env, vec!["laptop_TOKEN"]);
+        assert_eq!(steps[1].script.as_ref().unwrap().code, "echo neovim");
+    }
+
+    #[test]
+    fn test_parse_numeric_mode() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("file.yaml");
+
+        fs::write(
+            &path,
+            r#"
+            steps:
+              - id: "step1"
+                files:
+                  - src: gitconfig.tmpl
+                    dest: ~/.gitconfig
+                    mode: 0o644
+            "#,
+        )
+        .expect("Failed to write file.yaml");
+
+        let err = format!("{:#}", parse(path.to_str().unwrap()).unwrap_err());
+
+        assert!(
+            err.contains(r#"invalid mode 420, quote octal modes like "0644""#),
+            "unexpected err: {err}"
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_var() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let parent_path = dir.path().join("file.yaml");
+
+        fs::write(
+            &parent_path,
+            r#"
+            steps:
+              - id: "step1"
+                script: echo {{ missing }}
+            "#,
+        )
+        .expect("Failed to write file.yaml");
+
+        let err = parse(parent_path.to_str().unwrap()).unwrap_err();
+        let messages: Vec<String> = err.chain().map(|e| e.to_string()).collect();
+
+        assert!(
+            messages.contains(&"unknown variable 'missing'".to_string()),
+            "unexpected err: {}",
+            messages.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_parse_error_without_step_location() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("file.yaml");
+        let path_str = path.to_str().unwrap();
+
+        // flow sequences can't be located, errors fall back to the file
+        fs::write(
+            &path,
+            r#"steps: [{ id: "step1", script: "echo {{ missing }}" }]"#,
+        )
+        .expect("Failed to write file.yaml");
+
+        let err = parse(path_str).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!("Failed to resolve variables of step 'step1' in file '{path_str}'")
+        );
+    }
+
+    #[test]
+    fn test_parse_step_extends_template() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let parent_path = dir.path().join("parent.yaml");
+
+        fs::write(
+            &parent_path,
+            r#"
+            templates:
+              aur:
+                os: "%arch"
+                package_source: aur
+                tags: ["arch"]
+                pre_script: echo pre
+            includes:
+              - child.yaml
+            steps:
+              - id: "step1"
+                extends: aur
+                tags: ["fonts", "arch"]
+                pre_script: echo own
+            "#,
+        )
+        .expect("Failed to write parent.yaml");
+
+        fs::write(
+            dir.path().join("child.yaml"),
+            r#"
+            steps:
+              - id: "step2"
+                extends: aur
+                packages: ["yay-bin"]
+            "#,
+        )
+        .expect("Failed to write child.yaml");
+
+        let steps = parse(parent_path.to_str().unwrap()).expect("Failed to parse YAML");
+
+        assert_eq!(steps.len(), 2);
+
+        assert_eq!(steps[0].id, "step2");
+        assert_eq!(steps[0].tags, vec!["arch"]);
+        assert!(steps[0].os.is_some());
+        assert_eq!(
+            steps[0].package_source,
+            Some(PackageSource::Repository(Repository::Aur))
+        );
+        assert_eq!(steps[0].pre_script.as_ref().unwrap().code, "echo pre");
+
+        assert_eq!(steps[1].id, "step1");
+        assert_eq!(steps[1].tags, vec!["arch", "fonts"]);
+        assert_eq!(steps[1].pre_script.as_ref().unwrap().code, "echo own");
+    }
+
+    #[test]
+    fn test_parse_unknown_template() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let parent_path = dir.path().join("file.yaml");
+
+        fs::write(
+            &parent_path,
+            r#"
+            steps:
+              - id: "step1"
+                extends: missing
+            "#,
+        )
+        .expect("Failed to write file.yaml");
+
+        let err = parse(parent_path.to_str().unwrap()).unwrap_err();
+        let messages: Vec<String> = err.chain().map(|e| e.to_string()).collect();
+
+        assert!(
+            messages.contains(&"unknown template 'missing'".to_string()),
+            "unexpected err: {}",
+            messages.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_parse_glob_and_directory_includes() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        fs::create_dir(dir.path().join("modules")).expect("Failed to create modules dir");
+        fs::create_dir(dir.path().join("hosts")).expect("Failed to create hosts dir");
+
+        let parent_path = dir.path().join("parent.yaml");
+
+        fs::write(
+            &parent_path,
+            r#"
+            includes:
+              - modules/*.yaml
+              - hosts/
+              - modules/a.yaml
+              - "*.yaml"
+            steps:
+              - id: "root"
+            "#,
+        )
+        .expect("Failed to write parent.yaml");
+
+        for (file, id) in [
+            ("modules/b.yaml", "module-b"),
+            ("modules/a.yaml", "module-a"),
+            ("hosts/laptop.yml", "host-laptop"),
+            ("hosts/desktop.yaml", "host-desktop"),
+        ] {
+            fs::write(dir.path().join(file), format!("steps:\n  - id: \"{id}\"\n"))
+                .expect("Failed to write included file");
+        }
+        fs::write(dir.path().join("hosts/notes.txt"), "not a config")
+            .expect("Failed to write notes.txt");
+
+        let steps = parse(parent_path.to_str().unwrap()).expect("Failed to parse YAML");
+        let ids: Vec<&str> = steps.iter().map(|s| s.id.as_str()).collect();
+
+        assert_eq!(
+            ids,
+            vec![
+                "module-a",
+                "module-b",
+                "host-desktop",
+                "host-laptop",
+                "root"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_conditional_include() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let parent_path = dir.path().join("parent.yaml");
+
+        fs::write(
+            &parent_path,
+            r#"
+            includes:
+              - path: windows.yaml
+                os: windows
+                tags: ["win"]
+            steps:
+              - id: "root"
+            "#,
+        )
+        .expect("Failed to write parent.yaml");
+
+        fs::write(
+            dir.path().join("windows.yaml"),
+            r#"
+            includes:
+              - path: nested.yaml
+                tags: ["nested"]
+            steps:
+              - id: "win-step"
+                tags: ["tool"]
+              - id: "win-pwsh-step"
+                os: "!arch"
+            "#,
+        )
+        .expect("Failed to write windows.yaml");
+
+        fs::write(
+            dir.path().join("nested.yaml"),
+            r#"
+            steps:
+              - id: "nested-step"
+            "#,
+        )
+        .expect("Failed to write nested.yaml");
+
+        let steps = parse(parent_path.to_str().unwrap()).expect("Failed to parse YAML");
+        let find = |id: &str| steps.iter().find(|s| s.id == id).unwrap();
+
+        assert_eq!(steps.len(), 4);
+        assert!(find("root").os.is_none());
+        assert!(find("root").tags.is_empty());
+
+        assert_eq!(find("win-step").tags, vec!["win", "tool"]);
+        assert!(matches!(find("win-step").os, Some(Expr::Var(ref os)) if os == "windows"));
+        assert!(matches!(find("win-pwsh-step").os, Some(Expr::And(_, _))));
+
+        assert_eq!(find("nested-step").tags, vec!["win", "nested"]);
+        assert!(matches!(find("nested-step").os, Some(Expr::Var(ref os)) if os == "windows"));
+    }
+
+    #[test]
+    fn test_parse_step_locations() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("config.yaml");
+
+        fs::write(
+            &path,
+            "steps:\n  - id: step1\n    script: echo 1\n\n  - id: step2\n",
+        )
+        .expect("Failed to write config.yaml");
+
+        let steps = parse(path.to_str().unwrap()).expect("Failed to parse YAML");
+        let file = path.to_str().unwrap();
+
+        assert_eq!(steps[0].location(), format!("{file}:2:3"));
+        assert_eq!(steps[1].location(), format!("{file}:5:3"));
+    }
+
+    #[test]
+    fn test_parse_invalid_os_expr_location() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("config.yaml");
+
+        fs::write(
+            &path,
+            "steps:\n  - id: step1\n    script: echo 1\n    os: \"(linux\"\n",
+        )
+        .expect("Failed to write config.yaml");
+
+        let err = format!("{:#}", parse(path.to_str().unwrap()).unwrap_err());
+        let file = path.to_str().unwrap();
+
+        assert!(
+            err.contains(&format!(
+                "{file}:4:10: steps[0]: Failed to parse OS expr '(linux'"
+            )),
+            "unexpected err: {err}"
+        );
+        assert!(
+            err.contains("4 |     os: \"(linux\""),
+            "unexpected err: {err}"
+        );
+    }
+
+    #[test]
+    fn test_parse_timeouts() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("config.yaml");
+
+        fs::write(
+            &path,
+            r#"
+            steps:
+              - id: "step1"
+                timeout: 1m 30s
+                script: echo 1
+                check:
+                  shell: bash
+                  run: echo 1
+                  timeout: 5
+              - id: "step2"
+                script: echo 2
+            "#,
+        )
+        .expect("Failed to write config.yaml");
+
+        let steps = parse(path.to_str().unwrap()).expect("Failed to parse YAML");
+        let timeout = |script: &Option<Script>| script.as_ref().unwrap().timeout;
+
+        assert_eq!(steps[0].script_timeout, Some(Duration::from_secs(90)));
+        assert_eq!(timeout(&steps[0].script), Some(Duration::from_secs(90)));
+        assert_eq!(
+            timeout(&steps[0].check_script),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(timeout(&steps[1].script), None);
+    }
+
+    #[test]
+    fn test_parse_privileged_scripts() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("config.yaml");
+
+        fs::write(
+            &path,
+            r#"
+            defaults:
+              linux_privilege: doas
+            steps:
+              - id: "step1"
+                script:
+                  run: systemctl enable --now docker
+                  privileged: true
+                check: systemctl is-enabled docker
+            "#,
+        )
+        .expect("Failed to write config.yaml");
+
+        let steps = parse(path.to_str().unwrap()).expect("Failed to parse YAML");
+        let script = steps[0].script.as_ref().unwrap();
+
+        assert!(script.privileged);
+        assert_eq!(script.shell, None);
+        assert!(!steps[0].check_script.as_ref().unwrap().privileged);
+        assert_eq!(
+            steps[0].defaults.as_ref().unwrap().linux_privilege,
+            Some(Privilege::Doas)
+        );
+    }
+
+    #[test]
+    fn test_parse_edit_file() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("config.yaml");
+
+        fs::write(
+            &path,
+            r##"
+            vars:
+              bin: ~/.local/bin
+            steps:
+              - id: "shell"
+                edit_file:
+                  - path: ~/.bashrc
+                    ensure_line: export PATH={{ bin }}:$PATH
+                  - path: /etc/pacman.conf
+                    remove_line: "#Color"
+                  - path: ~/.gitconfig
+                    block:
+                      id: aliases
+                      content: |
+                        [alias]
+                          st = status
+            "##,
+        )
+        .expect("Failed to write config.yaml");
+
+        let steps = parse(path.to_str().unwrap()).expect("Failed to parse YAML");
+        let actions = steps[0]
+            .edit_file
+            .iter()
+            .map(|e| e.action.clone())
+            .collect::<Vec<FileEditAction>>();
+
+        assert_eq!(
+            actions,
+            vec![
+                FileEditAction::EnsureLine("export PATH=~/.local/bin:$PATH".to_string()),
+                FileEditAction::RemoveLine("#Color".to_string()),
+                FileEditAction::Block(ManagedBlock {
+                    id: "aliases".to_string(),
+                    content: "[alias]\n  st = status\n".to_string(),
+                }),
+            ]
+        );
+    }
 }
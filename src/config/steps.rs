@@ -1,19 +1,30 @@
 use crate::config::expr;
 use crate::config::expr::Expr;
+use crate::config::location::Span;
+use crate::config::templates::Templates;
+use crate::config::vars::Vars;
 use crate::system::pkg::{PackageManager, PackageSource, Repository};
+use crate::system::privilege::Privilege;
 use crate::system::shell::Shell;
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
 use serde::de::IntoDeserializer;
 use serde::de::value::StringDeserializer;
 use serde::{Deserialize, Deserializer, de};
+use std::borrow::Cow;
+use std::time::Duration;
 use strum::IntoEnumIterator;
 use strum_macros::Display;
 
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
 pub struct Defaults {
     pub windows_package_manager: Option<PackageManager>,
     pub windows_shell: Option<Shell>,
     pub linux_shell: Option<Shell>,
     pub macos_shell: Option<Shell>,
+    /// Refresh the package index of a manager before its first installation in a run (default `true`).
+    pub refresh_index: Option<bool>,
+    /// How package managers and privileged scripts get root on Linux (default `auto`).
+    pub linux_privilege: Option<Privilege>,
 }
 
 impl Defaults {
@@ -34,6 +45,12 @@ impl Defaults {
             macos_shell: overrides
                 .and_then(|overrides| overrides.macos_shell.clone())
                 .or(inherited.and_then(|d| d.macos_shell.clone())),
+            refresh_index: overrides
+                .and_then(|overrides| overrides.refresh_index)
+                .or(inherited.and_then(|d| d.refresh_index)),
+            linux_privilege: overrides
+                .and_then(|overrides| overrides.linux_privilege.clone())
+                .or(inherited.and_then(|d| d.linux_privilege.clone())),
         }
     }
 }
@@ -47,15 +64,10 @@ impl<'de> Deserialize<'de> for PackageSource {
         let s_lower = s.to_lowercase();
 
         let parse_err = || {
-            let mut expected: Vec<String> = PackageManager::iter()
-                .filter(|pm| !Repository::Aur.get_package_managers().contains(pm))
-                .map(|pm| pm.to_string().to_lowercase())
-                .collect();
-            expected.extend(Repository::iter().map(|repo| repo.to_string().to_lowercase()));
             de::Error::custom(format!(
                 "unknown package_source '{}', expected one of [{}]",
                 s_lower,
-                expected.join(", ")
+                package_source_names().join(", ")
             ))
         };
 
@@ -77,10 +89,84 @@ impl<'de> Deserialize<'de> for PackageSource {
         Err(parse_err())
     }
 }
-#[derive(Debug, Clone)]
+
+impl JsonSchema for PackageSource {
+    fn schema_name() -> Cow<'static, str> {
+        "PackageSource".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "enum": package_source_names(),
+        })
+    }
+}
+
+fn package_source_names() -> Vec<String> {
+    let mut names: Vec<String> = PackageManager::iter()
+        .filter(|pm| !Repository::Aur.get_package_managers().contains(pm))
+        .map(|pm| pm.to_string().to_lowercase())
+        .collect();
+    names.extend(Repository::iter().map(|repo| repo.to_string().to_lowercase()));
+    names
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Script {
     pub shell: Option<Shell>,
     pub code: String,
+    pub timeout: Option<Duration>,
+    /// Run the script as root.
+    pub privileged: bool,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ScriptDef {
+    Short(String),
+    Full {
+        shell: Option<Shell>,
+        #[serde(rename = "run")]
+        code: String,
+        #[serde(default, deserialize_with = "parse_duration")]
+        #[schemars(with = "Option<DurationDef>")]
+        timeout: Option<Duration>,
+        #[serde(default)]
+        privileged: bool,
+    },
+}
+
+/// Duration in seconds or in human-readable form, e.g. `90`, `30s`, `5m`, `1h 30m`.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum DurationDef {
+    Seconds(u64),
+    Text(String),
+}
+
+fn parse_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: Option<DurationDef> = Option::deserialize(deserializer)?;
+    match value {
+        Some(DurationDef::Seconds(secs)) => Ok(Some(Duration::from_secs(secs))),
+        Some(DurationDef::Text(text)) => humantime::parse_duration(&text)
+            .map(Some)
+            .map_err(|e| de::Error::custom(format!("invalid duration '{text}': {e}"))),
+        None => Ok(None),
+    }
+}
+
+impl JsonSchema for Script {
+    fn schema_name() -> Cow<'static, str> {
+        "Script".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        ScriptDef::json_schema(generator)
+    }
 }
 
 impl<'de> Deserialize<'de> for Script {
@@ -88,23 +174,22 @@ impl<'de> Deserialize<'de> for Script {
     where
         D: Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        #[serde(untagged)]
-        enum ScriptDef {
-            Short(String),
-            Full {
-                shell: Shell,
-                #[serde(rename = "run")]
-                code: String,
-            },
-        }
-
         let value = ScriptDef::deserialize(deserializer)?;
         Ok(match value {
-            ScriptDef::Short(code) => Script { shell: None, code },
-            ScriptDef::Full { shell, code } => Script {
-                shell: Some(shell),
+            ScriptDef::Short(code) => Script {
                 code,
+                ..Default::default()
+            },
+            ScriptDef::Full {
+                shell,
+                code,
+                timeout,
+                privileged,
+            } => Script {
+                shell,
+                code,
+                timeout,
+                privileged,
             },
         })
     }
@@ -117,24 +202,35 @@ pub struct Require {
     pub when_script: Option<Script>,
 }
 
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum RequireDef {
+    Short(String),
+    Full {
+        id: String,
+        #[serde(default, deserialize_with = "expr::os::parse_os_expr")]
+        #[schemars(with = "Option<String>")]
+        os: Option<Expr>,
+        #[serde(rename = "when")]
+        when_script: Option<Script>,
+    },
+}
+
+impl JsonSchema for Require {
+    fn schema_name() -> Cow<'static, str> {
+        "Require".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        RequireDef::json_schema(generator)
+    }
+}
+
 impl<'de> Deserialize<'de> for Require {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        #[serde(untagged)]
-        enum RequireDef {
-            Short(String),
-            Full {
-                id: String,
-                #[serde(default, deserialize_with = "expr::os::parse_os_expr")]
-                os: Option<Expr>,
-                #[serde(rename = "when")]
-                when_script: Option<Script>,
-            },
-        }
-
         let value = RequireDef::deserialize(deserializer).map_err(|err| {
             de::Error::custom(format!(
                 "Invalid require format: {}\n\
@@ -161,6 +257,231 @@ impl<'de> Deserialize<'de> for Require {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Include {
+    pub path: String,
+    pub os: Option<Expr>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum IncludeDef {
+    Short(String),
+    Full {
+        path: String,
+        #[serde(default, deserialize_with = "expr::os::parse_os_expr")]
+        #[schemars(with = "Option<String>")]
+        os: Option<Expr>,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+}
+
+impl JsonSchema for Include {
+    fn schema_name() -> Cow<'static, str> {
+        "Include".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        IncludeDef::json_schema(generator)
+    }
+}
+
+impl<'de> Deserialize<'de> for Include {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = IncludeDef::deserialize(deserializer).map_err(|err| {
+            de::Error::custom(format!(
+                "Invalid include format: {}\n\
+                 Expected either a short string (path) or a full object with fields 'path', optional 'os' and optional 'tags'.",
+                err
+            ))
+        })?;
+        Ok(match value {
+            IncludeDef::Short(path) => Include {
+                path,
+                os: None,
+                tags: vec![],
+            },
+            IncludeDef::Full { path, os, tags } => Include { path, os, tags },
+        })
+    }
+}
+
+/// Symlink `dest` pointing to `src`; `src` is relative to the directory of the config file.
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct Link {
+    pub src: String,
+    pub dest: String,
+}
+
+/// Template `src` rendered to `dest`; `src` is relative to the directory of the config file.
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct TemplateFile {
+    pub src: String,
+    pub dest: String,
+    #[serde(default, deserialize_with = "parse_mode")]
+    #[schemars(with = "Option<String>")]
+    pub mode: Option<u32>,
+}
+
+/// File fetched from `url` to `dest` and verified against `sha256`.
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct Download {
+    pub url: String,
+    pub dest: String,
+    pub sha256: String,
+    /// Unpack the archive into the `dest` directory instead of saving it as a file.
+    pub extract: Option<ArchiveFormat>,
+    #[serde(default, deserialize_with = "parse_mode")]
+    #[schemars(with = "Option<String>")]
+    pub mode: Option<u32>,
+}
+
+/// Idempotent change of the file at `path`, which is relative to the directory of the config file.
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct FileEdit {
+    pub path: String,
+    #[serde(flatten)]
+    pub action: FileEditAction,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileEditAction {
+    /// Append the line unless the file already contains it.
+    EnsureLine(String),
+    /// Remove every occurrence of the line.
+    RemoveLine(String),
+    /// Keep `content` between `# BEGIN mepris <id>` and `# END mepris <id>` markers.
+    Block(ManagedBlock),
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+pub struct ManagedBlock {
+    pub id: String,
+    pub content: String,
+}
+
+/// Git repository cloned to `dest`; relative paths are relative to the directory of the config file.
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct GitRepo {
+    pub repo: String,
+    pub dest: String,
+    /// Branch, tag or commit to check out; the remote's default branch if not set.
+    #[serde(rename = "ref")]
+    pub rev: Option<String>,
+    /// Fetch and fast-forward to the latest `ref` when the repository already exists.
+    #[serde(default)]
+    pub update: bool,
+}
+
+/// Package repository added before the packages of the step are installed.
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PackageRepo {
+    /// APT source `/etc/apt/sources.list.d/<name>.sources` signed by `key`,
+    /// a URL or a path relative to the directory of the config file.
+    Apt {
+        name: String,
+        uri: String,
+        suite: String,
+        #[serde(default)]
+        components: Vec<String>,
+        key: String,
+    },
+    /// COPR project `owner/project` enabled with `dnf copr`.
+    Copr { name: String },
+    /// Flatpak remote added from a `.flatpakrepo` URL.
+    Flatpak { name: String, url: String },
+    /// Scoop bucket; known buckets like `extras` don't need a `url`.
+    Scoop { name: String, url: Option<String> },
+    /// Section of `/etc/pacman.conf`.
+    Pacman {
+        name: String,
+        server: String,
+        sig_level: Option<String>,
+    },
+}
+
+impl PackageRepo {
+    pub fn name(&self) -> &str {
+        match self {
+            PackageRepo::Apt { name, .. }
+            | PackageRepo::Copr { name }
+            | PackageRepo::Flatpak { name, .. }
+            | PackageRepo::Scoop { name, .. }
+            | PackageRepo::Pacman { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "zip")]
+    Zip,
+}
+
+/// Octal permission bits as a quoted string, e.g. `"0644"`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ModeDef {
+    Number(u64),
+    Text(String),
+}
+
+fn parse_mode<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // YAML reads `0o644` as 420 and `0644` as 644, so the digits of a number can't be trusted
+    let text = match Option::<ModeDef>::deserialize(deserializer)? {
+        Some(ModeDef::Number(number)) => {
+            return Err(de::Error::custom(format!(
+                "invalid mode {number}, quote octal modes like \"0644\""
+            )));
+        }
+        Some(ModeDef::Text(text)) => text,
+        None => return Ok(None),
+    };
+    let digits = text.trim_start_matches("0o");
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .map(Some)
+        .ok_or_else(|| {
+            de::Error::custom(format!("invalid mode '{text}', expected octal like 0644"))
+        })
+}
+
+/// Package name with an optional version constraint: `ripgrep`, `ripgrep@14.1.0`
+/// or `{ name: node, version: ">=20" }`.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum PackageDef {
+    Name(String),
+    Versioned { name: String, version: String },
+}
+
+/// Keeps packages as `name[@version]` strings, so variables can be used in versions too.
+fn parse_packages<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Vec::<PackageDef>::deserialize(deserializer)?
+        .into_iter()
+        .map(|pkg| match pkg {
+            PackageDef::Name(name) => name,
+            PackageDef::Versioned { name, version } => format!("{name}@{version}"),
+        })
+        .collect())
+}
+
 #[derive(Clone, Debug, PartialEq, Display, Default)]
 pub enum StepSelectionReason {
     #[default]
@@ -168,44 +489,109 @@ pub enum StepSelectionReason {
     Dependency,
 }
 
-#[derive(Debug, Deserialize, Default, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Default, Clone)]
 pub struct Step {
     pub id: String,
+    pub extends: Option<String>,
     #[serde(default)]
     pub tags: Vec<String>,
     #[serde(default, deserialize_with = "expr::os::parse_os_expr")]
+    #[schemars(with = "Option<String>")]
     pub os: Option<Expr>,
     #[serde(default)]
     pub env: Vec<String>,
     #[serde(rename = "when")]
     pub when_script: Option<Script>,
     pub package_source: Option<PackageSource>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "parse_packages")]
+    #[schemars(with = "Vec<PackageDef>")]
     pub packages: Vec<String>,
+    #[serde(default)]
+    pub absent_packages: Vec<String>,
     pub pre_script: Option<Script>,
     pub script: Option<Script>,
     #[serde(rename = "check")]
     pub check_script: Option<Script>,
+    #[serde(rename = "undo")]
+    pub undo_script: Option<Script>,
+    #[serde(default)]
+    pub links: Vec<Link>,
+    #[serde(default)]
+    pub files: Vec<TemplateFile>,
+    #[serde(default)]
+    pub downloads: Vec<Download>,
+    #[serde(default)]
+    pub git: Vec<GitRepo>,
+    #[serde(default)]
+    pub edit_file: Vec<FileEdit>,
+    #[serde(default)]
+    pub repositories: Vec<PackageRepo>,
     #[serde(default)]
     pub requires: Vec<Require>,
     #[serde(default)]
     pub provides: Vec<String>,
+    /// Default timeout of each script of the step, not a limit on the whole step.
+    #[serde(default, rename = "timeout", deserialize_with = "parse_duration")]
+    #[schemars(with = "Option<DurationDef>")]
+    pub script_timeout: Option<Duration>,
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default, deserialize_with = "parse_duration")]
+    #[schemars(with = "Option<DurationDef>")]
+    pub retry_delay: Option<Duration>,
 
     #[serde(skip_deserializing)]
+    #[schemars(skip)]
     pub source_file: String,
     #[serde(skip_deserializing)]
+    #[schemars(skip)]
+    pub span: Option<Span>,
+    #[serde(skip_deserializing)]
+    #[schemars(skip)]
     pub defaults: Option<Defaults>,
     #[serde(skip_deserializing)]
+    #[schemars(skip)]
+    pub vars: Vars,
+    #[serde(skip_deserializing)]
+    #[schemars(skip)]
     pub selection_reason: Option<StepSelectionReason>,
     #[serde(skip_deserializing)]
+    #[schemars(skip)]
     pub dependencies: Vec<String>,
     #[serde(skip_deserializing)]
+    #[schemars(skip)]
     pub dependency_of: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl Step {
+    /// All scripts of the step, including `when` scripts of its requirements.
+    pub fn scripts_mut(&mut self) -> impl Iterator<Item = &mut Script> {
+        [
+            &mut self.when_script,
+            &mut self.pre_script,
+            &mut self.script,
+            &mut self.check_script,
+            &mut self.undo_script,
+        ]
+        .into_iter()
+        .chain(self.requires.iter_mut().map(|r| &mut r.when_script))
+        .filter_map(Option::as_mut)
+    }
+
+    /// Location of the step in its config file, `file:line:col` if the position is known.
+    pub fn location(&self) -> String {
+        match &self.span {
+            Some(span) => format!("{}:{}:{}", self.source_file, span.line, span.column),
+            None => self.source_file.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Config {
-    pub includes: Option<Vec<String>>,
+    pub includes: Option<Vec<Include>>,
     pub defaults: Option<Defaults>,
+    pub vars: Option<Vars>,
+    pub templates: Option<Templates>,
     pub steps: Option<Vec<Step>>,
 }
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use indexmap::IndexSet;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::config::expr;
+use crate::config::expr::Expr;
+use crate::config::{PackageSource, Script, Step};
+
+pub type Templates = HashMap<String, StepTemplate>;
+
+/// Fields shared by steps that declare `extends: <template-id>`.
+#[derive(Debug, Deserialize, JsonSchema, Default, Clone)]
+pub struct StepTemplate {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, deserialize_with = "expr::os::parse_os_expr")]
+    #[schemars(with = "Option<String>")]
+    pub os: Option<Expr>,
+    #[serde(default)]
+    pub env: Vec<String>,
+    #[serde(rename = "when")]
+    pub when_script: Option<Script>,
+    pub package_source: Option<PackageSource>,
+    pub pre_script: Option<Script>,
+    #[serde(rename = "check")]
+    pub check_script: Option<Script>,
+}
+
+pub fn merge(inherited: &Templates, overrides: &Option<Templates>) -> Templates {
+    let mut merged = inherited.clone();
+    if let Some(overrides) = overrides {
+        merged.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    merged
+}
+
+/// Fills the step with fields of its template. Fields set on the step take precedence,
+/// list fields (`tags`, `env`) are combined.
+pub fn apply(step: &mut Step, templates: &Templates) -> Result<()> {
+    let Some(template_id) = step.extends.as_ref() else {
+        return Ok(());
+    };
+
+    let template = templates
+        .get(template_id)
+        .with_context(|| format!("unknown template '{template_id}'"))?;
+
+    step.tags = combine(&template.tags, &step.tags);
+    step.env = combine(&template.env, &step.env);
+
+    if step.os.is_none() {
+        step.os.clone_from(&template.os);
+    }
+    if step.when_script.is_none() {
+        step.when_script.clone_from(&template.when_script);
+    }
+    if step.package_source.is_none() {
+        step.package_source.clone_from(&template.package_source);
+    }
+    if step.pre_script.is_none() {
+        step.pre_script.clone_from(&template.pre_script);
+    }
+    if step.check_script.is_none() {
+        step.check_script.clone_from(&template.check_script);
+    }
+
+    Ok(())
+}
+
+/// Values of both lists without duplicates, the outer ones first.
+pub(crate) fn combine(outer: &[String], inner: &[String]) -> Vec<String> {
+    outer
+        .iter()
+        .chain(inner)
+        .cloned()
+        .collect::<IndexSet<String>>()
+        .into_iter()
+        .collect()
+}
@@ -1,4 +1,6 @@
-use crate::config::Step;
+use crate::config::location::format_step_error;
+use crate::config::{PackageRepo, Step};
+use crate::system::pkg::version::parse_package_spec;
 use anyhow::bail;
 use std::cmp::PartialEq;
 use std::collections::{HashMap, HashSet};
@@ -18,6 +20,9 @@ pub fn validate(steps: &[Step], mode: ValidationMode) -> anyhow::Result<()> {
 
     let mut errors: Vec<String> = Vec::new();
     check_unique_id(steps, &mut errors);
+    check_package_versions(steps, &mut errors);
+    check_repositories(steps, &mut errors);
+    check_downloads(steps, &mut errors);
     if mode == ValidationMode::Full {
         check_provides_requires(steps, &mut errors);
     }
@@ -35,7 +40,16 @@ fn check_unique_id(steps: &[Step], errors: &mut Vec<String>) {
         if let Some(&duplicate_idx) = steps_id.get(&step.id) {
             let duplicate = &steps[duplicate_idx];
 
-            if step.source_file == duplicate.source_file {
+            if step.span.is_some() {
+                errors.push(format_step_error(
+                    step,
+                    &format!(
+                        "duplicate step '{}', first defined at {}",
+                        step.id,
+                        duplicate.location()
+                    ),
+                ));
+            } else if step.source_file == duplicate.source_file {
                 errors.push(format!(
                     "duplicate step '{}' in file '{}'",
                     step.id,
@@ -57,6 +71,80 @@ fn check_unique_id(steps: &[Step], errors: &mut Vec<String>) {
     }
 }
 
+fn check_package_versions(steps: &[Step], errors: &mut Vec<String>) {
+    for step in steps {
+        for pkg in &step.packages {
+            if let Err(err) = parse_package_spec(pkg) {
+                errors.push(format_step_error(
+                    step,
+                    &format!("step '{}': package '{}': {}", step.id, pkg, err),
+                ));
+            }
+        }
+    }
+}
+
+fn check_repositories(steps: &[Step], errors: &mut Vec<String>) {
+    let is_simple_name = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+
+    for step in steps {
+        for repo in &step.repositories {
+            // names end up in file names and config sections
+            let valid = match repo {
+                PackageRepo::Copr { name } => {
+                    name.split_once('/').is_some_and(|(owner, project)| {
+                        is_simple_name(owner.trim_start_matches('@')) && is_simple_name(project)
+                    })
+                }
+                _ => is_simple_name(repo.name()),
+            };
+            if !valid {
+                errors.push(format_step_error(
+                    step,
+                    &format!(
+                        "step '{}': invalid repository name '{}'",
+                        step.id,
+                        repo.name()
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+fn check_downloads(steps: &[Step], errors: &mut Vec<String>) {
+    for step in steps {
+        for download in &step.downloads {
+            if download.sha256.len() != 64
+                || !download.sha256.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                errors.push(format_step_error(
+                    step,
+                    &format!(
+                        "step '{}': download '{}': sha256 must be 64 hexadecimal characters",
+                        step.id, download.url
+                    ),
+                ));
+            }
+            // extracted files keep the modes stored in the archive
+            if download.extract.is_some() && download.mode.is_some() {
+                errors.push(format_step_error(
+                    step,
+                    &format!(
+                        "step '{}': download '{}': mode can't be set for extracted archives",
+                        step.id, download.url
+                    ),
+                ));
+            }
+        }
+    }
+}
+
 fn check_provides_requires(steps: &[Step], errors: &mut Vec<String>) {
     let available_provides: HashMap<String, Vec<String>> = steps
         .iter()
@@ -70,9 +158,9 @@ fn check_provides_requires(steps: &[Step], errors: &mut Vec<String>) {
         let mut seen_provides = HashSet::new();
         for provide in &step.provides {
             if !seen_provides.insert(provide.clone()) {
-                errors.push(format!(
-                    "step '{}': duplicated provide '{}'",
-                    step.id, provide
+                errors.push(format_step_error(
+                    step,
+                    &format!("step '{}': duplicated provide '{}'", step.id, provide),
                 ));
             }
         }
@@ -84,11 +172,17 @@ fn check_provides_requires(steps: &[Step], errors: &mut Vec<String>) {
             let req = &require.id;
 
             if !seen_requires.insert(req.clone()) {
-                errors.push(format!("step '{}': duplicated require '{}'", step.id, req));
+                errors.push(format_step_error(
+                    step,
+                    &format!("step '{}': duplicated require '{}'", step.id, req),
+                ));
             }
 
             if seen_provides.contains(req) {
-                errors.push(format!("step '{}': self-reference on '{}'", step.id, req));
+                errors.push(format_step_error(
+                    step,
+                    &format!("step '{}': self-reference on '{}'", step.id, req),
+                ));
             }
 
             if !available_provides.contains_key(req) {
@@ -97,10 +191,13 @@ fn check_provides_requires(steps: &[Step], errors: &mut Vec<String>) {
         }
 
         if !unknown_requires.is_empty() {
-            errors.push(format!(
-                "step '{}': unknown requirements: {}",
-                step.id,
-                unknown_requires.join(", ")
+            errors.push(format_step_error(
+                step,
+                &format!(
+                    "step '{}': unknown requirements: {}",
+                    step.id,
+                    unknown_requires.join(", ")
+                ),
             ));
         }
     }
@@ -115,8 +212,9 @@ fn get_file_name(full_path: &str) -> &str {
 
 #[cfg(test)]
 mod tests {
+    use crate::config::location::Span;
     use crate::config::validate::validate;
-    use crate::config::{Require, Step, ValidationMode};
+    use crate::config::{ArchiveFormat, Download, PackageRepo, Require, Step, ValidationMode};
 
     fn make_step(id: &str, requires: Vec<&str>, provides: Vec<&str>) -> Step {
         Step {
@@ -186,6 +284,32 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_duplicate_step_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.yaml");
+        std::fs::write(&file, "steps:\n  - id: step1\n  - id: step1\n").unwrap();
+        let file = file.to_str().unwrap().to_string();
+
+        let mut first = make_step("step1", vec![], vec![]);
+        first.source_file.clone_from(&file);
+        first.span = Some(Span { line: 2, column: 3 });
+        let mut second = first.clone();
+        second.span = Some(Span { line: 3, column: 3 });
+
+        let err_str = validate(&[first, second], ValidationMode::IdIntegrity)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err_str.contains(&format!(
+                "{file}:3:3: duplicate step 'step1', first defined at {file}:2:3\n"
+            )),
+            "unexpected err: {}",
+            err_str
+        );
+        assert!(err_str.contains("3 |   - id: step1"), "unexpected err: {}", err_str);
+    }
+
     #[test]
     fn test_unknown_requires() {
         let steps = vec![
@@ -206,4 +330,87 @@ mod tests {
         let result = validate(&steps, ValidationMode::IdIntegrity);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_invalid_package_version() {
+        let mut step = make_step("step1", vec![], vec![]);
+        step.packages = vec!["node@>=20".to_string(), "ripgrep@>=".to_string()];
+        let result = validate(&[step], ValidationMode::IdIntegrity);
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("package 'ripgrep@>=': invalid version constraint '>='")
+        );
+    }
+
+    #[test]
+    fn test_invalid_repository_name() {
+        let mut step = make_step("step1", vec![], vec![]);
+        step.repositories = vec![
+            PackageRepo::Copr {
+                name: "atim/lazygit".to_string(),
+            },
+            PackageRepo::Copr {
+                name: "lazygit".to_string(),
+            },
+            PackageRepo::Scoop {
+                name: "../extras".to_string(),
+                url: None,
+            },
+        ];
+        let result = validate(&[step], ValidationMode::IdIntegrity);
+        let err = result.unwrap_err().to_string();
+        assert!(!err.contains("'atim/lazygit'"), "{err}");
+        assert!(err.contains("invalid repository name 'lazygit'"), "{err}");
+        assert!(err.contains("invalid repository name '../extras'"), "{err}");
+    }
+
+    #[test]
+    fn test_download_mode_with_extract() {
+        let mut step = make_step("step1", vec![], vec![]);
+        step.downloads = vec![Download {
+            url: "https://example.com/tool.tar.gz".to_string(),
+            dest: "~/.local/opt/tool".to_string(),
+            sha256: "a".repeat(64),
+            extract: Some(ArchiveFormat::TarGz),
+            mode: Some(0o755),
+        }];
+        let result = validate(&[step], ValidationMode::IdIntegrity);
+        let err = result.unwrap_err().to_string();
+        assert!(!err.contains("sha256"), "{err}");
+        assert!(
+            err.contains(
+                "download 'https://example.com/tool.tar.gz': mode can't be set for extracted archives"
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_download_sha256_format() {
+        let mut step = make_step("step1", vec![], vec![]);
+        let download = |sha256: String| Download {
+            url: "https://example.com/tool".to_string(),
+            dest: "~/.local/bin/tool".to_string(),
+            sha256,
+            extract: None,
+            mode: None,
+        };
+        step.downloads = vec![download("A".repeat(64))];
+        assert!(validate(&[step.clone()], ValidationMode::IdIntegrity).is_ok());
+
+        for sha256 in ["abc".to_string(), "g".repeat(64), "a".repeat(65)] {
+            step.downloads = vec![download(sha256)];
+            let result = validate(&[step.clone()], ValidationMode::IdIntegrity);
+            let err = result.unwrap_err().to_string();
+            assert!(
+                err.contains(
+                    "download 'https://example.com/tool': sha256 must be 64 hexadecimal characters"
+                ),
+                "{err}"
+            );
+        }
+    }
 }
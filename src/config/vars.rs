@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, bail};
+
+use crate::config::{FileEditAction, PackageRepo, Script, Step};
+
+pub type Vars = HashMap<String, String>;
+
+pub fn merge(inherited: &Vars, overrides: &Option<Vars>) -> Vars {
+    let mut merged = inherited.clone();
+    if let Some(overrides) = overrides {
+        merged.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    merged
+}
+
+/// Replaces every `{{ name }}` placeholder with the value of the variable `name`.
+///
+/// Placeholders whose content is not a valid variable name (e.g. Go templates like
+/// `{{.State.Status}}`) are left untouched, so they can still be used in scripts.
+pub fn interpolate(input: &str, vars: &Vars) -> Result<String> {
+    replace_placeholders(input, vars, is_var_name)
+}
+
+/// Renders a template file. Works like [`interpolate`], but names can be dotted (`env.HOME`).
+pub fn render(input: &str, vars: &Vars) -> Result<String> {
+    replace_placeholders(input, vars, |name| name.split('.').all(is_var_name))
+}
+
+fn replace_placeholders(input: &str, vars: &Vars, is_name: fn(&str) -> bool) -> Result<String> {
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+
+        let end = start + 2 + len + 2;
+        let name = rest[start + 2..end - 2].trim();
+        result.push_str(&rest[..start]);
+
+        if is_name(name) {
+            match vars.get(name) {
+                Some(value) => result.push_str(value),
+                None => bail!("unknown variable '{name}'"),
+            }
+        } else {
+            result.push_str(&rest[start..end]);
+        }
+
+        rest = &rest[end..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+pub fn interpolate_step(step: &mut Step, vars: &Vars) -> Result<()> {
+    let interpolate_script = |script: &mut Option<Script>, name: &str| -> Result<()> {
+        if let Some(script) = script {
+            script.code = interpolate(&script.code, vars).context(format!("in {name}"))?;
+        }
+        Ok(())
+    };
+
+    interpolate_script(&mut step.when_script, "when-script")?;
+    interpolate_script(&mut step.pre_script, "pre-script")?;
+    interpolate_script(&mut step.script, "script")?;
+    interpolate_script(&mut step.check_script, "check-script")?;
+    interpolate_script(&mut step.undo_script, "undo-script")?;
+
+    for require in &mut step.requires {
+        let name = format!("requirement '{}' when-script", require.id);
+        interpolate_script(&mut require.when_script, &name)?;
+    }
+
+    for pkg in &mut step.packages {
+        *pkg = interpolate(pkg, vars).context("in packages")?;
+    }
+    for pkg in &mut step.absent_packages {
+        *pkg = interpolate(pkg, vars).context("in absent_packages")?;
+    }
+
+    for env in &mut step.env {
+        *env = interpolate(env, vars).context("in env")?;
+    }
+
+    for link in &mut step.links {
+        link.src = interpolate(&link.src, vars).context("in links")?;
+        link.dest = interpolate(&link.dest, vars).context("in links")?;
+    }
+
+    for file in &mut step.files {
+        file.src = interpolate(&file.src, vars).context("in files")?;
+        file.dest = interpolate(&file.dest, vars).context("in files")?;
+    }
+
+    for download in &mut step.downloads {
+        download.url = interpolate(&download.url, vars).context("in downloads")?;
+        download.dest = interpolate(&download.dest, vars).context("in downloads")?;
+        download.sha256 = interpolate(&download.sha256, vars).context("in downloads")?;
+    }
+
+    for file_edit in &mut step.edit_file {
+        file_edit.path = interpolate(&file_edit.path, vars).context("in edit_file")?;
+        match &mut file_edit.action {
+            FileEditAction::EnsureLine(line) | FileEditAction::RemoveLine(line) => {
+                *line = interpolate(line, vars).context("in edit_file")?;
+            }
+            FileEditAction::Block(block) => {
+                block.content = interpolate(&block.content, vars).context("in edit_file")?;
+            }
+        }
+    }
+
+    for repo in &mut step.git {
+        repo.repo = interpolate(&repo.repo, vars).context("in git")?;
+        repo.dest = interpolate(&repo.dest, vars).context("in git")?;
+        if let Some(rev) = &mut repo.rev {
+            *rev = interpolate(rev, vars).context("in git")?;
+        }
+    }
+
+    for repo in &mut step.repositories {
+        let fields = match repo {
+            PackageRepo::Apt {
+                uri, suite, key, ..
+            } => vec![uri, suite, key],
+            PackageRepo::Copr { .. } | PackageRepo::Scoop { url: None, .. } => vec![],
+            PackageRepo::Flatpak { url, .. } | PackageRepo::Scoop { url: Some(url), .. } => {
+                vec![url]
+            }
+            PackageRepo::Pacman { server, .. } => vec![server],
+        };
+        for field in fields {
+            *field = interpolate(field, vars).context("in repositories")?;
+        }
+    }
+
+    step.vars = vars.clone();
+    Ok(())
+}
+
+fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vars {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let vars = vars(&[("name", "world"), ("version", "14")]);
+
+        let cases = vec![
+            ("hello {{ name }}", "hello world"),
+            ("hello {{name}}", "hello world"),
+            ("{{name}}-{{ version }}", "world-14"),
+            ("no vars", "no vars"),
+            ("unclosed {{ name", "unclosed {{ name"),
+            (
+                "docker inspect -f '{{.State.Status}}'",
+                "docker inspect -f '{{.State.Status}}'",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                interpolate(input, &vars).unwrap(),
+                expected,
+                "testing {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_interpolate_unknown_var() {
+        let err = interpolate("{{ missing }}", &Vars::new()).unwrap_err();
+        assert_eq!(err.to_string(), "unknown variable 'missing'");
+    }
+
+    #[test]
+    fn test_render_dotted_names() {
+        let vars = vars(&[("env.HOME", "/home/user"), ("name", "world")]);
+
+        assert_eq!(
+            render("{{ env.HOME }}/{{ name }} {{.State}}", &vars).unwrap(),
+            "/home/user/world {{.State}}"
+        );
+        assert_eq!(
+            interpolate("{{ env.HOME }}", &vars).unwrap(),
+            "{{ env.HOME }}"
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let inherited = vars(&[("a", "1"), ("b", "2")]);
+        let merged = merge(&inherited, &Some(vars(&[("b", "3"), ("c", "4")])));
+
+        assert_eq!(merged, vars(&[("a", "1"), ("b", "3"), ("c", "4")]));
+        assert_eq!(merge(&inherited, &None), inherited);
+    }
+}
@@ -1,7 +1,7 @@
 use std::io::Write;
 
 use anyhow::Result;
-use commands::{completion, list_steps, list_tags, resume, run};
+use commands::{completion, list_steps, list_tags, resume, rollback, run, schema, upgrade};
 
 pub mod cli;
 pub mod commands;
@@ -18,13 +18,17 @@ pub use logging::setup_tracing;
 pub use logging::test::run_with_tracing;
 
 pub fn run(cli: crate::cli::Cli, out: &mut impl Write) -> Result<()> {
+    system::pkg::cache::configure(cli.cache_ttl, cli.refresh);
     match cli.command {
         cli::Commands::Run(args) => run::handle(args.normalize()?, out)?,
         cli::Commands::Resume(args) => resume::handle(args, out)?,
+        cli::Commands::Rollback(args) => rollback::handle(args.normalize()?, out)?,
+        cli::Commands::Upgrade(args) => upgrade::handle(args.normalize()?, out)?,
         cli::Commands::Validate(args) => validate::handle(args.normalize()?, out)?,
         cli::Commands::ListSteps(args) => list_steps::handle(args.normalize()?, out)?,
         cli::Commands::ListTags(args) => list_tags::handle(args.normalize()?, out)?,
         cli::Commands::Completion(args) => completion::handle(args, out)?,
+        cli::Commands::Schema(args) => schema::handle(args, out)?,
     }
     Ok(())
 }
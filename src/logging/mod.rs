@@ -53,6 +53,7 @@ pub enum EventType {
     ScriptsCheckCompleted,
     PackagesCheckCompleted,
     PackagesInstallStarted,
+    PackageIndexRefreshStarted,
     StepCheckStarted,
     StepCheckFinished,
     FilterCompleted,
@@ -60,6 +61,23 @@ pub enum EventType {
     StepRunFinished,
     CompletedStepSkipped,
     CachePopulationCompleted,
+    AttemptStarted,
+    StepFailed,
+    StepBlocked,
+    RunFailed,
+    LinksCreationStarted,
+    FilesRenderingStarted,
+    DownloadsStarted,
+    GitSyncStarted,
+    RepositoriesAddStarted,
+    FileEditsStarted,
+    PackagesRemoveStarted,
+    StepUndoStarted,
+    StepUndoFinished,
+    NothingToUndo,
+    RollbackCompleted,
+    PackagesUpgradeStarted,
+    UpgradeCompleted,
     Unknown,
 }
 
@@ -80,8 +98,26 @@ impl EventType {
             EventType::StepRunStarted => "step_run_started",
             EventType::StepRunFinished => "step_run_finished",
             EventType::PackagesInstallStarted => "packages_install_started",
+            EventType::PackageIndexRefreshStarted => "package_index_refresh_started",
             EventType::ScriptStarted => "script_started",
             EventType::CachePopulationCompleted => "cache_population_completed",
+            EventType::AttemptStarted => "attempt_started",
+            EventType::StepFailed => "step_failed",
+            EventType::StepBlocked => "step_blocked",
+            EventType::RunFailed => "run_failed",
+            EventType::LinksCreationStarted => "links_creation_started",
+            EventType::FilesRenderingStarted => "files_rendering_started",
+            EventType::DownloadsStarted => "downloads_started",
+            EventType::GitSyncStarted => "git_sync_started",
+            EventType::RepositoriesAddStarted => "repositories_add_started",
+            EventType::FileEditsStarted => "file_edits_started",
+            EventType::PackagesRemoveStarted => "packages_remove_started",
+            EventType::StepUndoStarted => "step_undo_started",
+            EventType::StepUndoFinished => "step_undo_finished",
+            EventType::NothingToUndo => "nothing_to_undo",
+            EventType::RollbackCompleted => "rollback_completed",
+            EventType::PackagesUpgradeStarted => "packages_upgrade_started",
+            EventType::UpgradeCompleted => "upgrade_completed",
         }
     }
 }
@@ -309,6 +345,13 @@ where
                     _ = writeln!(out, "✅ {progress} Step '{current_step_id}' completed")
                 }
             }
+            EventType::PackageIndexRefreshStarted => {
+                _ = writeln!(
+                    out,
+                    "🔄 {progress} Refreshing package index: {}",
+                    v.fields.get("manager").unwrap_or(&"?".into())
+                );
+            }
             EventType::PackagesInstallStarted => {
                 _ = writeln!(
                     out,
@@ -316,6 +359,77 @@ where
                     v.fields.get("packages").unwrap_or(&"?".into())
                 );
             }
+            EventType::LinksCreationStarted => {
+                _ = writeln!(
+                    out,
+                    "🔗 {progress} Creating links: {}",
+                    v.fields.get("links").unwrap_or(&"?".into())
+                );
+            }
+            EventType::FilesRenderingStarted => {
+                _ = writeln!(
+                    out,
+                    "📝 {progress} Rendering files: {}",
+                    v.fields.get("files").unwrap_or(&"?".into())
+                );
+            }
+            EventType::DownloadsStarted => {
+                _ = writeln!(
+                    out,
+                    "📥 {progress} Downloading: {}",
+                    v.fields.get("downloads").unwrap_or(&"?".into())
+                );
+            }
+            EventType::GitSyncStarted => {
+                _ = writeln!(
+                    out,
+                    "🌿 {progress} Syncing git repositories: {}",
+                    v.fields.get("repos").unwrap_or(&"?".into())
+                );
+            }
+            EventType::RepositoriesAddStarted => {
+                _ = writeln!(
+                    out,
+                    "📚 {progress} Adding package repositories: {}",
+                    v.fields.get("repositories").unwrap_or(&"?".into())
+                );
+            }
+            EventType::FileEditsStarted => {
+                _ = writeln!(
+                    out,
+                    "✏️ {progress} Editing files: {}",
+                    v.fields.get("files").unwrap_or(&"?".into())
+                );
+            }
+            EventType::PackagesRemoveStarted => {
+                _ = writeln!(
+                    out,
+                    "🗑️ {progress} Removing packages: {}",
+                    v.fields.get("packages").unwrap_or(&"?".into())
+                );
+            }
+            EventType::StepUndoStarted => {
+                _ = writeln!(out, "↩️ {progress} Undoing step '{current_step_id}'...")
+            }
+            EventType::StepUndoFinished => {
+                _ = writeln!(out, "✅ {progress} Step '{current_step_id}' undone")
+            }
+            EventType::NothingToUndo => {
+                _ = writeln!(
+                    out,
+                    "➖ {progress} Step '{current_step_id}' has nothing to undo, skipping"
+                )
+            }
+            EventType::RollbackCompleted => _ = writeln!(out, "✅ Rollback completed"),
+            EventType::PackagesUpgradeStarted => {
+                _ = writeln!(
+                    out,
+                    "⬆️ Upgrading packages ({}): {}",
+                    v.fields.get("manager").unwrap_or(&"?".into()),
+                    v.fields.get("packages").unwrap_or(&"?".into())
+                );
+            }
+            EventType::UpgradeCompleted => _ = writeln!(out, "✅ Upgrade completed"),
             EventType::DryRunPlanGenerated => {
                 _ = writeln!(
                     out,
@@ -334,6 +448,33 @@ where
                     _ = writeln!(out, "✅ Run completed")
                 }
             }
+            EventType::AttemptStarted => {
+                let kind = v.fields.get("kind").map(|s| s.as_str()).unwrap_or("?");
+                let attempt = v.fields.get("attempt").map(|s| s.as_str()).unwrap_or("?");
+                let attempts = v.fields.get("attempts").map(|s| s.as_str()).unwrap_or("?");
+                _ = writeln!(out, "🔁 {progress} {kind}: attempt {attempt}/{attempts}");
+            }
+            EventType::StepFailed => {
+                let error = v.fields.get("error").map(|s| s.as_str()).unwrap_or("?");
+                _ = writeln!(
+                    out,
+                    "❌ {progress} Step '{current_step_id}' failed: {error}"
+                );
+            }
+            EventType::StepBlocked => {
+                let failed_deps = v.fields.get("failed_deps").map(|s| s.as_str()).unwrap_or("?");
+                _ = writeln!(
+                    out,
+                    "⏭️ {progress} Skipping step '{current_step_id}': depends on failed {failed_deps}"
+                );
+            }
+            EventType::RunFailed => {
+                let failed = v.fields.get("failed").map(|s| s.as_str()).unwrap_or("?");
+                _ = writeln!(out, "❌ Run finished with failures\n  Failed steps: {failed}");
+                if let Some(blocked) = v.fields.get("blocked").filter(|s| !s.is_empty()) {
+                    _ = writeln!(out, "  Blocked steps: {blocked}");
+                }
+            }
             EventType::CachePopulationCompleted => {
                 _ = writeln!(
                     out,
@@ -12,6 +12,8 @@ fn main() {
     let debug = match &cli.command {
         cli::Commands::Run(args) => args.debug,
         cli::Commands::Resume(args) => args.debug,
+        cli::Commands::Rollback(args) => args.debug,
+        cli::Commands::Upgrade(args) => args.debug,
         _ => false,
     };
     setup_tracing(debug);
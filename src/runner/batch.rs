@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use tracing::info;
+
+use crate::runner::pkg::{IndexRefresher, install_packages};
+use crate::runner::{Package, Step};
+
+/// Steps whose packages are installed in one transaction, with the packages each of them is missing.
+pub struct Batch<'a> {
+    pub steps: Vec<(&'a Step, Vec<&'a Package>)>,
+}
+
+impl Batch<'_> {
+    pub fn step_ids(&self) -> Vec<String> {
+        self.steps.iter().map(|(step, _)| step.id.clone()).collect()
+    }
+
+    /// Returns the packages that weren't installed at all before, by step id.
+    pub fn install(&self, refresher: &mut IndexRefresher) -> Result<Vec<(String, Vec<String>)>> {
+        let mut seen = HashSet::new();
+        let packages = self
+            .steps
+            .iter()
+            .flat_map(|(_, packages)| packages)
+            .filter(|pkg| seen.insert(pkg.spec()))
+            .map(|pkg| (*pkg).clone())
+            .collect::<Vec<Package>>();
+
+        info!(
+            "Installing packages of steps {} in one transaction",
+            self.step_ids().join(", ")
+        );
+        // a package shared by several steps belongs to the first one, as if they ran one by one
+        let mut recorded = HashSet::new();
+        let mut new_packages = Vec::new();
+        for (step, packages) in &self.steps {
+            let mut names = step.new_package_names(packages.iter().copied())?;
+            names.retain(|name| recorded.insert(name.clone()));
+            if !names.is_empty() {
+                new_packages.push((step.id.clone(), names));
+            }
+        }
+
+        let (first, _) = &self.steps[0];
+        if first.refresh_index {
+            refresher.refresh(&first.package_manager, &first.privilege);
+        }
+        install_packages(&packages, &first.package_manager, &first.privilege)?;
+        Ok(new_packages)
+    }
+}
+
+/// Collects the missing packages of `steps[start]` and of later steps that can be installed together with them:
+/// steps using the same package manager, privilege and index refresh setting without a pre-script or repositories,
+/// whose dependencies are all done.
+/// Returns `None` if there is nothing to merge.
+pub fn collect_batch<'a>(
+    steps: &'a [Step],
+    start: usize,
+    is_done: impl Fn(&str) -> bool,
+) -> Result<Option<Batch<'a>>> {
+    let first = &steps[start];
+    if !can_batch(first) {
+        return Ok(None);
+    }
+
+    let mut batch = Batch { steps: Vec::new() };
+    for (i, step) in steps.iter().enumerate().skip(start) {
+        if i > start
+            && (!can_batch(step)
+                || step.package_manager != first.package_manager
+                || step.privilege != first.privilege
+                || step.refresh_index != first.refresh_index
+                || !step.dependencies.iter().all(|dep| is_done(dep)))
+        {
+            continue;
+        }
+
+        let mut missing = Vec::new();
+        for pkg in &step.packages {
+            if !step.is_package_installed(pkg)? {
+                missing.push(pkg);
+            }
+        }
+        if !missing.is_empty() {
+            batch.steps.push((step, missing));
+        } else if i == start {
+            return Ok(None);
+        }
+    }
+
+    if batch.steps.len() < 2 {
+        return Ok(None);
+    }
+    Ok(Some(batch))
+}
+
+fn can_batch(step: &Step) -> bool {
+    // repositories are added after the pre-script, right before the packages of the step are installed
+    !step.packages.is_empty() && step.pre_script.is_none() && step.repositories.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EnvGuard;
+    use crate::system::pkg::PackageManager;
+    use crate::system::privilege::Privilege;
+    use serial_test::serial;
+
+    fn step(id: &str, manager: PackageManager, packages: &[&str], deps: &[&str]) -> Step {
+        Step {
+            id: id.to_string(),
+            package_manager: manager,
+            packages: packages
+                .iter()
+                .map(|name| Package {
+                    name: name.to_string(),
+                    version: None,
+                    used_alias: false,
+                })
+                .collect(),
+            dependencies: deps.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_collect_batch() {
+        let _guard = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+        let steps = vec![
+            step("git", PackageManager::Apt, &["git"], &[]),
+            step("rust", PackageManager::Cargo, &["ripgrep"], &[]),
+            step("curl", PackageManager::Apt, &["curl", "git"], &[]),
+            step("after-git", PackageManager::Apt, &["fd-find"], &["git"]),
+            step("after-setup", PackageManager::Apt, &["jq"], &["setup"]),
+        ];
+
+        // "setup" already ran, "git" runs first in the batch
+        let batch = collect_batch(&steps, 0, |dep| dep == "setup")
+            .unwrap()
+            .unwrap();
+        assert_eq!(batch.step_ids(), vec!["git", "curl", "after-setup"]);
+
+        assert!(collect_batch(&steps, 1, |_| true).unwrap().is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_collect_batch_keeps_install_settings_apart() {
+        let _guard = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+        let steps = vec![
+            step("git", PackageManager::Apt, &["git"], &[]),
+            Step {
+                privilege: Privilege::Doas,
+                ..step("curl", PackageManager::Apt, &["curl"], &[])
+            },
+            Step {
+                refresh_index: true,
+                ..step("jq", PackageManager::Apt, &["jq"], &[])
+            },
+            step("fd", PackageManager::Apt, &["fd-find"], &[]),
+        ];
+
+        let batch = collect_batch(&steps, 0, |_| true).unwrap().unwrap();
+        assert_eq!(batch.step_ids(), vec!["git", "fd"]);
+    }
+}
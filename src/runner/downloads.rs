@@ -0,0 +1,277 @@
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+use tracing::info;
+
+use crate::config;
+use crate::config::ArchiveFormat;
+use crate::logging::EventType;
+use crate::utils::file::{resolve_path, set_mode};
+
+/// File in an extracted `dest` directory holding the checksum of the unpacked archive.
+const EXTRACTED_MARKER: &str = ".mepris-sha256";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Whole body, not per read, so it leaves room for large files on slow connections.
+const BODY_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    pub url: String,
+    pub dest: PathBuf,
+    pub sha256: String,
+    pub extract: Option<ArchiveFormat>,
+    pub mode: Option<u32>,
+}
+
+impl Download {
+    pub fn from(download: &config::Download, step_dir: &Path) -> Self {
+        Download {
+            url: download.url.clone(),
+            dest: resolve_path(&download.dest, step_dir),
+            sha256: download.sha256.to_lowercase(),
+            extract: download.extract,
+            mode: download.mode,
+        }
+    }
+
+    /// Whether `dest` already has the expected checksum. For extracted archives the checksum
+    /// of the archive is remembered in the `dest` directory.
+    pub fn is_downloaded(&self) -> bool {
+        match self.extract {
+            Some(_) => fs::read_to_string(self.dest.join(EXTRACTED_MARKER))
+                .is_ok_and(|hash| hash.trim() == self.sha256),
+            None => fs::File::open(&self.dest)
+                .and_then(sha256)
+                .is_ok_and(|hash| hash == self.sha256),
+        }
+    }
+}
+
+pub fn fetch_downloads(downloads: &[Download]) -> Result<()> {
+    let pending = downloads
+        .iter()
+        .filter(|d| !d.is_downloaded())
+        .collect::<Vec<&Download>>();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        event_type = %EventType::DownloadsStarted.as_str(),
+        downloads = pending
+            .iter()
+            .map(|d| d.url.clone())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+
+    for download in pending {
+        fetch(download).with_context(|| format!("failed to download '{}'", download.url))?;
+    }
+    Ok(())
+}
+
+fn fetch(download: &Download) -> Result<()> {
+    let parent = match download.extract {
+        Some(_) => download.dest.as_path(),
+        None => download.dest.parent().unwrap_or(Path::new(".")),
+    };
+    fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create directory '{}'", parent.display()))?;
+
+    let mut response = agent()
+        .get(&download.url)
+        .call()
+        .context("request failed")?;
+    let mut temp =
+        temp_file(parent, download.extract.is_none()).context("failed to create temp file")?;
+    io::copy(&mut response.body_mut().as_reader(), &mut temp).context("failed to read response")?;
+
+    temp.seek(SeekFrom::Start(0))?;
+    let hash = sha256(temp.as_file())?;
+    if hash != download.sha256 {
+        bail!(
+            "checksum mismatch: expected {}, got {hash}",
+            download.sha256
+        );
+    }
+
+    match download.extract {
+        Some(format) => {
+            temp.seek(SeekFrom::Start(0))?;
+            extract(temp.as_file(), format, &download.dest)?;
+            fs::write(download.dest.join(EXTRACTED_MARKER), &hash)
+                .context("failed to write checksum marker")?;
+        }
+        None => {
+            temp.persist(&download.dest)
+                .context("failed to save downloaded file")?;
+            if let Some(mode) = download.mode {
+                set_mode(&download.dest, mode)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// HTTP agent with the download timeouts, shared by everything that fetches over the network.
+pub(super) fn agent() -> ureq::Agent {
+    let config = ureq::Agent::config_builder()
+        .timeout_connect(Some(CONNECT_TIMEOUT))
+        .timeout_recv_response(Some(RESPONSE_TIMEOUT))
+        .timeout_recv_body(Some(BODY_TIMEOUT))
+        .build();
+    ureq::Agent::new_with_config(config)
+}
+
+/// Temp file in `dir`. A file that becomes `dest` gets the mode of new files (0666 minus the
+/// umask) instead of 0600; archives are removed after extraction.
+fn temp_file(dir: &Path, is_dest: bool) -> io::Result<NamedTempFile> {
+    let mut builder = tempfile::Builder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if is_dest {
+            builder.permissions(fs::Permissions::from_mode(0o666));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = is_dest;
+    builder.tempfile_in(dir)
+}
+
+fn extract(archive: &fs::File, format: ArchiveFormat, dest: &Path) -> Result<()> {
+    match format {
+        ArchiveFormat::TarGz => tar::Archive::new(GzDecoder::new(archive))
+            .unpack(dest)
+            .context("failed to extract tar.gz archive"),
+        ArchiveFormat::Zip => zip::ZipArchive::new(archive)
+            .and_then(|mut zip| zip.extract(dest))
+            .context("failed to extract zip archive"),
+    }
+}
+
+fn sha256(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+    use tempfile::tempdir;
+
+    /// Serves `body` to a single request and returns the URL to fetch it from.
+    fn serve_once(body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            _ = stream.read(&mut request);
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        });
+        format!("http://{addr}/file")
+    }
+
+    fn download(url: &str, dest: &Path, body: &[u8], extract: Option<ArchiveFormat>) -> Download {
+        Download {
+            url: url.to_string(),
+            dest: dest.to_path_buf(),
+            sha256: sha256(body).unwrap(),
+            extract,
+            mode: None,
+        }
+    }
+
+    #[test]
+    fn test_fetch_downloads() {
+        let dir = tempdir().unwrap();
+        let url = serve_once(b"binary".to_vec());
+        let download = download(&url, &dir.path().join("bin/tool"), b"binary", None);
+
+        assert!(!download.is_downloaded());
+        fetch_downloads(std::slice::from_ref(&download)).unwrap();
+
+        assert!(download.is_downloaded());
+        assert_eq!(fs::read(&download.dest).unwrap(), b"binary");
+
+        // completed downloads are not fetched again, the server only answers once
+        fetch_downloads(std::slice::from_ref(&download)).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_fetch_uses_default_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let url = serve_once(b"config".to_vec());
+        let download = download(&url, &dir.path().join("config"), b"config", None);
+        fetch_downloads(std::slice::from_ref(&download)).unwrap();
+
+        // same mode as any new file, given the umask of the test
+        let other = dir.path().join("other");
+        fs::write(&other, "").unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode(&download.dest), mode(&other));
+    }
+
+    #[test]
+    fn test_fetch_checksum_mismatch() {
+        let dir = tempdir().unwrap();
+        let url = serve_once(b"tampered".to_vec());
+        let download = download(&url, &dir.path().join("tool"), b"binary", None);
+
+        let err = fetch_downloads(&[download]).unwrap_err();
+
+        assert!(format!("{err:#}").contains("checksum mismatch"), "{err:#}");
+        assert!(!dir.path().join("tool").exists());
+    }
+
+    #[test]
+    fn test_fetch_extracts_tar_gz() {
+        let dir = tempdir().unwrap();
+        let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o755);
+        header.set_cksum();
+        archive
+            .append_data(&mut header, "tool/bin", &b"tool"[..])
+            .unwrap();
+        let body = archive.into_inner().unwrap().finish().unwrap();
+
+        let url = serve_once(body.clone());
+        let download = download(
+            &url,
+            &dir.path().join("opt"),
+            &body,
+            Some(ArchiveFormat::TarGz),
+        );
+        fetch_downloads(std::slice::from_ref(&download)).unwrap();
+
+        assert!(download.is_downloaded());
+        assert_eq!(fs::read(dir.path().join("opt/tool/bin")).unwrap(), b"tool");
+    }
+}
@@ -13,7 +13,16 @@ pub struct StepRun {
     pub step_completed_result: StepCompletedResult,
     pub missing_shells: Vec<String>,
     pub package_manager: Option<PackageManagerInfo>,
+    /// The package index of the manager would be refreshed before installing packages.
+    pub refresh_index: bool,
+    pub repositories: Vec<RepositoryInfo>,
     pub packages_to_install: Vec<PackageInfo>,
+    pub packages_to_remove: Vec<PackageInfo>,
+    pub links: Vec<LinkInfo>,
+    pub files: Vec<FileInfo>,
+    pub downloads: Vec<DownloadInfo>,
+    pub git: Vec<GitRepoInfo>,
+    pub edits: Vec<FileEditInfo>,
     pub selection_reason: StepSelectionReason,
     pub dependencies: Vec<String>,
     pub dependency_of: Vec<String>,
@@ -36,6 +45,49 @@ impl fmt::Display for PackageInfo {
     }
 }
 
+#[derive(Debug)]
+pub struct RepositoryInfo {
+    pub name: String,
+    pub added: bool,
+}
+
+#[derive(Debug)]
+pub struct LinkInfo {
+    pub src: String,
+    pub dest: String,
+    pub created: bool,
+}
+
+#[derive(Debug)]
+pub struct FileInfo {
+    pub dest: String,
+    pub rendered: bool,
+    pub diff: String,
+}
+
+#[derive(Debug)]
+pub struct DownloadInfo {
+    pub url: String,
+    pub dest: String,
+    pub downloaded: bool,
+}
+
+#[derive(Debug)]
+pub struct GitRepoInfo {
+    pub repo: String,
+    pub dest: String,
+    pub rev: Option<String>,
+    pub cloned: bool,
+    pub synced: bool,
+}
+
+#[derive(Debug)]
+pub struct FileEditInfo {
+    pub path: String,
+    pub description: String,
+    pub applied: bool,
+}
+
 #[derive(Debug)]
 pub struct PackageManagerInfo {
     pub name: String,
@@ -47,10 +99,11 @@ pub struct RunPlan {
     pub steps_to_run: Vec<StepRun>,
 }
 
-pub fn run(steps: &[Step]) -> anyhow::Result<RunPlan> {
+pub fn run(steps: &[Step], refresh_index: bool) -> anyhow::Result<RunPlan> {
     let mut res = RunPlan {
         steps_to_run: vec![],
     };
+    let mut refreshed_managers = HashSet::new();
 
     for step in steps {
         let step_completed_res = step.is_completed(None)?;
@@ -64,14 +117,31 @@ pub fn run(steps: &[Step]) -> anyhow::Result<RunPlan> {
             ..Default::default()
         };
 
-        if !step.packages.is_empty() {
+        if !step.packages.is_empty() || !step.absent_packages.is_empty() {
             let package_manager = step.package_manager.clone();
 
             step_dry_run.package_manager = Some(PackageManagerInfo {
                 name: package_manager.to_string(),
                 installed: package_manager.is_available(),
             });
+        }
+
+        for repo in &step.repositories {
+            step_dry_run.repositories.push(RepositoryInfo {
+                name: repo.to_string(),
+                added: repo.is_added()?,
+            });
+        }
+
+        // like the runner, only before the first installation of each manager
+        step_dry_run.refresh_index = refresh_index
+            && step.refresh_index
+            && !step.packages.is_empty()
+            && step_completed_res != StepCompletedResult::Completed
+            && step.package_manager.has_index()
+            && refreshed_managers.insert(step.package_manager.clone());
 
+        if !step.packages.is_empty() {
             let not_installed_pkgs: HashSet<String> = match &step_completed_res {
                 StepCompletedResult::NotInstalledPackages(pkgs) => {
                     HashSet::from_iter(pkgs.iter().cloned())
@@ -83,7 +153,7 @@ pub fn run(steps: &[Step]) -> anyhow::Result<RunPlan> {
                 .packages
                 .iter()
                 .map(|p| PackageInfo {
-                    name: p.name.clone(),
+                    name: p.spec(),
                     use_alias: p.used_alias,
                     installed: step_completed_res
                         != StepCompletedResult::NotInstalledPackageManager
@@ -92,6 +162,67 @@ pub fn run(steps: &[Step]) -> anyhow::Result<RunPlan> {
                 .collect();
         }
 
+        if !step.absent_packages.is_empty() {
+            let installed_pkgs = step.installed_absent_packages()?;
+            step_dry_run.packages_to_remove = step
+                .absent_packages
+                .iter()
+                .map(|p| PackageInfo {
+                    name: p.name.clone(),
+                    use_alias: p.used_alias,
+                    installed: installed_pkgs.contains(&p.name),
+                })
+                .collect();
+        }
+
+        step_dry_run.links = step
+            .links
+            .iter()
+            .map(|link| LinkInfo {
+                src: link.src.display().to_string(),
+                dest: link.dest.display().to_string(),
+                created: link.is_created(),
+            })
+            .collect();
+
+        for file in &step.files {
+            step_dry_run.files.push(FileInfo {
+                dest: file.dest.display().to_string(),
+                rendered: file.is_rendered()?,
+                diff: file.diff()?,
+            });
+        }
+
+        step_dry_run.downloads = step
+            .downloads
+            .iter()
+            .map(|download| DownloadInfo {
+                url: download.url.clone(),
+                dest: download.dest.display().to_string(),
+                downloaded: download.is_downloaded(),
+            })
+            .collect();
+
+        for repo in &step.git {
+            step_dry_run.git.push(GitRepoInfo {
+                repo: repo.repo.clone(),
+                dest: repo.dest.display().to_string(),
+                rev: repo.rev.clone(),
+                cloned: repo.is_cloned(),
+                synced: repo.is_synced()?,
+            });
+        }
+
+        step_dry_run.edits = step
+            .edits
+            .iter()
+            .map(|edit| FileEditInfo {
+                path: edit.path.display().to_string(),
+                description: edit.to_string(),
+                applied: edit.is_applied(),
+            })
+            .collect();
+
         let not_available_shells = step
             .all_used_shells()
             .into_iter()
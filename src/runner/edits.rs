@@ -0,0 +1,289 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tracing::info;
+
+use crate::config;
+use crate::config::FileEditAction;
+use crate::logging::EventType;
+use crate::system::privilege::Privilege;
+use crate::utils::file::resolve_path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEdit {
+    pub path: PathBuf,
+    pub action: FileEditAction,
+}
+
+impl FileEdit {
+    pub fn from(edit: &config::FileEdit, step_dir: &Path) -> Self {
+        FileEdit {
+            path: resolve_path(&edit.path, step_dir),
+            action: edit.action.clone(),
+        }
+    }
+
+    /// Whether the file is already in the desired state. A missing file counts as empty.
+    pub fn is_applied(&self) -> bool {
+        let content = fs::read_to_string(&self.path).unwrap_or_default();
+        edit(&content, &self.action) == content
+    }
+}
+
+impl fmt::Display for FileEdit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.action {
+            FileEditAction::EnsureLine(line) => write!(f, "ensure line '{line}'"),
+            FileEditAction::RemoveLine(line) => write!(f, "remove line '{line}'"),
+            FileEditAction::Block(block) => write!(f, "block '{}'", block.id),
+        }
+    }
+}
+
+/// Applies the pending edits. Files the user can't write are written through `privilege`.
+pub fn apply_edits(edits: &[FileEdit], privilege: &Privilege) -> Result<()> {
+    let pending = edits
+        .iter()
+        .filter(|e| !e.is_applied())
+        .collect::<Vec<&FileEdit>>();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        event_type = %EventType::FileEditsStarted.as_str(),
+        files = pending
+            .iter()
+            .map(|e| e.path.display().to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+
+    for file_edit in pending {
+        apply_edit(file_edit, privilege)
+            .with_context(|| format!("failed to edit file '{}'", file_edit.path.display()))?;
+    }
+    Ok(())
+}
+
+fn apply_edit(file_edit: &FileEdit, privilege: &Privilege) -> Result<()> {
+    let content = match fs::read_to_string(&file_edit.path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).context("failed to read file"),
+    };
+    let content = edit(&content, &file_edit.action);
+
+    match write_file(&file_edit.path, &content) {
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+            write_root_file(&file_edit.path, &content, privilege)
+        }
+        result => result.context("failed to write file"),
+    }
+}
+
+fn write_file(path: &Path, content: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)
+}
+
+/// Copies the content over the file as root. `cp` keeps the owner and mode of an existing file,
+/// new files are created with mode 0644.
+#[cfg(unix)]
+fn write_root_file(path: &Path, content: &str, privilege: &Privilege) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+
+    use tempfile::NamedTempFile;
+
+    use crate::system::privilege::run_root;
+
+    let mut temp = NamedTempFile::new().context("failed to create temp file")?;
+    temp.write_all(content.as_bytes())
+        .context("failed to write temp file")?;
+    fs::set_permissions(temp.path(), fs::Permissions::from_mode(0o644))
+        .context("failed to set temp file permissions")?;
+
+    let src = temp.path().display().to_string();
+    let dest = path.display().to_string();
+    if let Some(parent) = path.parent() {
+        run_root(privilege, &["mkdir", "-p", &parent.display().to_string()])?;
+    }
+    run_root(privilege, &["cp", &src, &dest])
+}
+
+#[cfg(not(unix))]
+fn write_root_file(path: &Path, _: &str, _: &Privilege) -> Result<()> {
+    anyhow::bail!(
+        "can't edit '{}': editing files as root is only supported on unix",
+        path.display()
+    )
+}
+
+/// Desired file content. Returns `content` unchanged if it's already in the desired state.
+fn edit(content: &str, action: &FileEditAction) -> String {
+    match action {
+        FileEditAction::EnsureLine(line) => {
+            if content.lines().any(|l| l == line) {
+                content.to_string()
+            } else {
+                append(content, &format!("{line}\n"))
+            }
+        }
+        FileEditAction::RemoveLine(line) => {
+            if content.lines().any(|l| l == line) {
+                content
+                    .lines()
+                    .filter(|l| l != line)
+                    .map(|l| format!("{l}\n"))
+                    .collect()
+            } else {
+                content.to_string()
+            }
+        }
+        FileEditAction::Block(block) => {
+            let begin = format!("# BEGIN mepris {}", block.id);
+            let end = format!("# END mepris {}", block.id);
+            let mut managed = format!("{begin}\n{}", block.content);
+            if !managed.ends_with('\n') {
+                managed.push('\n');
+            }
+            managed.push_str(&end);
+            managed.push('\n');
+
+            let lines = content.split_inclusive('\n').collect::<Vec<&str>>();
+            let is_marker = |l: &str, marker: &str| l.trim_end() == marker;
+            let start = lines.iter().position(|l| is_marker(l, &begin));
+            let finish = start.and_then(|start| {
+                lines[start..]
+                    .iter()
+                    .position(|l| is_marker(l, &end))
+                    .map(|pos| start + pos)
+            });
+
+            match (start, finish) {
+                (Some(start), Some(finish)) => {
+                    let before: String = lines[..start].concat();
+                    let after: String = lines[finish + 1..].concat();
+                    format!("{before}{managed}{after}")
+                }
+                _ => append(content, &managed),
+            }
+        }
+    }
+}
+
+fn append(content: &str, addition: &str) -> String {
+    if content.is_empty() || content.ends_with('\n') {
+        format!("{content}{addition}")
+    } else {
+        format!("{content}\n{addition}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ManagedBlock;
+    use tempfile::tempdir;
+
+    fn block(id: &str, content: &str) -> FileEditAction {
+        FileEditAction::Block(ManagedBlock {
+            id: id.to_string(),
+            content: content.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_edit() {
+        let path_line = FileEditAction::EnsureLine("export PATH=$HOME/bin:$PATH".to_string());
+        let color = FileEditAction::RemoveLine("#Color".to_string());
+
+        let cases = vec![
+            ("", &path_line, "export PATH=$HOME/bin:$PATH\n"),
+            (
+                "alias ll='ls -l'",
+                &path_line,
+                "alias ll='ls -l'\nexport PATH=$HOME/bin:$PATH\n",
+            ),
+            (
+                "export PATH=$HOME/bin:$PATH",
+                &path_line,
+                "export PATH=$HOME/bin:$PATH",
+            ),
+            ("[options]\n#Color\nColor\n", &color, "[options]\nColor\n"),
+            ("[options]\nColor", &color, "[options]\nColor"),
+        ];
+
+        for (content, action, expected) in cases {
+            assert_eq!(edit(content, action), expected, "editing {content:?}");
+        }
+    }
+
+    #[test]
+    fn test_edit_block() {
+        let aliases = block("aliases", "[alias]\n  st = status");
+
+        let added = edit("[user]\n  name = me\n", &aliases);
+        assert_eq!(
+            added,
+            "[user]\n  name = me\n# BEGIN mepris aliases\n[alias]\n  st = status\n# END mepris aliases\n"
+        );
+        assert_eq!(edit(&added, &aliases), added);
+
+        let replaced = edit(&added, &block("aliases", "[alias]\n  co = checkout\n"));
+        assert_eq!(
+            replaced,
+            "[user]\n  name = me\n# BEGIN mepris aliases\n[alias]\n  co = checkout\n# END mepris aliases\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let dir = tempdir().unwrap();
+        let edit = FileEdit {
+            path: dir.path().join("home/.bashrc"),
+            action: FileEditAction::EnsureLine("source ~/.aliases".to_string()),
+        };
+
+        assert!(!edit.is_applied());
+        apply_edits(std::slice::from_ref(&edit), &Privilege::None).unwrap();
+        apply_edits(std::slice::from_ref(&edit), &Privilege::None).unwrap();
+
+        assert!(edit.is_applied());
+        assert_eq!(
+            fs::read_to_string(&edit.path).unwrap(),
+            "source ~/.aliases\n"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_write_root_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let created = dir.path().join("etc/pacman.d/mirrorlist");
+        write_root_file(&created, "Server = a\n", &Privilege::None).unwrap();
+        assert_eq!(fs::read_to_string(&created).unwrap(), "Server = a\n");
+        assert_eq!(
+            fs::metadata(&created).unwrap().permissions().mode() & 0o777,
+            0o644
+        );
+
+        let existing = dir.path().join("sudoers");
+        fs::write(&existing, "root ALL=(ALL) ALL\n").unwrap();
+        fs::set_permissions(&existing, fs::Permissions::from_mode(0o640)).unwrap();
+        write_root_file(&existing, "me ALL=(ALL) ALL\n", &Privilege::None).unwrap();
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "me ALL=(ALL) ALL\n");
+        assert_eq!(
+            fs::metadata(&existing).unwrap().permissions().mode() & 0o777,
+            0o640
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use similar::TextDiff;
+use tracing::info;
+
+use crate::config;
+use crate::config::vars::{Vars, render};
+use crate::logging::EventType;
+use crate::system::os_info::OS_INFO;
+use crate::utils::file::{resolve_path, set_mode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateFile {
+    pub src: PathBuf,
+    pub dest: PathBuf,
+    pub mode: Option<u32>,
+    pub vars: Vars,
+}
+
+impl TemplateFile {
+    pub fn from(file: &config::TemplateFile, step_dir: &Path, vars: &Vars) -> Self {
+        TemplateFile {
+            src: resolve_path(&file.src, step_dir),
+            dest: resolve_path(&file.dest, step_dir),
+            mode: file.mode,
+            vars: vars.clone(),
+        }
+    }
+
+    /// Content of `src` with `{{ name }}`, `{{ env.NAME }}` and `{{ os.platform }}` placeholders replaced.
+    pub fn render(&self) -> Result<String> {
+        let template = fs::read_to_string(&self.src)
+            .with_context(|| format!("failed to read template '{}'", self.src.display()))?;
+        render(&template, &self.template_vars())
+            .with_context(|| format!("failed to render template '{}'", self.src.display()))
+    }
+
+    /// Whether `dest` already has the rendered content and the requested mode.
+    pub fn is_rendered(&self) -> Result<bool> {
+        let rendered = self.render()?;
+        let Ok(current) = fs::read_to_string(&self.dest) else {
+            return Ok(false);
+        };
+        Ok(current == rendered && self.has_mode())
+    }
+
+    /// Unified diff between `dest` and the rendered content, empty if the content matches.
+    pub fn diff(&self) -> Result<String> {
+        let rendered = self.render()?;
+        let current = fs::read_to_string(&self.dest).unwrap_or_default();
+        let dest = self.dest.display().to_string();
+
+        Ok(TextDiff::from_lines(&current, &rendered)
+            .unified_diff()
+            .header(&dest, &dest)
+            .to_string())
+    }
+
+    fn template_vars(&self) -> Vars {
+        let mut vars: Vars = std::env::vars()
+            .map(|(name, value)| (format!("env.{name}"), value))
+            .collect();
+        vars.insert("os.platform".into(), OS_INFO.platform.as_str().into());
+        vars.insert("os.id".into(), OS_INFO.id.clone().unwrap_or_default());
+        vars.insert("os.id_like".into(), OS_INFO.id_like.join(" "));
+        vars.extend(self.vars.clone());
+        vars
+    }
+
+    #[cfg(unix)]
+    fn has_mode(&self) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+
+        let Some(mode) = self.mode else {
+            return true;
+        };
+        fs::metadata(&self.dest).is_ok_and(|m| m.permissions().mode() & 0o7777 == mode)
+    }
+
+    #[cfg(windows)]
+    fn has_mode(&self) -> bool {
+        true
+    }
+}
+
+pub fn render_files(files: &[TemplateFile]) -> Result<()> {
+    let mut pending = Vec::new();
+    for file in files {
+        if !file.is_rendered()? {
+            pending.push(file);
+        }
+    }
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        event_type = %EventType::FilesRenderingStarted.as_str(),
+        files = pending
+            .iter()
+            .map(|f| f.dest.display().to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+
+    for file in pending {
+        render_file(file)
+            .with_context(|| format!("failed to render file '{}'", file.dest.display()))?;
+    }
+    Ok(())
+}
+
+fn render_file(file: &TemplateFile) -> Result<()> {
+    let content = file.render()?;
+
+    if let Some(parent) = file.dest.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory '{}'", parent.display()))?;
+    }
+    fs::write(&file.dest, content).context("failed to write file")?;
+
+    if let Some(mode) = file.mode {
+        set_mode(&file.dest, mode)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    fn file(dir: &Path, src: &str, dest: &str, mode: Option<u32>) -> TemplateFile {
+        TemplateFile::from(
+            &config::TemplateFile {
+                src: src.to_string(),
+                dest: dest.to_string(),
+                mode,
+            },
+            dir,
+            &Vars::from([("user".to_string(), "alice".to_string())]),
+        )
+    }
+
+    #[test]
+    fn test_render_files() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("gitconfig.tmpl"),
+            "name = {{ user }}\nos = {{ os.platform }}\n",
+        )
+        .unwrap();
+        let file = file(dir.path(), "gitconfig.tmpl", "home/.gitconfig", Some(0o600));
+
+        assert!(!file.is_rendered().unwrap());
+        render_files(std::slice::from_ref(&file)).unwrap();
+
+        assert!(file.is_rendered().unwrap());
+        assert_eq!(
+            fs::read_to_string(&file.dest).unwrap(),
+            format!("name = alice\nos = {}\n", OS_INFO.platform.as_str())
+        );
+        let mode = fs::metadata(&file.dest).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o600);
+    }
+
+    #[test]
+    fn test_changed_mode_is_not_rendered() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("script.tmpl"), "echo").unwrap();
+        fs::write(dir.path().join("script.sh"), "echo").unwrap();
+        fs::set_permissions(
+            dir.path().join("script.sh"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+
+        assert!(
+            file(dir.path(), "script.tmpl", "script.sh", None)
+                .is_rendered()
+                .unwrap()
+        );
+        assert!(
+            !file(dir.path(), "script.tmpl", "script.sh", Some(0o755))
+                .is_rendered()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("conf.tmpl"), "a\nuser = {{ user }}\n").unwrap();
+        fs::write(dir.path().join("conf"), "a\nuser = bob\n").unwrap();
+
+        let diff = file(dir.path(), "conf.tmpl", "conf", None).diff().unwrap();
+
+        assert!(diff.contains("-user = bob\n+user = alice\n"), "{diff}");
+    }
+
+    #[test]
+    fn test_render_unknown_variable() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("conf.tmpl"), "{{ missing }}").unwrap();
+
+        let err = file(dir.path(), "conf.tmpl", "conf", None)
+            .render()
+            .unwrap_err();
+
+        assert!(
+            format!("{err:#}").contains("unknown variable 'missing'"),
+            "{err:#}"
+        );
+    }
+}
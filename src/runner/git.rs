@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+use crate::config;
+use crate::logging::EventType;
+use crate::utils::file::resolve_path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitRepo {
+    pub repo: String,
+    pub dest: PathBuf,
+    pub rev: Option<String>,
+    pub update: bool,
+}
+
+impl GitRepo {
+    pub fn from(repo: &config::GitRepo, step_dir: &Path) -> Self {
+        GitRepo {
+            // local remotes are relative to the config file as well
+            repo: match Path::new(&repo.repo).is_relative() && !repo.repo.contains(':') {
+                true => resolve_path(&repo.repo, step_dir).display().to_string(),
+                false => repo.repo.clone(),
+            },
+            dest: resolve_path(&repo.dest, step_dir),
+            rev: repo.rev.clone(),
+            update: repo.update,
+        }
+    }
+
+    pub fn is_cloned(&self) -> bool {
+        self.dest.join(".git").exists()
+    }
+
+    /// Whether `dest` is cloned and checked out at `ref`. With `update`, `ref` (or the default
+    /// branch) is resolved on the remote, so new upstream commits make the repository outdated.
+    pub fn is_synced(&self) -> Result<bool> {
+        if !self.is_cloned() {
+            return Ok(false);
+        }
+
+        let expected = match (&self.rev, self.update) {
+            (None, false) => return Ok(true),
+            (rev, true) => match self.remote_commit(rev.as_deref().unwrap_or("HEAD"))? {
+                Some(commit) => Some(commit),
+                None => self.local_commit(rev.as_deref().unwrap_or("HEAD"))?,
+            },
+            (Some(rev), false) => self.local_commit(rev)?,
+        };
+
+        Ok(expected.is_some() && expected == self.local_commit("HEAD")?)
+    }
+
+    fn local_commit(&self, rev: &str) -> Result<Option<String>> {
+        let output = git_output(
+            Some(&self.dest),
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{rev}^{{commit}}"),
+            ],
+        )?;
+        Ok(output.map(|out| out.trim().to_string()))
+    }
+
+    /// Commit of a branch or tag on the remote, `None` for commit hashes and unknown refs.
+    /// Fails if `rev` names both a branch and a tag.
+    fn remote_commit(&self, rev: &str) -> Result<Option<String>> {
+        let Some(output) = git_output(None, &["ls-remote", &self.repo, rev])? else {
+            bail!("failed to query remote '{}'", self.repo);
+        };
+
+        // `ls-remote` matches the end of ref names, `main` also lists `refs/heads/feature/main`
+        let refs = output
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(commit, name)| (name, commit))
+            .collect::<HashMap<&str, &str>>();
+        let names = match rev == "HEAD" || rev.starts_with("refs/") {
+            true => vec![rev.to_string()],
+            false => vec![format!("refs/heads/{rev}"), format!("refs/tags/{rev}")],
+        };
+        // annotated tags are listed twice, the peeled `^{}` entry points to the commit
+        let commits = names
+            .iter()
+            .filter_map(|name| {
+                refs.get(format!("{name}^{{}}").as_str())
+                    .or(refs.get(name.as_str()))
+            })
+            .collect::<Vec<&&str>>();
+
+        match commits.as_slice() {
+            [] => Ok(None),
+            [commit] => Ok(Some(commit.to_string())),
+            _ => bail!(
+                "'{rev}' is both a branch and a tag on remote '{}'",
+                self.repo
+            ),
+        }
+    }
+
+    fn is_remote_branch(&self, rev: &str) -> Result<bool> {
+        Ok(self
+            .local_commit(&format!("refs/remotes/origin/{rev}"))?
+            .is_some())
+    }
+}
+
+pub fn sync_repos(repos: &[GitRepo]) -> Result<()> {
+    let mut pending = Vec::new();
+    for repo in repos {
+        if !repo.is_synced()? {
+            pending.push(repo);
+        }
+    }
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        event_type = %EventType::GitSyncStarted.as_str(),
+        repos = pending
+            .iter()
+            .map(|r| r.dest.display().to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+
+    for repo in pending {
+        sync_repo(repo).with_context(|| format!("failed to sync '{}'", repo.dest.display()))?;
+    }
+    Ok(())
+}
+
+fn sync_repo(repo: &GitRepo) -> Result<()> {
+    if !repo.is_cloned() {
+        if let Some(parent) = repo.dest.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create directory '{}'", parent.display()))?;
+        }
+        let dest = repo.dest.display().to_string();
+        git(None, &["clone", &repo.repo, &dest])?;
+    } else {
+        // without `update` only a `ref` the clone doesn't know yet is fetched
+        let missing_rev = match &repo.rev {
+            Some(rev) => repo.local_commit(rev)?.is_none(),
+            None => false,
+        };
+        if repo.update || missing_rev {
+            git(Some(&repo.dest), &["fetch", "--tags", "origin"])?;
+        }
+    }
+
+    let Some(rev) = &repo.rev else {
+        if repo.update {
+            git(Some(&repo.dest), &["merge", "--ff-only", "@{upstream}"])?;
+        }
+        return Ok(());
+    };
+
+    git(Some(&repo.dest), &["checkout", rev])?;
+    if repo.is_remote_branch(rev)? {
+        git(
+            Some(&repo.dest),
+            &["merge", "--ff-only", &format!("origin/{rev}")],
+        )?;
+    }
+    Ok(())
+}
+
+fn git(dir: Option<&Path>, args: &[&str]) -> Result<()> {
+    let output = command(dir, args).output().context("failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Stdout of a successful git command, `None` if it exited with an error.
+fn git_output(dir: Option<&Path>, args: &[&str]) -> Result<Option<String>> {
+    let output = command(dir, args)
+        .stderr(Stdio::null())
+        .output()
+        .context("failed to run git")?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string()))
+}
+
+fn command(dir: Option<&Path>, args: &[&str]) -> Command {
+    let mut cmd = Command::new("git");
+    if let Some(dir) = dir {
+        cmd.arg("-C").arg(dir);
+    }
+    cmd.args(args)
+        .stdin(Stdio::null())
+        .env("GIT_TERMINAL_PROMPT", "0");
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::{TempDir, tempdir};
+
+    /// Bare repository with one commit on `main` and tag `v1`.
+    fn remote() -> (TempDir, String) {
+        let dir = tempdir().unwrap();
+        let work = dir.path().join("work");
+        let bare = dir.path().join("remote.git").display().to_string();
+
+        git(None, &["init", "-q", "-b", "main", work.to_str().unwrap()]).unwrap();
+        commit(&work, "first");
+        git(Some(&work), &["tag", "v1"]).unwrap();
+        git(
+            None,
+            &["clone", "-q", "--bare", work.to_str().unwrap(), &bare],
+        )
+        .unwrap();
+        git(Some(&work), &["remote", "add", "origin", &bare]).unwrap();
+
+        (dir, bare)
+    }
+
+    fn commit(work: &Path, message: &str) {
+        std::fs::write(work.join("file"), message).unwrap();
+        git(Some(work), &["add", "file"]).unwrap();
+        git(
+            Some(work),
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "-q",
+                "-m",
+                message,
+            ],
+        )
+        .unwrap();
+    }
+
+    fn repo(remote: &str, dest: &Path, rev: Option<&str>, update: bool) -> GitRepo {
+        GitRepo {
+            repo: remote.to_string(),
+            dest: dest.to_path_buf(),
+            rev: rev.map(str::to_string),
+            update,
+        }
+    }
+
+    #[test]
+    fn test_sync_repos_clones_at_ref() {
+        let (dir, remote) = remote();
+        let work = dir.path().join("work");
+        commit(&work, "second");
+        git(Some(&work), &["push", "-q", "origin", "main"]).unwrap();
+
+        let repo = repo(&remote, &dir.path().join("clone"), Some("v1"), false);
+        assert!(!repo.is_synced().unwrap());
+
+        sync_repos(std::slice::from_ref(&repo)).unwrap();
+
+        assert!(repo.is_synced().unwrap());
+        assert_eq!(
+            std::fs::read_to_string(repo.dest.join("file")).unwrap(),
+            "first"
+        );
+    }
+
+    #[test]
+    fn test_sync_repos_updates_branch() {
+        let (dir, remote) = remote();
+        let work = dir.path().join("work");
+        let repo = repo(&remote, &dir.path().join("clone"), Some("main"), true);
+        sync_repos(std::slice::from_ref(&repo)).unwrap();
+        assert!(repo.is_synced().unwrap());
+
+        commit(&work, "second");
+        git(Some(&work), &["push", "-q", "origin", "main"]).unwrap();
+        assert!(!repo.is_synced().unwrap());
+
+        sync_repos(std::slice::from_ref(&repo)).unwrap();
+
+        assert!(repo.is_synced().unwrap());
+        assert_eq!(
+            std::fs::read_to_string(repo.dest.join("file")).unwrap(),
+            "second"
+        );
+    }
+
+    #[test]
+    fn test_existing_clone_without_update_is_synced() {
+        let (dir, remote) = remote();
+        let work = dir.path().join("work");
+        let repo = repo(&remote, &dir.path().join("clone"), None, false);
+        sync_repos(std::slice::from_ref(&repo)).unwrap();
+
+        commit(&work, "second");
+        git(Some(&work), &["push", "-q", "origin", "main"]).unwrap();
+
+        assert!(repo.is_synced().unwrap());
+    }
+
+    #[test]
+    fn test_sync_repos_fetches_missing_ref() {
+        let (dir, remote) = remote();
+        let work = dir.path().join("work");
+        let mut repo = repo(&remote, &dir.path().join("clone"), Some("v1"), false);
+        sync_repos(std::slice::from_ref(&repo)).unwrap();
+
+        commit(&work, "second");
+        git(Some(&work), &["tag", "v2"]).unwrap();
+        git(Some(&work), &["push", "-q", "--tags", "origin", "main"]).unwrap();
+        repo.rev = Some("v2".to_string());
+        assert!(!repo.is_synced().unwrap());
+
+        sync_repos(std::slice::from_ref(&repo)).unwrap();
+
+        assert!(repo.is_synced().unwrap());
+        assert_eq!(
+            std::fs::read_to_string(repo.dest.join("file")).unwrap(),
+            "second"
+        );
+    }
+
+    #[test]
+    fn test_remote_commit_matches_exact_ref() {
+        let (dir, remote) = remote();
+        let work = dir.path().join("work");
+        let repo = repo(&remote, &dir.path().join("clone"), None, false);
+        let main = git_output(Some(&work), &["rev-parse", "main"])
+            .unwrap()
+            .unwrap();
+
+        git(Some(&work), &["checkout", "-q", "-b", "feature/main"]).unwrap();
+        commit(&work, "feature");
+        git(Some(&work), &["tag", "main"]).unwrap();
+        git(Some(&work), &["push", "-q", "origin", "feature/main"]).unwrap();
+
+        assert_eq!(
+            repo.remote_commit("main").unwrap(),
+            Some(main.trim().to_string())
+        );
+        assert_eq!(repo.remote_commit("feature").unwrap(), None);
+
+        git(Some(&work), &["push", "-q", "origin", "refs/tags/main"]).unwrap();
+        let err = repo.remote_commit("main").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("'main' is both a branch and a tag"),
+            "unexpected err: {err}"
+        );
+    }
+}
@@ -130,6 +130,16 @@ fn print_step(
         writeln!(out, "pre_script:")?;
         output_script(&pre_script.code, max_script_lines, out)?;
     }
+    if !step.repositories.is_empty() {
+        writeln!(out, "repositories:")?;
+        for repo in &step.repositories {
+            let status = match repo.is_added()? {
+                true => "already added".green(),
+                false => "would add".yellow(),
+            };
+            writeln!(out, "  {status}: {repo}")?;
+        }
+    }
     if !step.packages.is_empty() {
         let not_installed_pkgs: HashSet<String> = match &completion {
             StepCompletedResult::NotInstalledPackages(pkgs) => {
@@ -138,18 +148,18 @@ fn print_step(
             _ => HashSet::new(),
         };
 
-        let mut installed: Vec<&str> = Vec::new();
-        let mut not_installed: Vec<&str> = Vec::new();
+        let mut installed: Vec<String> = Vec::new();
+        let mut not_installed: Vec<String> = Vec::new();
 
         for pkg in &step.packages {
             if *completion != StepCompletedResult::NotInstalledPackageManager
                 && not_installed_pkgs.contains(&pkg.name)
             {
-                not_installed.push(pkg.name.as_str());
+                not_installed.push(pkg.spec());
             } else if *completion != StepCompletedResult::NotInstalledPackageManager {
-                installed.push(pkg.name.as_str());
+                installed.push(pkg.spec());
             } else {
-                not_installed.push(pkg.name.as_str());
+                not_installed.push(pkg.spec());
             }
         }
 
@@ -171,6 +181,88 @@ fn print_step(
             )?;
         }
     }
+    if !step.absent_packages.is_empty() {
+        let installed = step.installed_absent_packages()?;
+        let (to_remove, absent): (Vec<&str>, Vec<&str>) = step
+            .absent_packages
+            .iter()
+            .map(|p| p.name.as_str())
+            .partition(|name| installed.iter().any(|p| p == name));
+
+        writeln!(out, "absent_packages ({}):", pkg_manager)?;
+        if !absent.is_empty() {
+            writeln!(out, "  {}: {}", "already absent".green(), absent.join(", "))?;
+        }
+        if !to_remove.is_empty() {
+            writeln!(
+                out,
+                "  {}: {}",
+                "would remove".yellow(),
+                to_remove.join(", ")
+            )?;
+        }
+    }
+    if !step.downloads.is_empty() {
+        writeln!(out, "downloads:")?;
+        for download in &step.downloads {
+            let status = match download.is_downloaded() {
+                true => "already downloaded".green(),
+                false => "would download".yellow(),
+            };
+            writeln!(
+                out,
+                "  {status}: {} -> {}",
+                download.url,
+                download.dest.display()
+            )?;
+        }
+    }
+    if !step.git.is_empty() {
+        writeln!(out, "git:")?;
+        for repo in &step.git {
+            let status = match (repo.is_cloned(), repo.is_synced()?) {
+                (_, true) => "up to date".green(),
+                (true, false) => "would update".yellow(),
+                (false, false) => "would clone".yellow(),
+            };
+            writeln!(out, "  {status}: {} -> {}", repo.repo, repo.dest.display())?;
+        }
+    }
+    if !step.links.is_empty() {
+        writeln!(out, "links:")?;
+        for link in &step.links {
+            let status = match link.is_created() {
+                true => "already linked".green(),
+                false => "would link".yellow(),
+            };
+            writeln!(
+                out,
+                "  {status}: {} -> {}",
+                link.dest.display(),
+                link.src.display()
+            )?;
+        }
+    }
+    if !step.files.is_empty() {
+        writeln!(out, "files:")?;
+        for file in &step.files {
+            let status = match file.is_rendered()? {
+                true => "up to date".green(),
+                false => "would render".yellow(),
+            };
+            writeln!(out, "  {status}: {}", file.dest.display())?;
+        }
+    }
+    if !step.edits.is_empty() {
+        writeln!(out, "edit_file:")?;
+        for edit in &step.edits {
+            let status = match edit.is_applied() {
+                true => "up to date".green(),
+                false => "would edit".yellow(),
+            };
+            writeln!(out, "  {status}: {} ({edit})", edit.path.display())?;
+        }
+    }
     if let Some(script) = &step.script {
         writeln!(out, "script:")?;
         output_script(&script.code, max_script_lines, out)?;
@@ -187,7 +279,14 @@ fn print_step(
         StepCompletedResult::NotInstalledPackageManager => {
             writeln!(out, "status: {}", "package manager not installed".yellow())?;
         }
-        StepCompletedResult::NotInstalledPackages(_) => {}
+        StepCompletedResult::NotInstalledPackages(_)
+        | StepCompletedResult::InstalledAbsentPackages(_)
+        | StepCompletedResult::PendingRepositories(_)
+        | StepCompletedResult::PendingLinks(_)
+        | StepCompletedResult::PendingFiles(_)
+        | StepCompletedResult::PendingDownloads(_)
+        | StepCompletedResult::PendingGitRepos(_)
+        | StepCompletedResult::PendingFileEdits(_) => {}
         StepCompletedResult::FailedCheckScript => {
             writeln!(out, "status: {}", "check-script failed".yellow())?;
         }
@@ -227,7 +326,7 @@ mod tests {
     use super::*;
     use crate::config::{self, StepSelectionReason::MatchedFilter};
     use crate::logging::test::run_with_tracing;
-    use crate::runner::dry::RunPlan;
+    use crate::runner::RunOutcome;
     use crate::runner::script::Script;
     use crate::runner::{Package, Step};
     use crate::system::pkg::PackageManager;
@@ -276,6 +375,7 @@ mod tests {
             script: Some(config::Script {
                 shell: Some(Shell::Bash),
                 code: "echo test".to_string(),
+                ..Default::default()
             }),
             source_file: "/test.yaml".to_string(),
             selection_reason: Some(MatchedFilter),
@@ -292,6 +392,7 @@ mod tests {
                 script: Some(config::Script {
                     shell: Some(Shell::Bash),
                     code: "echo test2".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/test.yaml".to_string(),
                 selection_reason: Some(MatchedFilter),
@@ -303,23 +404,29 @@ mod tests {
             decisions: vec![Decision::Skip, Decision::Run],
         };
 
-        let mut result: Option<RunPlan> = None;
+        let mut result: Option<RunOutcome> = None;
         let trace_output = run_with_tracing(false, || {
             result = crate::runner::run(
                 &steps,
                 &crate::runner::RunParameters {
                     dry_run: false,
                     source_file_path: std::path::PathBuf::from("/test.yaml"),
+                    keep_going: false,
+                    batch_packages: false,
+                    refresh_index: false,
                 },
                 &FakeStateSaver,
                 &mut MockScriptChecker,
                 Some(&mut interactor),
                 &mut sink(),
             )
-            .unwrap();
+            .ok();
         });
 
-        assert!(result.is_none());
+        match result {
+            Some(RunOutcome::Completed(ids)) => assert_eq!(ids, vec!["test-step-2".to_string()]),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
         assert!(
             !trace_output
                 .as_string()
@@ -346,11 +453,14 @@ mod tests {
         };
         let mut output = Vec::new();
 
-        let _ = crate::runner::run(
+        let result = crate::runner::run(
             &steps,
             &crate::runner::RunParameters {
                 dry_run: false,
                 source_file_path: std::path::PathBuf::from("/test.yaml"),
+                keep_going: false,
+                batch_packages: false,
+                refresh_index: false,
             },
             &FakeStateSaver,
             &mut MockScriptChecker,
@@ -358,6 +468,7 @@ mod tests {
             &mut output,
         )?;
 
+        assert!(matches!(result, RunOutcome::Aborted));
         let output_str = String::from_utf8(output).unwrap();
         assert!(
             !output_str.contains("Running step"),
@@ -382,6 +493,9 @@ mod tests {
             &crate::runner::RunParameters {
                 dry_run: false,
                 source_file_path: std::path::PathBuf::from("/test.yaml"),
+                keep_going: false,
+                batch_packages: false,
+                refresh_index: false,
             },
             &FakeStateSaver,
             &mut MockScriptChecker,
@@ -389,7 +503,7 @@ mod tests {
             &mut output,
         )?;
 
-        assert!(result.is_none());
+        assert!(matches!(result, RunOutcome::Completed(_)));
         Ok(())
     }
 
@@ -401,23 +515,26 @@ mod tests {
             decisions: vec![Decision::Run],
         };
 
-        let mut result: Option<RunPlan> = None;
+        let mut result: Option<RunOutcome> = None;
         let trace_output = run_with_tracing(false, || {
             result = crate::runner::run(
                 &steps,
                 &crate::runner::RunParameters {
                     dry_run: false,
                     source_file_path: std::path::PathBuf::from("/test.yaml"),
+                    keep_going: false,
+                    batch_packages: false,
+                    refresh_index: false,
                 },
                 &FakeStateSaver,
                 &mut MockScriptChecker,
                 Some(&mut interactor),
                 &mut sink(),
             )
-            .unwrap();
+            .ok();
         });
 
-        assert!(result.is_none());
+        assert!(matches!(result, Some(RunOutcome::Completed(_))));
         assert!(
             trace_output.as_string().contains("Running step"),
             "unexpected output: {}",
@@ -434,6 +551,7 @@ mod tests {
                 script: Some(config::Script {
                     shell: Some(Shell::Bash),
                     code: "echo test2".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/test.yaml".to_string(),
                 selection_reason: Some(MatchedFilter),
@@ -445,6 +563,7 @@ mod tests {
                 script: Some(config::Script {
                     shell: Some(Shell::Bash),
                     code: "echo test2".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/test.yaml".to_string(),
                 selection_reason: Some(MatchedFilter),
@@ -455,6 +574,7 @@ mod tests {
                 script: Some(config::Script {
                     shell: Some(Shell::Bash),
                     code: "echo test2".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/test.yaml".to_string(),
                 selection_reason: Some(MatchedFilter),
@@ -467,13 +587,16 @@ mod tests {
             decisions: vec![Decision::Skip, Decision::LeaveInteractiveMode],
         };
 
-        let mut result: Result<Option<RunPlan>> = Ok(None);
+        let mut result: Result<RunOutcome> = Ok(RunOutcome::Aborted);
         let trace_output = run_with_tracing(false, || {
             result = crate::runner::run(
                 &steps,
                 &crate::runner::RunParameters {
                     dry_run: false,
                     source_file_path: std::path::PathBuf::from("/test.yaml"),
+                    keep_going: false,
+                    batch_packages: false,
+                    refresh_index: false,
                 },
                 &FakeStateSaver,
                 &mut MockScriptChecker,
@@ -508,10 +631,12 @@ mod tests {
                 script: Some(config::Script {
                     shell: Some(Shell::Bash),
                     code: "echo test2".to_string(),
+                    ..Default::default()
                 }),
                 check_script: Some(config::Script {
                     shell: Some(Shell::Bash),
                     code: "exit 0".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/test.yaml".to_string(),
                 selection_reason: Some(MatchedFilter),
@@ -523,6 +648,7 @@ mod tests {
                 script: Some(config::Script {
                     shell: Some(Shell::Bash),
                     code: "echo test2".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/test.yaml".to_string(),
                 selection_reason: Some(MatchedFilter),
@@ -535,20 +661,23 @@ mod tests {
             decisions: vec![Decision::Skip, Decision::LeaveInteractiveMode],
         };
 
-        let mut result: Option<RunPlan> = None;
+        let mut result: Option<RunOutcome> = None;
         let trace_output = run_with_tracing(false, || {
             result = crate::runner::run(
                 &steps,
                 &crate::runner::RunParameters {
                     dry_run: false,
                     source_file_path: std::path::PathBuf::from("/test.yaml"),
+                    keep_going: false,
+                    batch_packages: false,
+                    refresh_index: false,
                 },
                 &FakeStateSaver,
                 &mut MockScriptChecker,
                 Some(&mut interactor),
                 &mut sink(),
             )
-            .unwrap();
+            .ok();
         });
 
         assert!(
@@ -566,29 +695,33 @@ mod tests {
             script: Some(config::Script {
                 shell: Some(Shell::Bash),
                 code: "echo test".to_string(),
+                ..Default::default()
             }),
             source_file: "/test.yaml".to_string(),
             selection_reason: Some(MatchedFilter),
             ..Default::default()
         };
 
-        let mut result: Option<RunPlan> = None;
+        let mut result: Option<RunOutcome> = None;
         let trace_output = run_with_tracing(false, || {
             result = crate::runner::run(
                 &[step],
                 &crate::runner::RunParameters {
                     dry_run: false,
                     source_file_path: std::path::PathBuf::from("/test.yaml"),
+                    keep_going: false,
+                    batch_packages: false,
+                    refresh_index: false,
                 },
                 &FakeStateSaver,
                 &mut MockScriptChecker,
                 None,
                 &mut sink(),
             )
-            .unwrap();
+            .ok();
         });
 
-        assert!(result.is_none());
+        assert!(matches!(result, Some(RunOutcome::Completed(_))));
         assert!(
             trace_output
                 .as_string()
@@ -607,6 +740,7 @@ mod tests {
             script: Some(config::Script {
                 shell: Some(Shell::Bash),
                 code: "echo test".to_string(),
+                ..Default::default()
             }),
             source_file: "/test.yaml".to_string(),
             selection_reason: Some(MatchedFilter),
@@ -618,6 +752,7 @@ mod tests {
             script: Some(config::Script {
                 shell: Some(Shell::Bash),
                 code: "echo test".to_string(),
+                ..Default::default()
             }),
             source_file: "/test.yaml".to_string(),
             selection_reason: Some(MatchedFilter),
@@ -628,23 +763,26 @@ mod tests {
             decisions: vec![Decision::Skip, Decision::Run],
         };
 
-        let mut result: Option<RunPlan> = None;
+        let mut result: Option<RunOutcome> = None;
         let trace_output = run_with_tracing(false, || {
             result = crate::runner::run(
                 &[step1, step2],
                 &crate::runner::RunParameters {
                     dry_run: false,
                     source_file_path: std::path::PathBuf::from("/test.yaml"),
+                    keep_going: false,
+                    batch_packages: false,
+                    refresh_index: false,
                 },
                 &FakeStateSaver,
                 &mut MockScriptChecker,
                 Some(&mut interactor),
                 &mut sink(),
             )
-            .unwrap();
+            .ok();
         });
 
-        assert!(result.is_none());
+        assert!(matches!(result, Some(RunOutcome::Completed(_))));
         assert!(
             trace_output
                 .as_string()
@@ -663,12 +801,10 @@ mod tests {
             package_manager: PackageManager::Npm,
             packages: vec![Package {
                 name: "pkg1".to_string(),
+                version: None,
                 used_alias: false,
             }],
-            script: Some(Script {
-                shell: Shell::Bash,
-                code: "echo hello".to_string(),
-            }),
+            script: Some(Script::new(Shell::Bash, "echo hello")),
             source_file: "/test.yaml".to_string(),
             selection_reason: MatchedFilter,
             ..Default::default()
@@ -728,10 +864,7 @@ mod tests {
         let step = Step {
             id: "long-step".to_string(),
             package_manager: PackageManager::Npm,
-            script: Some(Script {
-                shell: Shell::Bash,
-                code: long_script,
-            }),
+            script: Some(Script::new(Shell::Bash, long_script)),
             source_file: "/test.yaml".to_string(),
             selection_reason: MatchedFilter,
             ..Default::default()
@@ -763,10 +896,7 @@ mod tests {
         let step = Step {
             id: "long-step".to_string(),
             package_manager: PackageManager::Npm,
-            script: Some(Script {
-                shell: Shell::Bash,
-                code: long_script,
-            }),
+            script: Some(Script::new(Shell::Bash, long_script)),
             source_file: "/test.yaml".to_string(),
             selection_reason: MatchedFilter,
             ..Default::default()
@@ -792,14 +922,8 @@ mod tests {
         let step = Step {
             id: "step-with-pre".to_string(),
             package_manager: PackageManager::Npm,
-            pre_script: Some(Script {
-                shell: Shell::Bash,
-                code: "pre-install".to_string(),
-            }),
-            script: Some(Script {
-                shell: Shell::Bash,
-                code: "main-install".to_string(),
-            }),
+            pre_script: Some(Script::new(Shell::Bash, "pre-install")),
+            script: Some(Script::new(Shell::Bash, "main-install")),
             source_file: "/test.yaml".to_string(),
             selection_reason: MatchedFilter,
             ..Default::default()
@@ -830,10 +954,12 @@ mod tests {
             packages: vec![
                 Package {
                     name: "installed-pkg".to_string(),
+                    version: None,
                     used_alias: false,
                 },
                 Package {
                     name: "not-installed-pkg".to_string(),
+                    version: None,
                     used_alias: false,
                 },
             ],
@@ -1027,10 +1153,7 @@ mod tests {
         let step = Step {
             id: "long-step".to_string(),
             package_manager: PackageManager::Npm,
-            script: Some(Script {
-                shell: Shell::Bash,
-                code: long_script,
-            }),
+            script: Some(Script::new(Shell::Bash, long_script)),
             source_file: "/test.yaml".to_string(),
             selection_reason: MatchedFilter,
             ..Default::default()
@@ -1063,12 +1186,10 @@ mod tests {
         let step = Step {
             id: "test-step".to_string(),
             package_manager: PackageManager::Npm,
-            script: Some(Script {
-                shell: Shell::Bash,
-                code: "echo test".to_string(),
-            }),
+            script: Some(Script::new(Shell::Bash, "echo test")),
             packages: vec![Package {
                 name: "mypackage".to_string(),
+                version: None,
                 used_alias: false,
             }],
             source_file: "/test.yaml".to_string(),
@@ -1102,10 +1223,7 @@ mod tests {
         let step = Step {
             id: "test-step".to_string(),
             package_manager: PackageManager::Npm,
-            script: Some(Script {
-                shell: Shell::Bash,
-                code: "echo test".to_string(),
-            }),
+            script: Some(Script::new(Shell::Bash, "echo test")),
             source_file: "/test.yaml".to_string(),
             selection_reason: MatchedFilter,
             ..Default::default()
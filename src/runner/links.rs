@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use tracing::{info, warn};
+
+use crate::config;
+use crate::logging::EventType;
+use crate::utils::file::resolve_path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub src: PathBuf,
+    pub dest: PathBuf,
+}
+
+impl Link {
+    pub fn from(link: &config::Link, step_dir: &Path) -> Self {
+        Link {
+            src: resolve_path(&link.src, step_dir),
+            dest: resolve_path(&link.dest, step_dir),
+        }
+    }
+
+    /// Whether `dest` is a symlink that already points to `src`.
+    pub fn is_created(&self) -> bool {
+        let is_symlink = fs::symlink_metadata(&self.dest).is_ok_and(|m| m.file_type().is_symlink());
+        if !is_symlink {
+            return false;
+        }
+
+        match (fs::canonicalize(&self.dest), fs::canonicalize(&self.src)) {
+            (Ok(target), Ok(src)) => target == src,
+            _ => false,
+        }
+    }
+}
+
+pub fn create_links(links: &[Link]) -> Result<()> {
+    let pending = links
+        .iter()
+        .filter(|l| !l.is_created())
+        .collect::<Vec<&Link>>();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        event_type = %EventType::LinksCreationStarted.as_str(),
+        links = pending
+            .iter()
+            .map(|l| l.dest.display().to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+
+    for link in pending {
+        create_link(link)
+            .with_context(|| format!("failed to create link '{}'", link.dest.display()))?;
+    }
+    Ok(())
+}
+
+fn create_link(link: &Link) -> Result<()> {
+    if !link.src.exists() {
+        bail!("link source '{}' doesn't exist", link.src.display());
+    }
+
+    if fs::symlink_metadata(&link.dest).is_ok() {
+        let backup = backup_path(&link.dest);
+        fs::rename(&link.dest, &backup)
+            .with_context(|| format!("failed to back up '{}'", link.dest.display()))?;
+        warn!(
+            "Moved existing '{}' to '{}'",
+            link.dest.display(),
+            backup.display()
+        );
+    } else if let Some(parent) = link.dest.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory '{}'", parent.display()))?;
+    }
+
+    symlink(&link.src, &link.dest)
+}
+
+/// First free path of the form `<dest>.mepris.bak`, `<dest>.mepris.bak.1`, ...
+fn backup_path(dest: &Path) -> PathBuf {
+    let mut backup = dest.as_os_str().to_owned();
+    backup.push(".mepris.bak");
+    let backup = PathBuf::from(backup);
+
+    let mut candidate = backup.clone();
+    let mut n = 1;
+    while fs::symlink_metadata(&candidate).is_ok() {
+        let mut numbered = backup.as_os_str().to_owned();
+        numbered.push(format!(".{n}"));
+        candidate = PathBuf::from(numbered);
+        n += 1;
+    }
+    candidate
+}
+
+#[cfg(unix)]
+fn symlink(src: &Path, dest: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(src, dest).context("failed to create symlink")
+}
+
+#[cfg(windows)]
+fn symlink(src: &Path, dest: &Path) -> Result<()> {
+    let res = if src.is_dir() {
+        std::os::windows::fs::symlink_dir(src, dest)
+    } else {
+        std::os::windows::fs::symlink_file(src, dest)
+    };
+    res.context("failed to create symlink (requires Developer Mode or administrator rights)")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn link(dir: &Path, src: &str, dest: &str) -> Link {
+        Link::from(
+            &config::Link {
+                src: src.to_string(),
+                dest: dest.to_string(),
+            },
+            dir,
+        )
+    }
+
+    #[test]
+    fn test_create_links() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("nvim")).unwrap();
+        let link = link(dir.path(), "nvim", "home/.config/nvim");
+
+        assert!(!link.is_created());
+        create_links(std::slice::from_ref(&link)).unwrap();
+
+        assert!(link.is_created());
+        assert_eq!(fs::read_link(&link.dest).unwrap(), dir.path().join("nvim"));
+
+        // idempotent
+        create_links(std::slice::from_ref(&link)).unwrap();
+        assert!(link.is_created());
+    }
+
+    #[test]
+    fn test_create_links_backs_up_existing() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join(".bashrc"), "new").unwrap();
+        fs::write(dir.path().join("bashrc"), "old").unwrap();
+        fs::write(dir.path().join("bashrc.mepris.bak"), "older").unwrap();
+        let link = link(dir.path(), ".bashrc", "bashrc");
+
+        create_links(std::slice::from_ref(&link)).unwrap();
+
+        assert!(link.is_created());
+        assert_eq!(fs::read_to_string(&link.dest).unwrap(), "new");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("bashrc.mepris.bak.1")).unwrap(),
+            "old"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("bashrc.mepris.bak")).unwrap(),
+            "older"
+        );
+    }
+
+    #[test]
+    fn test_link_pointing_elsewhere_is_not_created() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a"), "").unwrap();
+        fs::write(dir.path().join("b"), "").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("b"), dir.path().join("dest")).unwrap();
+
+        assert!(!link(dir.path(), "a", "dest").is_created());
+        assert!(link(dir.path(), "b", "dest").is_created());
+    }
+
+    #[test]
+    fn test_create_links_missing_source() {
+        let dir = tempdir().unwrap();
+        let err = create_links(&[link(dir.path(), "missing", "dest")]).unwrap_err();
+
+        assert!(format!("{err:#}").contains("doesn't exist"), "{err:#}");
+    }
+}
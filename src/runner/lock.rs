@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::config;
+use crate::config::aliases::load_aliases;
+use crate::runner::Step;
+use crate::runner::pkg::resolve_step_package_manager;
+use crate::system::pkg::PackageManager;
+use crate::system::pkg::version::parse_package_spec;
+
+pub const LOCK_FILE_NAME: &str = "mepris.lock";
+
+/// Package versions installed by the steps of a config, keyed by step id.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Lock {
+    #[serde(default)]
+    pub steps: BTreeMap<String, LockedStep>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct LockedStep {
+    pub manager: PackageManager,
+    pub packages: Vec<LockedPackage>,
+}
+
+/// Alias-resolved package name with its installed version.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+}
+
+impl Lock {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .context(format!("Failed to read lock file {}", path.display()))?;
+        serde_yaml::from_str(&content)
+            .context(format!("Failed to parse lock file {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_yaml::to_string(self)?)
+            .context(format!("Failed to write lock file {}", path.display()))
+    }
+}
+
+/// `mepris.lock` next to the config file.
+pub fn lock_path(source_file_path: &Path) -> PathBuf {
+    source_file_path.parent().unwrap().join(LOCK_FILE_NAME)
+}
+
+/// Records the installed versions of the packages of `steps` in the lock file;
+/// entries of other steps are kept.
+pub fn update_lock(steps: &[config::Step], source_file_path: &Path) -> Result<()> {
+    let aliases = load_aliases(source_file_path.parent().unwrap())?;
+    let path = lock_path(source_file_path);
+    let mut lock = if path.exists() {
+        Lock::load(&path)?
+    } else {
+        Lock::default()
+    };
+
+    for step in steps.iter().filter(|s| !s.packages.is_empty()) {
+        let step = Step::from(step, &aliases);
+        if std::env::var("MEPRIS_INSTALLED_VERSION").is_err()
+            && !step.package_manager.is_available()
+        {
+            continue;
+        }
+
+        let mut packages = Vec::new();
+        for pkg in &step.packages {
+            if let Some(version) = step.package_manager.installed_version(&pkg.name)? {
+                packages.push(LockedPackage {
+                    name: pkg.name.clone(),
+                    version,
+                });
+            }
+        }
+        lock.steps.insert(
+            step.id.clone(),
+            LockedStep {
+                manager: step.package_manager.clone(),
+                packages,
+            },
+        );
+    }
+
+    if lock.steps.is_empty() && !path.exists() {
+        return Ok(());
+    }
+    lock.save(&path)
+}
+
+/// Pins the packages of `steps` to the exact versions from `lock`.
+/// Steps resolved to another package manager than the locked one are left as they are.
+pub fn apply_lock(steps: &mut [config::Step], lock: &Lock, source_file_path: &Path) -> Result<()> {
+    let aliases = load_aliases(source_file_path.parent().unwrap())?;
+
+    for step in steps.iter_mut() {
+        let Some(locked) = lock.steps.get(&step.id) else {
+            continue;
+        };
+        let manager = resolve_step_package_manager(step);
+        if manager != locked.manager {
+            warn!(
+                "step '{}' was locked with {}, but uses {}; ignoring its locked versions",
+                step.id, locked.manager, manager
+            );
+            continue;
+        }
+        if !manager.can_pin_versions() {
+            debug!("{manager} can't install specific versions, ignoring locked versions");
+            continue;
+        }
+
+        for pkg in &mut step.packages {
+            let (name, _) = parse_package_spec(pkg)?;
+            let resolved_name = aliases.resolve_name(name, &manager);
+            if let Some(locked_pkg) = locked.packages.iter().find(|p| p.name == resolved_name) {
+                *pkg = format!("{name}@={}", locked_pkg.version);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EnvGuard;
+    use crate::config::StepSelectionReason::MatchedFilter;
+    use crate::system::pkg::PackageSource;
+    use serial_test::serial;
+    use tempfile::tempdir;
+
+    fn step(id: &str, packages: &[&str], manager: PackageManager, dir: &Path) -> config::Step {
+        config::Step {
+            id: id.to_string(),
+            packages: packages.iter().map(|p| p.to_string()).collect(),
+            package_source: Some(PackageSource::Manager(manager)),
+            source_file: dir.join("mepris.yaml").display().to_string(),
+            selection_reason: Some(MatchedFilter),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_update_lock_keeps_other_steps() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("mepris.yaml");
+        fs::write(dir.path().join("pkg_aliases.yaml"), "fd:\n  apt: fd-find\n").unwrap();
+        let _guard = EnvGuard::new("MEPRIS_INSTALLED_VERSION", "9.0.0");
+
+        Lock {
+            steps: BTreeMap::from([(
+                "old".to_string(),
+                LockedStep {
+                    manager: PackageManager::Npm,
+                    packages: vec![],
+                },
+            )]),
+        }
+        .save(&lock_path(&config_path))
+        .unwrap();
+
+        update_lock(
+            &[step("cli", &["fd@>=8"], PackageManager::Apt, dir.path())],
+            &config_path,
+        )
+        .unwrap();
+
+        let lock = Lock::load(&dir.path().join(LOCK_FILE_NAME)).unwrap();
+        assert_eq!(lock.steps.len(), 2);
+        assert_eq!(
+            lock.steps["cli"],
+            LockedStep {
+                manager: PackageManager::Apt,
+                packages: vec![LockedPackage {
+                    name: "fd-find".to_string(),
+                    version: "9.0.0".to_string(),
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_lock() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("mepris.yaml");
+        fs::write(dir.path().join("pkg_aliases.yaml"), "fd:\n  apt: fd-find\n").unwrap();
+
+        let locked = |manager: PackageManager, name: &str, version: &str| LockedStep {
+            manager,
+            packages: vec![LockedPackage {
+                name: name.to_string(),
+                version: version.to_string(),
+            }],
+        };
+        let lock = Lock {
+            steps: BTreeMap::from([
+                (
+                    "cli".to_string(),
+                    locked(PackageManager::Apt, "fd-find", "9.0.0-1"),
+                ),
+                (
+                    "desktop".to_string(),
+                    locked(PackageManager::Flatpak, "org.gimp.GIMP", "2.10"),
+                ),
+                (
+                    "rust".to_string(),
+                    locked(PackageManager::Npm, "ripgrep", "14.1.1"),
+                ),
+            ]),
+        };
+        let mut steps = vec![
+            step("cli", &["fd@>=8", "git"], PackageManager::Apt, dir.path()),
+            step(
+                "desktop",
+                &["org.gimp.GIMP"],
+                PackageManager::Flatpak,
+                dir.path(),
+            ),
+            step("rust", &["ripgrep"], PackageManager::Cargo, dir.path()),
+        ];
+
+        apply_lock(&mut steps, &lock, &config_path).unwrap();
+
+        assert_eq!(steps[0].packages, vec!["fd@=9.0.0-1", "git"]);
+        assert_eq!(steps[1].packages, vec!["org.gimp.GIMP"]);
+        assert_eq!(steps[2].packages, vec!["ripgrep"]);
+    }
+}
@@ -1,16 +1,28 @@
 use std::cmp::PartialEq;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::thread;
+use std::time::Duration;
 use std::{
     io::Write,
     path::{Path, PathBuf},
 };
 
+mod batch;
+pub mod downloads;
 pub mod dry;
+pub mod edits;
+pub mod files;
+pub mod git;
 pub mod interactive;
+pub mod links;
+pub mod lock;
 mod pkg;
+pub mod repos;
+pub mod rollback;
 pub mod script;
 pub mod script_checker;
 pub mod state;
+pub mod upgrade;
 
 pub use interactive::{CliInteractor, Decision, Interactor};
 
@@ -19,62 +31,128 @@ use crate::{config, config::aliases::load_aliases};
 use crate::config::StepSelectionReason;
 use crate::config::aliases::PackageAliases;
 use crate::logging::{EventType, SpanType};
-use crate::runner::pkg::{install_packages, resolve_step_package_manager};
+use crate::runner::downloads::{Download, fetch_downloads};
+use crate::runner::edits::{FileEdit, apply_edits};
+use crate::runner::files::{TemplateFile, render_files};
+use crate::runner::git::{GitRepo, sync_repos};
+use crate::runner::links::{Link, create_links};
+use crate::runner::pkg::{
+    IndexRefresher, install_packages, remove_packages, resolve_step_package_manager,
+};
+use crate::runner::repos::{PackageRepo, add_repositories};
 use crate::runner::script::ScriptStatus;
 use crate::runner::script::ScriptStatus::Failed;
+use crate::runner::script::resolve_privilege;
 pub(crate) use crate::runner::script::{
     Script, ScriptResult, run_noninteractive_script, run_script,
 };
+use crate::system::os_info::OS_INFO;
 use crate::system::pkg::PackageManager;
+use crate::system::pkg::version::{VersionReq, parse_package_spec};
+use crate::system::privilege::Privilege;
 use crate::system::shell::Shell;
 use anyhow::{Context, Result, bail};
 use script_checker::ScriptChecker;
-use tracing::{debug, debug_span, info, info_span, warn};
+use tracing::{debug, debug_span, error, info, info_span, warn};
+
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(5);
 
 pub struct RunParameters {
     pub source_file_path: PathBuf,
     pub dry_run: bool,
+    pub keep_going: bool,
+    /// Install packages of steps using the same package manager in one transaction.
+    pub batch_packages: bool,
+    /// Refresh package indexes before the first installation of each manager.
+    pub refresh_index: bool,
 }
 
 pub struct RunState {
     pub last_step_id: Option<String>,
     pub interactive: bool,
+    pub executed_steps: Vec<String>,
+    /// Packages installed by mepris, by step id.
+    pub installed_packages: BTreeMap<String, Vec<String>>,
 }
 
 pub trait StateSaver {
     fn save(&self, info: &RunState) -> Result<()>;
 }
 
+#[derive(Clone)]
 pub struct Package {
     pub name: String,
+    pub version: Option<VersionReq>,
     pub used_alias: bool,
 }
 
+impl Package {
+    /// Name with the version constraint, `name@version`.
+    pub fn spec(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}@{}", self.name, version),
+            None => self.name.clone(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Default, Clone)]
 pub enum StepCompletedResult {
     #[default]
     Completed,
     NotInstalledPackageManager,
     NotInstalledPackages(Vec<String>),
+    InstalledAbsentPackages(Vec<String>),
+    PendingRepositories(Vec<String>),
+    PendingLinks(Vec<String>),
+    PendingFiles(Vec<String>),
+    PendingDownloads(Vec<String>),
+    PendingGitRepos(Vec<String>),
+    PendingFileEdits(Vec<String>),
     FailedCheckScript,
     HasScriptWithoutCheck,
 }
 
+/// How a run that didn't fail ended.
+#[derive(Debug)]
+pub enum RunOutcome {
+    /// What a dry run would do.
+    Planned(dry::RunPlan),
+    /// Ids of the completed steps, in run order, whether they ran or were already completed.
+    Completed(Vec<String>),
+    /// The run was aborted in interactive mode.
+    Aborted,
+}
+
 #[derive(PartialEq, Debug)]
 enum ExecutionResult {
     Completed,
     Skipped,
     CompletedWithMissingDeps,
+    Failed,
+    Blocked,
 }
 
 #[derive(Default)]
 pub struct Step {
     pub id: String,
     pub package_manager: PackageManager,
+    pub repositories: Vec<PackageRepo>,
     pub packages: Vec<Package>,
+    pub absent_packages: Vec<Package>,
     pub pre_script: Option<Script>,
     pub script: Option<Script>,
     pub check_script: Option<Script>,
+    pub undo_script: Option<Script>,
+    pub links: Vec<Link>,
+    pub files: Vec<TemplateFile>,
+    pub downloads: Vec<Download>,
+    pub git: Vec<GitRepo>,
+    pub edits: Vec<FileEdit>,
+    pub retries: u32,
+    pub retry_delay: Duration,
+    pub refresh_index: bool,
+    pub privilege: Privilege,
     pub source_file: String,
     pub selection_reason: StepSelectionReason,
     pub dependencies: Vec<String>,
@@ -94,24 +172,80 @@ impl Step {
         };
 
         let pkg_manager = resolve_step_package_manager(config_step);
-
-        let mut packages: Vec<Package> = Vec::new();
-        for cfg_pkg in &config_step.packages {
-            let mut resolved_pkg = Package {
-                name: aliases.resolve_name(cfg_pkg, &pkg_manager),
-                used_alias: false,
-            };
-            resolved_pkg.used_alias = cfg_pkg != &resolved_pkg.name;
-            packages.push(resolved_pkg);
-        }
+        let step_dir = Path::new(&config_step.source_file)
+            .parent()
+            .unwrap_or(Path::new(""));
+
+        let resolve_packages = |cfg_pkgs: &[String]| -> Vec<Package> {
+            let mut packages: Vec<Package> = Vec::new();
+            for cfg_pkg in cfg_pkgs {
+                // versions are checked on validation
+                let (cfg_name, version) = parse_package_spec(cfg_pkg).unwrap_or((cfg_pkg, None));
+                let mut resolved_pkg = Package {
+                    name: aliases.resolve_name(cfg_name, &pkg_manager),
+                    version,
+                    used_alias: false,
+                };
+                resolved_pkg.used_alias = cfg_name != resolved_pkg.name;
+                // Homebrew pins versions with versioned formulae, e.g. `python@3.12`
+                if pkg_manager == PackageManager::Brew
+                    && let Some(version) = resolved_pkg.version.as_ref().and_then(|v| v.pinned())
+                {
+                    resolved_pkg.name = format!("{}@{}", resolved_pkg.name, version);
+                    resolved_pkg.version = None;
+                }
+                packages.push(resolved_pkg);
+            }
+            packages
+        };
 
         Step {
             id: config_step.id.clone(),
+            repositories: config_step
+                .repositories
+                .iter()
+                .map(|repo| PackageRepo::from(repo, step_dir))
+                .collect(),
+            packages: resolve_packages(&config_step.packages),
+            absent_packages: resolve_packages(&config_step.absent_packages),
             package_manager: pkg_manager,
-            packages,
             pre_script: resolve_script(&config_step.pre_script),
             script: resolve_script(&config_step.script),
             check_script: resolve_script(&config_step.check_script),
+            undo_script: resolve_script(&config_step.undo_script),
+            links: config_step
+                .links
+                .iter()
+                .map(|link| Link::from(link, step_dir))
+                .collect(),
+            files: config_step
+                .files
+                .iter()
+                .map(|file| TemplateFile::from(file, step_dir, &config_step.vars))
+                .collect(),
+            downloads: config_step
+                .downloads
+                .iter()
+                .map(|download| Download::from(download, step_dir))
+                .collect(),
+            git: config_step
+                .git
+                .iter()
+                .map(|repo| GitRepo::from(repo, step_dir))
+                .collect(),
+            edits: config_step
+                .edit_file
+                .iter()
+                .map(|edit| FileEdit::from(edit, step_dir))
+                .collect(),
+            retries: config_step.retries,
+            retry_delay: config_step.retry_delay.unwrap_or(DEFAULT_RETRY_DELAY),
+            refresh_index: config_step
+                .defaults
+                .as_ref()
+                .and_then(|d| d.refresh_index)
+                .unwrap_or(true),
+            privilege: resolve_privilege(OS_INFO.platform, &config_step.defaults),
             source_file: config_step.source_file.clone(),
             selection_reason: config_step
                 .selection_reason
@@ -137,11 +271,62 @@ impl Step {
         Path::new(&self.source_file).parent().unwrap()
     }
 
+    /// Packages from `absent_packages` that are currently installed.
+    pub fn installed_absent_packages(&self) -> Result<Vec<String>> {
+        // packages of an unavailable manager can't be installed
+        if !self.package_manager.can_remove() {
+            return Ok(Vec::new());
+        }
+
+        let mut installed = Vec::new();
+        for pkg in &self.absent_packages {
+            if self.package_manager.is_installed(&pkg.name)? {
+                installed.push(pkg.name.clone());
+            }
+        }
+        Ok(installed)
+    }
+
+    /// Whether `pkg` is installed in a version matching its constraint.
+    pub fn is_package_installed(&self, pkg: &Package) -> Result<bool> {
+        match &pkg.version {
+            Some(version) => Ok(self
+                .package_manager
+                .installed_version(&pkg.name)?
+                .is_some_and(|installed| version.matches(&installed))),
+            None => self.package_manager.is_installed(&pkg.name),
+        }
+    }
+
+    /// Names of the packages that are not installed at all. Only those are removed on rollback,
+    /// packages that were on the machine before mepris ran are kept.
+    pub fn new_package_names<'a>(
+        &self,
+        packages: impl IntoIterator<Item = &'a Package>,
+    ) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for pkg in packages {
+            if !self.package_manager.is_installed(&pkg.name)? {
+                names.push(pkg.name.clone());
+            }
+        }
+        Ok(names)
+    }
+
     pub fn is_completed(
         &self,
         script_checker: Option<&mut dyn ScriptChecker>,
     ) -> Result<StepCompletedResult> {
-        if self.packages.is_empty() && self.check_script.is_none() {
+        if self.repositories.is_empty()
+            && self.packages.is_empty()
+            && self.absent_packages.is_empty()
+            && self.links.is_empty()
+            && self.files.is_empty()
+            && self.downloads.is_empty()
+            && self.git.is_empty()
+            && self.edits.is_empty()
+            && self.check_script.is_none()
+        {
             return match self.script {
                 Some(_) => Ok(StepCompletedResult::HasScriptWithoutCheck),
                 None => Ok(StepCompletedResult::Completed),
@@ -169,7 +354,7 @@ impl Step {
 
             for pkg in self.packages.iter() {
                 let _package_span = debug_span!(SpanType::PackageCheck.as_str()).entered();
-                if !self.package_manager.is_installed(&pkg.name)? {
+                if !self.is_package_installed(pkg)? {
                     not_installed_pkgs.push(pkg.name.clone());
                 }
             }
@@ -183,6 +368,75 @@ impl Step {
             ));
         }
 
+        if !self.absent_packages.is_empty() {
+            let installed_absent_pkgs = self.installed_absent_packages()?;
+            if !installed_absent_pkgs.is_empty() {
+                return exit(StepCompletedResult::InstalledAbsentPackages(
+                    installed_absent_pkgs,
+                ));
+            }
+        }
+
+        let mut pending_repos = Vec::new();
+        for repo in &self.repositories {
+            if !repo.is_added()? {
+                pending_repos.push(repo.name().to_string());
+            }
+        }
+        if !pending_repos.is_empty() {
+            return exit(StepCompletedResult::PendingRepositories(pending_repos));
+        }
+
+        let pending_links = self
+            .links
+            .iter()
+            .filter(|link| !link.is_created())
+            .map(|link| link.dest.display().to_string())
+            .collect::<Vec<String>>();
+        if !pending_links.is_empty() {
+            return exit(StepCompletedResult::PendingLinks(pending_links));
+        }
+
+        let mut pending_files = Vec::new();
+        for file in &self.files {
+            if !file.is_rendered()? {
+                pending_files.push(file.dest.display().to_string());
+            }
+        }
+        if !pending_files.is_empty() {
+            return exit(StepCompletedResult::PendingFiles(pending_files));
+        }
+
+        let pending_edits = self
+            .edits
+            .iter()
+            .filter(|edit| !edit.is_applied())
+            .map(|edit| edit.path.display().to_string())
+            .collect::<Vec<String>>();
+        if !pending_edits.is_empty() {
+            return exit(StepCompletedResult::PendingFileEdits(pending_edits));
+        }
+
+        let pending_downloads = self
+            .downloads
+            .iter()
+            .filter(|download| !download.is_downloaded())
+            .map(|download| download.dest.display().to_string())
+            .collect::<Vec<String>>();
+        if !pending_downloads.is_empty() {
+            return exit(StepCompletedResult::PendingDownloads(pending_downloads));
+        }
+
+        let mut pending_repos = Vec::new();
+        for repo in &self.git {
+            if !repo.is_synced()? {
+                pending_repos.push(repo.dest.display().to_string());
+            }
+        }
+        if !pending_repos.is_empty() {
+            return exit(StepCompletedResult::PendingGitRepos(pending_repos));
+        }
+
         if let Some(check_script) = self.check_script.as_ref() {
             let res = run_noninteractive_script(check_script, self.directory(), script_checker)
                 .context(format!("failed to run check-script for step '{}'", self.id))?;
@@ -195,7 +449,7 @@ impl Step {
             );
 
             match res.status {
-                Failed(_) => {
+                Failed(_) | ScriptStatus::TimedOut(_) => {
                     return exit(StepCompletedResult::FailedCheckScript);
                 }
                 ScriptStatus::Success => {}
@@ -215,20 +469,32 @@ pub fn run(
     script_checker: &mut dyn ScriptChecker,
     mut interactor: Option<&mut dyn Interactor>,
     out: &mut impl Write,
-) -> Result<Option<dry::RunPlan>> {
+) -> Result<RunOutcome> {
     let aliases = load_aliases(params.source_file_path.parent().unwrap())?;
-    let mut steps: Vec<Step> = steps.iter().map(|s| Step::from(s, &aliases)).collect();
+    let steps: Vec<Step> = steps.iter().map(|s| Step::from(s, &aliases)).collect();
 
     let _span = info_span!("run").entered();
     if params.dry_run {
-        return dry::run(&steps).map(Some);
+        return dry::run(&steps, params.refresh_index).map(RunOutcome::Planned);
     }
 
     let mut interactive = interactor.is_some();
     let mut execution_results: HashMap<String, ExecutionResult> = HashMap::new();
+    let mut failed_steps: Vec<String> = Vec::new();
+    let mut blocked_steps: Vec<String> = Vec::new();
+    let mut executed_steps: Vec<String> = Vec::new();
+    let mut installed_packages: BTreeMap<String, Vec<String>> = BTreeMap::new();
     let total_steps = steps.len();
-
-    for (i, step) in steps.iter_mut().enumerate() {
+    let positions: HashMap<&str, usize> = steps
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.id.as_str(), i))
+        .collect();
+    // steps whose packages were already installed together with the packages of an earlier step
+    let mut batched: HashSet<String> = HashSet::new();
+    let mut refresher = IndexRefresher::new(params.refresh_index);
+
+    for (i, step) in steps.iter().enumerate() {
         let _span = info_span!(
             "step",
             step_id = step.id,
@@ -236,6 +502,29 @@ pub fn run(
             total = total_steps
         )
         .entered();
+
+        // failed steps are only recorded in keep-going mode
+        let failed_deps = step
+            .dependencies
+            .iter()
+            .filter(|dep| {
+                matches!(
+                    execution_results.get(*dep),
+                    Some(ExecutionResult::Failed | ExecutionResult::Blocked)
+                )
+            })
+            .cloned()
+            .collect::<Vec<String>>();
+        if !failed_deps.is_empty() {
+            info!(
+                event_type = %EventType::StepBlocked.as_str(),
+                failed_deps = failed_deps.join(", ")
+            );
+            execution_results.insert(step.id.clone(), ExecutionResult::Blocked);
+            blocked_steps.push(step.id.clone());
+            continue;
+        }
+
         let has_broken_deps = step.dependencies.iter().any(|dep| {
             execution_results
                 .get(dep)
@@ -246,13 +535,31 @@ pub fn run(
             .save(&RunState {
                 last_step_id: Some(step.id.clone()),
                 interactive,
+                executed_steps: executed_steps.clone(),
+                installed_packages: installed_packages.clone(),
             })
             .is_err()
         {
             warn!("failed to save run state");
         }
 
-        let completion = step.is_completed(Some(script_checker))?;
+        // a check that can't run fails the step like a failed run
+        let completion = match step
+            .is_completed(Some(script_checker))
+            .context(format!("failed to check step '{}'", step.id))
+        {
+            Ok(completion) => completion,
+            Err(err) => {
+                record_failure(
+                    step,
+                    err,
+                    params.keep_going,
+                    &mut execution_results,
+                    &mut failed_steps,
+                )?;
+                continue;
+            }
+        };
         if interactive && let Some(interactor) = interactor.as_mut() {
             match interactor.ask_confirmation(step, has_broken_deps, &completion, out)? {
                 Decision::Run => {}
@@ -264,11 +571,12 @@ pub fn run(
                     }
                     continue;
                 }
-                Decision::Abort => return Ok(None),
+                Decision::Abort => return Ok(RunOutcome::Aborted),
                 Decision::LeaveInteractiveMode => interactive = false,
             }
-        } else if completion == StepCompletedResult::Completed {
+        } else if completion == StepCompletedResult::Completed && !batched.contains(&step.id) {
             info!(event_type = %EventType::CompletedStepSkipped.as_str());
+            execution_results.insert(step.id.clone(), ExecutionResult::Completed);
             continue;
         }
 
@@ -276,8 +584,51 @@ pub fn run(
             bail!("cannot run step with broken dependencies without interactive mode")
         }
 
-        run_step(step, script_checker, out).context(format!("failed to run step '{}'", step.id))?;
+        if params.batch_packages && !interactive && !batched.contains(&step.id) {
+            let is_done = |dep: &str| {
+                positions.get(dep).is_none_or(|&pos| pos < i)
+                    && execution_results
+                        .get(dep)
+                        .is_none_or(|res| res == &ExecutionResult::Completed)
+            };
+            if let Some(batch) = batch::collect_batch(&steps, i, is_done)? {
+                // on failure each step installs its own packages, so the error is reported for the right step
+                match batch.install(&mut refresher) {
+                    Ok(new_packages) => {
+                        batched.extend(batch.step_ids());
+                        installed_packages.extend(new_packages);
+                    }
+                    Err(err) => warn!("failed to install packages in one transaction: {err:#}"),
+                }
+            }
+        }
+
+        let result = run_step(
+            step,
+            batched.contains(&step.id),
+            &mut refresher,
+            script_checker,
+            out,
+        )
+        .context(format!("failed to run step '{}'", step.id));
+        match result {
+            Ok(new_packages) if !new_packages.is_empty() => {
+                installed_packages.insert(step.id.clone(), new_packages);
+            }
+            Ok(_) => {}
+            Err(err) => {
+                record_failure(
+                    step,
+                    err,
+                    params.keep_going,
+                    &mut execution_results,
+                    &mut failed_steps,
+                )?;
+                continue;
+            }
+        }
 
+        executed_steps.push(step.id.clone());
         if has_broken_deps {
             execution_results.insert(step.id.clone(), ExecutionResult::CompletedWithMissingDeps);
         } else {
@@ -287,77 +638,197 @@ pub fn run(
 
     if state_saver
         .save(&RunState {
-            last_step_id: None,
+            last_step_id: failed_steps.first().cloned(),
             interactive,
+            executed_steps,
+            installed_packages,
         })
         .is_err()
     {
         warn!("failed to save run state");
     }
 
+    if !failed_steps.is_empty() {
+        info!(
+            event_type = %EventType::RunFailed.as_str(),
+            failed = failed_steps.join(", "),
+            blocked = blocked_steps.join(", ")
+        );
+        bail!(
+            "{} step(s) failed, {} step(s) blocked",
+            failed_steps.len(),
+            blocked_steps.len()
+        );
+    }
+
     info!(event_type = %EventType::RunCompleted.as_str(), interactive = interactive);
-    Ok(None)
+    let completed_steps = steps
+        .iter()
+        .filter(|step| {
+            matches!(
+                execution_results.get(&step.id),
+                Some(ExecutionResult::Completed | ExecutionResult::CompletedWithMissingDeps)
+            )
+        })
+        .map(|step| step.id.clone())
+        .collect();
+    Ok(RunOutcome::Completed(completed_steps))
+}
+
+/// Returns the error unless in keep-going mode, where the step is recorded as failed so its
+/// dependents are blocked.
+fn record_failure(
+    step: &Step,
+    err: anyhow::Error,
+    keep_going: bool,
+    execution_results: &mut HashMap<String, ExecutionResult>,
+    failed_steps: &mut Vec<String>,
+) -> Result<()> {
+    if !keep_going {
+        return Err(err);
+    }
+    error!(event_type = %EventType::StepFailed.as_str(), error = format!("{err:#}"));
+    execution_results.insert(step.id.clone(), ExecutionResult::Failed);
+    failed_steps.push(step.id.clone());
+    Ok(())
 }
 
 fn run_step(
     step: &Step,
+    packages_installed: bool,
+    refresher: &mut IndexRefresher,
     script_checker: &mut dyn ScriptChecker,
     out: &mut impl Write,
-) -> Result<()> {
+) -> Result<Vec<String>> {
     let _span = info_span!("step_run").entered();
     info!(event_type = %EventType::StepRunStarted.as_str());
 
-    let step_dir = step.directory();
-
-    let mut run_step_script =
-        |name: &str, script: &Option<Script>, out: &mut dyn Write| -> Result<()> {
-            if let Some(script) = script {
-                info!(event_type = %EventType::ScriptStarted.as_str(), kind=name);
-                let result = run_script(script, step_dir, Some(script_checker), out);
-                if let Ok(res) = result.as_ref() {
-                    debug!(
-                        event_type = %EventType::ScriptCompleted.as_str(),
-                        code = res.status.code(),
-                        elapsed_secs = res.time.as_secs_f64(),
-                        kind = name,
-                    );
-                }
-
-                match result {
-                    Ok(ScriptResult {
-                        status: ScriptStatus::Success,
-                        ..
-                    }) => return Ok(()),
-                    Ok(ScriptResult {
-                        status: Failed(code),
-                        ..
-                    }) => {
-                        bail!("failed to run {name}: status code {code}")
-                    }
-                    Err(e) => bail!("failed to run {name}: {e}"),
-                }
-            }
-            Ok(())
-        };
+    run_step_script(step, "pre-script", &step.pre_script, script_checker, out)?;
+    if !step.repositories.is_empty() {
+        with_retries(step, "repositories", || {
+            add_repositories(&step.repositories, &step.privilege)
+        })?;
+    }
 
-    run_step_script("pre-script", &step.pre_script, out)?;
+    let mut new_packages = Vec::new();
+    if !step.packages.is_empty() && !packages_installed {
+        new_packages = step.new_package_names(&step.packages)?;
+        if step.refresh_index {
+            refresher.refresh(&step.package_manager, &step.privilege);
+        }
+        with_retries(step, "packages installation", || {
+            install_packages(&step.packages, &step.package_manager, &step.privilege)
+        })?;
+    }
 
-    if !step.packages.is_empty() {
-        install_packages(
-            &step
-                .packages
-                .iter()
-                .map(|p| p.name.clone())
-                .collect::<Vec<String>>(),
-            &step.package_manager,
-        )?;
+    let installed_absent_pkgs = step.installed_absent_packages()?;
+    if !installed_absent_pkgs.is_empty() {
+        with_retries(step, "packages removal", || {
+            remove_packages(
+                &installed_absent_pkgs,
+                &step.package_manager,
+                &step.privilege,
+            )
+        })?;
     }
 
-    run_step_script("script", &step.script, out)?;
-    run_step_script("check-script", &step.check_script, out)?;
+    if !step.downloads.is_empty() {
+        with_retries(step, "downloads", || fetch_downloads(&step.downloads))?;
+    }
+    if !step.git.is_empty() {
+        with_retries(step, "git", || sync_repos(&step.git))?;
+    }
+    create_links(&step.links)?;
+    render_files(&step.files)?;
+    apply_edits(&step.edits, &step.privilege)?;
+
+    if step.script.is_some() {
+        with_retries(step, "script", || {
+            run_step_script(step, "script", &step.script, script_checker, out)
+        })?;
+    }
+    run_step_script(
+        step,
+        "check-script",
+        &step.check_script,
+        script_checker,
+        out,
+    )?;
 
     info!(event_type = %EventType::StepRunFinished.as_str());
-    Ok(())
+    Ok(new_packages)
+}
+
+fn run_step_script(
+    step: &Step,
+    name: &str,
+    script: &Option<Script>,
+    script_checker: &mut dyn ScriptChecker,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let Some(script) = script else {
+        return Ok(());
+    };
+
+    info!(event_type = %EventType::ScriptStarted.as_str(), kind=name);
+    let result = run_script(script, step.directory(), Some(script_checker), out);
+    if let Ok(res) = result.as_ref() {
+        debug!(
+            event_type = %EventType::ScriptCompleted.as_str(),
+            code = res.status.code(),
+            elapsed_secs = res.time.as_secs_f64(),
+            kind = name,
+        );
+    }
+
+    match result {
+        Ok(ScriptResult {
+            status: ScriptStatus::Success,
+            ..
+        }) => Ok(()),
+        Ok(ScriptResult {
+            status: Failed(code),
+            ..
+        }) => {
+            bail!("failed to run {name}: status code {code}")
+        }
+        Ok(ScriptResult {
+            status: ScriptStatus::TimedOut(timeout),
+            ..
+        }) => {
+            bail!(
+                "failed to run {name}: timed out after {}",
+                humantime::format_duration(timeout)
+            )
+        }
+        Err(e) => bail!("failed to run {name}: {e}"),
+    }
+}
+
+/// Runs the action up to `retries + 1` times, doubling the delay after each failed attempt.
+fn with_retries(step: &Step, kind: &str, mut action: impl FnMut() -> Result<()>) -> Result<()> {
+    let attempts = step.retries + 1;
+    let mut delay = step.retry_delay;
+    let mut attempt = 1;
+
+    loop {
+        if step.retries > 0 {
+            info!(event_type = %EventType::AttemptStarted.as_str(), kind, attempt, attempts);
+        }
+
+        match action() {
+            Err(err) if attempt < attempts => {
+                warn!(
+                    "{kind} failed: {err:#}, retrying in {}",
+                    humantime::format_duration(delay)
+                );
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -368,7 +839,7 @@ mod tests {
     use crate::EnvGuard;
     use crate::config::StepSelectionReason::MatchedFilter;
     use crate::logging::test::run_with_tracing;
-    use crate::runner::dry::RunPlan;
+    use crate::runner::RunOutcome;
     use crate::runner::script_checker::DefaultScriptChecker;
     use crate::system::pkg::PackageSource;
     use rstest::rstest;
@@ -415,6 +886,7 @@ mod tests {
             script: Some(config::Script {
                 shell: Some(Shell::Bash),
                 code: "cat file.txt".to_string(),
+                ..Default::default()
             }),
             source_file: step_path.clone(),
             selection_reason: Some(MatchedFilter),
@@ -429,6 +901,9 @@ mod tests {
             &RunParameters {
                 dry_run: false,
                 source_file_path: Path::new("/file.yaml").to_path_buf(),
+                keep_going: false,
+                batch_packages: false,
+                refresh_index: false,
             },
             &FakeStateSaver,
             &mut DefaultScriptChecker::new(),
@@ -448,6 +923,7 @@ mod tests {
             script: Some(config::Script {
                 shell: Some(Shell::PowerShellCore),
                 code: "cat file.txt".to_string(),
+                ..Default::default()
             }),
             selection_reason: Some(MatchedFilter),
             ..Default::default()
@@ -458,13 +934,18 @@ mod tests {
             &RunParameters {
                 dry_run: true,
                 source_file_path: Path::new("/file.yaml").to_path_buf(),
+                keep_going: false,
+                batch_packages: false,
+                refresh_index: false,
             },
             &FakeStateSaver,
             &mut DefaultScriptChecker::new(),
             None,
             &mut output,
-        )?
-        .unwrap();
+        )?;
+        let RunOutcome::Planned(plan) = plan else {
+            panic!("expected a dry-run plan");
+        };
 
         assert_eq!(plan.steps_to_run.len(), 1);
         assert!(!plan.steps_to_run[0].missing_shells.is_empty());
@@ -503,13 +984,18 @@ mod tests {
             &RunParameters {
                 dry_run: true,
                 source_file_path: Path::new("/file.yaml").to_path_buf(),
+                keep_going: false,
+                batch_packages: false,
+                refresh_index: false,
             },
             &FakeStateSaver,
             &mut DefaultScriptChecker::new(),
             None,
             &mut output,
-        )?
-        .unwrap();
+        )?;
+        let RunOutcome::Planned(plan) = plan else {
+            panic!("expected a dry-run plan");
+        };
 
         assert_eq!(plan.steps_to_run.len(), 1);
         assert!(
@@ -532,10 +1018,12 @@ mod tests {
             script: Some(config::Script {
                 shell: Some(Shell::Bash),
                 code: "exit 0".to_string(),
+                ..Default::default()
             }),
             check_script: Some(config::Script {
                 shell: Some(Shell::Bash),
                 code: "exit 1".to_string(),
+                ..Default::default()
             }),
             source_file: "/file.yaml".to_string(),
             selection_reason: Some(MatchedFilter),
@@ -547,6 +1035,9 @@ mod tests {
             &RunParameters {
                 dry_run: false,
                 source_file_path: Path::new("/file.yaml").to_path_buf(),
+                keep_going: false,
+                batch_packages: false,
+                refresh_index: false,
             },
             &FakeStateSaver,
             &mut DefaultScriptChecker::new(),
@@ -575,10 +1066,12 @@ mod tests {
                 script: Some(config::Script {
                     shell: Some(Shell::Bash),
                     code: "exit 0".to_string(),
+                    ..Default::default()
                 }),
                 check_script: Some(config::Script {
                     shell: Some(Shell::Bash),
                     code: "exit 0".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/file.yaml".to_string(),
                 selection_reason: Some(MatchedFilter),
@@ -590,6 +1083,7 @@ mod tests {
                 script: Some(config::Script {
                     shell: Some(Shell::Bash),
                     code: "exit 0".to_string(),
+                    ..Default::default()
                 }),
                 source_file: "/file.yaml".to_string(),
                 selection_reason: Some(MatchedFilter),
@@ -598,20 +1092,23 @@ mod tests {
             },
         ];
 
-        let mut res: Option<RunPlan> = None;
+        let mut res: Option<RunOutcome> = None;
         let trace_output = run_with_tracing(false, || {
             res = run(
                 &steps,
                 &RunParameters {
                     dry_run: false,
                     source_file_path: Path::new("/file.yaml").to_path_buf(),
+                    keep_going: false,
+                    batch_packages: false,
+                    refresh_index: false,
                 },
                 &FakeStateSaver,
                 &mut DefaultScriptChecker::new(),
                 None,
                 &mut sink(),
             )
-            .unwrap();
+            .ok();
         });
 
         assert!(
@@ -641,6 +1138,7 @@ mod tests {
                 code: r#"echo "what"
                     exit '0"#
                     .to_string(),
+                ..Default::default()
             }),
             source_file: "/file.yaml".to_string(),
             selection_reason: Some(MatchedFilter),
@@ -648,13 +1146,16 @@ mod tests {
             ..Default::default()
         }];
 
-        let mut res: Result<Option<RunPlan>> = Ok(None);
+        let mut res: Result<RunOutcome> = Ok(RunOutcome::Aborted);
         let trace_output = run_with_tracing(false, || {
             res = run(
                 &steps,
                 &RunParameters {
                     dry_run: false,
                     source_file_path: Path::new("/file.yaml").to_path_buf(),
+                    keep_going: false,
+                    batch_packages: false,
+                    refresh_index: false,
                 },
                 &FakeStateSaver,
                 &mut DefaultScriptChecker::new(),
@@ -674,6 +1175,182 @@ mod tests {
         Ok(())
     }
 
+    #[rstest]
+    #[case("[ -f marker ] || { touch marker; exit 1; }", true)]
+    #[case("exit 1", false)]
+    #[cfg(unix)]
+    fn test_run_step_retries_script(#[case] code: &str, #[case] succeeds: bool) {
+        mock_available_shells(HashSet::from_iter([Shell::Bash]));
+        let dir = tempdir().expect("Failed to create temp dir");
+        let step_path = dir.path().join("file.yaml");
+
+        let steps = vec![config::Step {
+            id: "flaky".to_string(),
+            script: Some(config::Script {
+                shell: Some(Shell::Bash),
+                code: code.to_string(),
+                ..Default::default()
+            }),
+            retries: 2,
+            retry_delay: Some(Duration::ZERO),
+            source_file: step_path.to_str().unwrap().to_string(),
+            selection_reason: Some(MatchedFilter),
+            ..Default::default()
+        }];
+
+        let mut res: Result<RunOutcome> = Ok(RunOutcome::Aborted);
+        let trace_output = run_with_tracing(false, || {
+            res = run(
+                &steps,
+                &RunParameters {
+                    dry_run: false,
+                    source_file_path: step_path.clone(),
+                    keep_going: false,
+                    batch_packages: false,
+                    refresh_index: false,
+                },
+                &FakeStateSaver,
+                &mut DefaultScriptChecker::new(),
+                None,
+                &mut sink(),
+            )
+        })
+        .as_string();
+
+        assert_eq!(res.is_ok(), succeeds, "unexpected result: {:?}", res.err());
+        assert!(trace_output.contains("script: attempt 1/3"), "{trace_output}");
+        assert!(trace_output.contains("script: attempt 2/3"), "{trace_output}");
+        assert_eq!(trace_output.contains("script: attempt 3/3"), !succeeds, "{trace_output}");
+        // the step declares nothing else to retry
+        assert_eq!(trace_output.matches(": attempt 1/3").count(), 1, "{trace_output}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_run_keep_going_skips_dependents_of_failed_step() {
+        mock_available_shells(HashSet::from_iter([Shell::Bash]));
+        let dir = tempdir().expect("Failed to create temp dir");
+        let step_path = dir.path().join("file.yaml");
+
+        let make_step = |id: &str, code: &str, dependencies: Vec<&str>| config::Step {
+            id: id.to_string(),
+            script: Some(config::Script {
+                shell: Some(Shell::Bash),
+                code: code.to_string(),
+                ..Default::default()
+            }),
+            source_file: step_path.to_str().unwrap().to_string(),
+            selection_reason: Some(MatchedFilter),
+            dependencies: dependencies.into_iter().map(String::from).collect(),
+            ..Default::default()
+        };
+        let steps = vec![
+            make_step("broken", "exit 1", vec![]),
+            make_step("dependent", "touch dependent", vec!["broken"]),
+            make_step("transitive", "touch transitive", vec!["dependent"]),
+            make_step("independent", "touch independent", vec![]),
+        ];
+
+        let mut res: Result<RunOutcome> = Ok(RunOutcome::Aborted);
+        let trace_output = run_with_tracing(false, || {
+            res = run(
+                &steps,
+                &RunParameters {
+                    dry_run: false,
+                    source_file_path: step_path.clone(),
+                    keep_going: true,
+                    batch_packages: false,
+                    refresh_index: false,
+                },
+                &FakeStateSaver,
+                &mut DefaultScriptChecker::new(),
+                None,
+                &mut sink(),
+            )
+        })
+        .as_string();
+
+        let err = res.err().unwrap();
+        assert_eq!(err.to_string(), "1 step(s) failed, 2 step(s) blocked");
+        assert!(dir.path().join("independent").exists());
+        assert!(!dir.path().join("dependent").exists());
+        assert!(!dir.path().join("transitive").exists());
+
+        assert!(
+            trace_output.contains("Step 'broken' failed: failed to run step 'broken'"),
+            "{trace_output}"
+        );
+        assert!(
+            trace_output.contains("Skipping step 'transitive': depends on failed dependent"),
+            "{trace_output}"
+        );
+        assert!(
+            trace_output.contains("Failed steps: broken\n  Blocked steps: dependent, transitive"),
+            "{trace_output}"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_run_keep_going_treats_failed_check_as_failed_step() {
+        mock_available_shells(HashSet::from_iter([Shell::Bash]));
+        let dir = tempdir().expect("Failed to create temp dir");
+        let step_path = dir.path().join("file.yaml");
+
+        let make_step = |id: &str, check: Option<&str>, dependencies: Vec<&str>| config::Step {
+            id: id.to_string(),
+            script: Some(config::Script {
+                shell: Some(Shell::Bash),
+                code: format!("touch {id}"),
+                ..Default::default()
+            }),
+            check_script: check.map(|code| config::Script {
+                shell: Some(Shell::Bash),
+                code: code.to_string(),
+                ..Default::default()
+            }),
+            source_file: step_path.to_str().unwrap().to_string(),
+            selection_reason: Some(MatchedFilter),
+            dependencies: dependencies.into_iter().map(String::from).collect(),
+            ..Default::default()
+        };
+        let steps = vec![
+            // the check has a syntax error, so it can't tell whether the step is completed
+            make_step("unchecked", Some("if then"), vec![]),
+            make_step("dependent", None, vec!["unchecked"]),
+            make_step("independent", None, vec![]),
+        ];
+
+        let mut res: Result<RunOutcome> = Ok(RunOutcome::Aborted);
+        let trace_output = run_with_tracing(false, || {
+            res = run(
+                &steps,
+                &RunParameters {
+                    dry_run: false,
+                    source_file_path: step_path.clone(),
+                    keep_going: true,
+                    batch_packages: false,
+                    refresh_index: false,
+                },
+                &FakeStateSaver,
+                &mut DefaultScriptChecker::new(),
+                None,
+                &mut sink(),
+            )
+        })
+        .as_string();
+
+        let err = res.err().unwrap();
+        assert_eq!(err.to_string(), "1 step(s) failed, 1 step(s) blocked");
+        assert!(dir.path().join("independent").exists());
+        assert!(!dir.path().join("unchecked").exists());
+        assert!(!dir.path().join("dependent").exists());
+        assert!(
+            trace_output.contains("Step 'unchecked' failed: failed to check step 'unchecked'"),
+            "{trace_output}"
+        );
+    }
+
     #[test]
     fn test_run_script_doesnt_check_script_again() -> Result<()> {
         mock_available_shells(HashSet::from_iter([Shell::Bash]));
@@ -683,10 +1360,7 @@ mod tests {
             check_value_calls: 0,
         };
 
-        let script = Script {
-            shell: Shell::Bash,
-            code: "echo \"what\"".to_string(),
-        };
+        let script = Script::new(Shell::Bash, "echo \"what\"");
 
         run_script(
             &script,
@@ -720,10 +1394,7 @@ mod tests {
         let _guard = EnvGuard::new("MEPRIS_INSTALL_COMMAND", "exit 0;");
         let step = Step {
             id: "test".to_string(),
-            check_script: Some(Script {
-                shell: Shell::Bash,
-                code: format!("exit {exit_code}"),
-            }),
+            check_script: Some(Script::new(Shell::Bash, format!("exit {exit_code}"))),
             source_file: "/test.yaml".to_string(),
             package_manager: PackageManager::Apt,
             ..Default::default()
@@ -743,14 +1414,12 @@ mod tests {
 
         let step = Step {
             id: "test".to_string(),
-            pre_script: Some(Script {
-                shell: Shell::Bash,
-                code: "exit 0".to_string(),
-            }),
+            pre_script: Some(Script::new(Shell::Bash, "exit 0")),
             source_file: "/test.yaml".to_string(),
             package_manager: PackageManager::Apt,
             packages: vec![Package {
                 name: "pkg".to_string(),
+                version: None,
                 used_alias: false,
             }],
             ..Default::default()
@@ -762,6 +1431,69 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn test_is_completed_absent_packages() -> Result<()> {
+        let _guard = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "0");
+        let _guard2 = EnvGuard::new("MEPRIS_INSTALL_COMMAND", "exit 0;");
+        let _guard3 = EnvGuard::new("MEPRIS_REMOVE_COMMAND", "exit 0;");
+
+        let step = Step {
+            id: "test".to_string(),
+            source_file: "/test.yaml".to_string(),
+            package_manager: PackageManager::Apt,
+            absent_packages: vec![Package {
+                name: "nano".to_string(),
+                version: None,
+                used_alias: false,
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            step.is_completed(None)?,
+            StepCompletedResult::InstalledAbsentPackages(vec!["nano".to_string()])
+        );
+
+        let _guard = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+        assert_eq!(step.is_completed(None)?, StepCompletedResult::Completed);
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_is_completed_outdated_package() -> Result<()> {
+        let _guard = EnvGuard::new("MEPRIS_INSTALL_COMMAND", "exit 0;");
+        let _guard2 = EnvGuard::new("MEPRIS_INSTALLED_VERSION", "18.19.0");
+
+        let step = Step {
+            id: "test".to_string(),
+            source_file: "/test.yaml".to_string(),
+            package_manager: PackageManager::Npm,
+            packages: vec![Package {
+                name: "node".to_string(),
+                version: Some(VersionReq::parse(">=20")?),
+                used_alias: false,
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            step.is_completed(None)?,
+            StepCompletedResult::NotInstalledPackages(vec!["node".to_string()])
+        );
+
+        let _guard2 = EnvGuard::new("MEPRIS_INSTALLED_VERSION", "20.11.1");
+        assert_eq!(step.is_completed(None)?, StepCompletedResult::Completed);
+
+        let _guard2 = EnvGuard::new("MEPRIS_INSTALLED_VERSION", "");
+        assert_eq!(
+            step.is_completed(None)?,
+            StepCompletedResult::NotInstalledPackages(vec!["node".to_string()])
+        );
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_is_completed_script_requires_check_script() -> Result<()> {
@@ -770,10 +1502,7 @@ mod tests {
             id: "test".to_string(),
             source_file: "/test.yaml".to_string(),
             package_manager: PackageManager::Apt,
-            script: Some(Script {
-                shell: Shell::Bash,
-                code: "exit 0".to_string(),
-            }),
+            script: Some(Script::new(Shell::Bash, "exit 0")),
             ..Default::default()
         };
 
@@ -783,6 +1512,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_is_completed_links() -> Result<()> {
+        let dir = tempdir().expect("Failed to create temp dir");
+        fs::write(dir.path().join("bashrc"), "")?;
+        let step = Step {
+            id: "test".to_string(),
+            source_file: dir.path().join("file.yaml").to_str().unwrap().to_string(),
+            links: vec![Link {
+                src: dir.path().join("bashrc"),
+                dest: dir.path().join(".bashrc"),
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            step.is_completed(None)?,
+            StepCompletedResult::PendingLinks(vec![
+                dir.path().join(".bashrc").display().to_string()
+            ])
+        );
+
+        create_links(&step.links)?;
+        assert_eq!(step.is_completed(None)?, StepCompletedResult::Completed);
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_is_completed_script_package_manager_not_installed() -> Result<()> {
@@ -792,12 +1548,10 @@ mod tests {
             package_manager: PackageManager::Choco,
             packages: vec![Package {
                 name: "pkg".to_string(),
+                version: None,
                 used_alias: false,
             }],
-            script: Some(Script {
-                shell: Shell::Bash,
-                code: "exit 0".to_string(),
-            }),
+            script: Some(Script::new(Shell::Bash, "exit 0")),
             ..Default::default()
         };
 
@@ -1,9 +1,12 @@
 use crate::config::Step;
 use crate::logging::EventType;
+use crate::runner::Package;
 use crate::system::os_info::{DEFAULT_PACKAGE_MANAGER, OS_INFO, Platform};
 use crate::system::pkg::PackageManager;
+use crate::system::privilege::Privilege;
 use anyhow::bail;
-use tracing::info;
+use std::collections::HashSet;
+use tracing::{info, warn};
 
 pub fn resolve_step_package_manager(step: &Step) -> PackageManager {
     if let Some(source) = &step.package_source {
@@ -30,11 +33,94 @@ pub fn resolve_step_package_manager(step: &Step) -> PackageManager {
     DEFAULT_PACKAGE_MANAGER.clone()
 }
 
-pub fn install_packages(packages: &[String], manager: &PackageManager) -> anyhow::Result<()> {
+/// Installs packages without a version constraint in one call, then each versioned package.
+pub fn install_packages(
+    packages: &[Package],
+    manager: &PackageManager,
+    privilege: &Privilege,
+) -> anyhow::Result<()> {
     if std::env::var("MEPRIS_INSTALL_COMMAND").is_err() && !manager.is_available() {
         bail!("Package manager {} not found", manager);
     }
 
-    info!(event_type=%EventType::PackagesInstallStarted, packages = packages.join(", "));
-    manager.install(packages)
+    let specs = packages.iter().map(Package::spec).collect::<Vec<String>>();
+    info!(event_type=%EventType::PackagesInstallStarted, packages = specs.join(", "));
+
+    let unversioned = packages
+        .iter()
+        .filter(|p| p.version.is_none())
+        .map(|p| p.name.clone())
+        .collect::<Vec<String>>();
+    if !unversioned.is_empty() {
+        manager.install(&unversioned, privilege)?;
+    }
+    for pkg in packages {
+        if let Some(version) = &pkg.version {
+            manager.install_version(&pkg.name, version, privilege)?;
+        }
+    }
+    Ok(())
+}
+
+/// Refreshes the package index of each manager at most once per run, before its first installation.
+pub struct IndexRefresher {
+    enabled: bool,
+    refreshed: HashSet<PackageManager>,
+}
+
+impl IndexRefresher {
+    pub fn new(enabled: bool) -> Self {
+        IndexRefresher {
+            enabled,
+            refreshed: HashSet::new(),
+        }
+    }
+
+    /// Whether the index of the manager would be refreshed before installing packages.
+    pub fn is_pending(&self, manager: &PackageManager) -> bool {
+        self.enabled && manager.has_index() && !self.refreshed.contains(manager)
+    }
+
+    pub fn refresh(&mut self, manager: &PackageManager, privilege: &Privilege) {
+        if !self.is_pending(manager) {
+            return;
+        }
+        self.refreshed.insert(manager.clone());
+
+        info!(event_type=%EventType::PackageIndexRefreshStarted, manager = manager.to_string());
+        // packages may still be installable from the old index
+        if let Err(err) = manager.refresh(privilege) {
+            warn!("failed to refresh package index of {manager}: {err:#}");
+        }
+    }
+}
+
+pub fn remove_packages(
+    packages: &[String],
+    manager: &PackageManager,
+    privilege: &Privilege,
+) -> anyhow::Result<()> {
+    if !manager.can_remove() {
+        bail!("Package manager {} not found", manager);
+    }
+
+    info!(event_type=%EventType::PackagesRemoveStarted, packages = packages.join(", "));
+    manager.remove(packages, privilege)
+}
+
+pub fn upgrade_packages(
+    packages: &[String],
+    manager: &PackageManager,
+    privilege: &Privilege,
+) -> anyhow::Result<()> {
+    if std::env::var("MEPRIS_UPGRADE_COMMAND").is_err() && !manager.is_available() {
+        bail!("Package manager {} not found", manager);
+    }
+
+    info!(
+        event_type=%EventType::PackagesUpgradeStarted,
+        manager = manager.to_string(),
+        packages = packages.join(", ")
+    );
+    manager.upgrade(packages, privilege)
 }
@@ -0,0 +1,325 @@
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use tempfile::NamedTempFile;
+use tracing::info;
+
+use crate::config;
+use crate::logging::EventType;
+use crate::runner::downloads;
+use crate::system::pkg::PackageManager;
+use crate::system::privilege::{Privilege, run, run_root};
+use crate::utils::file::resolve_path;
+
+const APT_SOURCES_DIR: &str = "/etc/apt/sources.list.d";
+const APT_KEYRINGS_DIR: &str = "/etc/apt/keyrings";
+const YUM_REPOS_DIR: &str = "/etc/yum.repos.d";
+const PACMAN_CONF: &str = "/etc/pacman.conf";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageRepo {
+    pub source: config::PackageRepo,
+}
+
+impl PackageRepo {
+    pub fn from(repo: &config::PackageRepo, step_dir: &Path) -> Self {
+        let mut source = repo.clone();
+        if let config::PackageRepo::Apt { key, .. } = &mut source
+            && !is_url(key)
+        {
+            *key = resolve_path(key, step_dir).display().to_string();
+        }
+        PackageRepo { source }
+    }
+
+    pub fn name(&self) -> &str {
+        self.source.name()
+    }
+
+    /// Whether the repository is configured; apt sources also have to match the config.
+    pub fn is_added(&self) -> Result<bool> {
+        if let Ok(result) = std::env::var("MEPRIS_REPOSITORY_ADDED_RESULT") {
+            return Ok(result == "0");
+        }
+
+        match &self.source {
+            config::PackageRepo::Apt { name, key, .. } => {
+                Ok(fs::read_to_string(apt_sources_path(name))
+                    .is_ok_and(|content| content == self.apt_sources())
+                    && apt_key_path(name, key).exists())
+            }
+            config::PackageRepo::Copr { name } => Ok(copr_repo_path(name).exists()),
+            config::PackageRepo::Flatpak { name, .. } => list_contains(
+                PackageManager::Flatpak,
+                &["remotes", "--columns=name"],
+                name,
+            ),
+            config::PackageRepo::Scoop { name, .. } => {
+                list_contains(PackageManager::Scoop, &["bucket", "list"], name)
+            }
+            config::PackageRepo::Pacman { name, .. } => Ok(fs::read_to_string(PACMAN_CONF)
+                .is_ok_and(|conf| conf.lines().any(|line| line.trim() == format!("[{name}]")))),
+        }
+    }
+
+    /// Content of the deb822 `.sources` file.
+    fn apt_sources(&self) -> String {
+        let config::PackageRepo::Apt {
+            name,
+            uri,
+            suite,
+            components,
+            key,
+        } = &self.source
+        else {
+            return String::new();
+        };
+
+        let mut sources = format!("Types: deb\nURIs: {uri}\nSuites: {suite}\n");
+        if !components.is_empty() {
+            sources += &format!("Components: {}\n", components.join(" "));
+        }
+        sources += &format!("Signed-By: {}\n", apt_key_path(name, key).display());
+        sources
+    }
+
+    fn pacman_section(&self) -> String {
+        let config::PackageRepo::Pacman {
+            name,
+            server,
+            sig_level,
+        } = &self.source
+        else {
+            return String::new();
+        };
+
+        let mut section = format!("\n[{name}]\n");
+        if let Some(sig_level) = sig_level {
+            section += &format!("SigLevel = {sig_level}\n");
+        }
+        section += &format!("Server = {server}\n");
+        section
+    }
+}
+
+impl fmt::Display for PackageRepo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match &self.source {
+            config::PackageRepo::Apt { .. } => "apt",
+            config::PackageRepo::Copr { .. } => "copr",
+            config::PackageRepo::Flatpak { .. } => "flatpak",
+            config::PackageRepo::Scoop { .. } => "scoop",
+            config::PackageRepo::Pacman { .. } => "pacman",
+        };
+        write!(f, "{} ({kind})", self.name())
+    }
+}
+
+pub fn add_repositories(repos: &[PackageRepo], privilege: &Privilege) -> Result<()> {
+    let mut pending = Vec::new();
+    for repo in repos {
+        if !repo.is_added()? {
+            pending.push(repo);
+        }
+    }
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        event_type = %EventType::RepositoriesAddStarted.as_str(),
+        repositories = pending
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+
+    for repo in pending {
+        add(repo, privilege)
+            .with_context(|| format!("failed to add repository '{}'", repo.name()))?;
+    }
+    Ok(())
+}
+
+fn add(repo: &PackageRepo, privilege: &Privilege) -> Result<()> {
+    if let Ok(cmd) = std::env::var("MEPRIS_ADD_REPOSITORY_COMMAND") {
+        let parts = shell_words::split(&cmd)?;
+        let (program, args) = parts.split_first().unwrap();
+        return run(program, &[args, &[repo.name().to_string()]].concat());
+    }
+
+    match &repo.source {
+        config::PackageRepo::Apt { name, key, .. } => {
+            install_root_file(&apt_key_path(name, key), &read_key(key)?, privilege)?;
+            install_root_file(
+                &apt_sources_path(name),
+                repo.apt_sources().as_bytes(),
+                privilege,
+            )?;
+            PackageManager::Apt.refresh(privilege)
+        }
+        config::PackageRepo::Copr { name } => {
+            run_root(privilege, &["dnf", "copr", "enable", "-y", name])
+        }
+        config::PackageRepo::Flatpak { name, url } => {
+            run("flatpak", &["remote-add", "--if-not-exists", name, url])
+        }
+        config::PackageRepo::Scoop { name, url } => {
+            let mut args = vec!["bucket", "add", name.as_str()];
+            args.extend(url.as_deref());
+            run("scoop.cmd", &args)
+        }
+        config::PackageRepo::Pacman { .. } => {
+            let mut conf = fs::read_to_string(PACMAN_CONF).context("failed to read pacman.conf")?;
+            conf += &repo.pacman_section();
+            install_root_file(Path::new(PACMAN_CONF), conf.as_bytes(), privilege)?;
+            // the packages of the new repository can't be installed before its database is synced
+            run_root(privilege, &["pacman", "-Sy"])
+        }
+    }
+}
+
+fn apt_sources_path(name: &str) -> PathBuf {
+    Path::new(APT_SOURCES_DIR).join(format!("{name}.sources"))
+}
+
+/// Binary keys need the `.gpg` extension, apt expects armored keys in `.asc` files.
+fn apt_key_path(name: &str, key: &str) -> PathBuf {
+    let extension = if key.ends_with(".gpg") { "gpg" } else { "asc" };
+    Path::new(APT_KEYRINGS_DIR).join(format!("{name}.{extension}"))
+}
+
+/// File written by `dnf copr enable`; group projects like `@fedora/docs` belong to `group_fedora`.
+fn copr_repo_path(name: &str) -> PathBuf {
+    let (owner, project) = name.split_once('/').unwrap_or((name, ""));
+    let owner = match owner.strip_prefix('@') {
+        Some(group) => format!("group_{group}"),
+        None => owner.to_string(),
+    };
+    Path::new(YUM_REPOS_DIR).join(format!(
+        "_copr:copr.fedorainfracloud.org:{owner}:{project}.repo"
+    ))
+}
+
+fn is_url(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+fn read_key(key: &str) -> Result<Vec<u8>> {
+    if !is_url(key) {
+        return fs::read(key).context(format!("failed to read key '{key}'"));
+    }
+
+    let mut response = downloads::agent()
+        .get(key)
+        .call()
+        .context("failed to fetch key")?;
+    let mut content = Vec::new();
+    response
+        .body_mut()
+        .as_reader()
+        .read_to_end(&mut content)
+        .context("failed to read key")?;
+    Ok(content)
+}
+
+/// Writes a file owned by root, creating missing directories.
+fn install_root_file(dest: &Path, content: &[u8], privilege: &Privilege) -> Result<()> {
+    let mut temp = NamedTempFile::new().context("failed to create temp file")?;
+    std::io::Write::write_all(&mut temp, content).context("failed to write temp file")?;
+    let src = temp.path().display().to_string();
+    let dest = dest.display().to_string();
+    run_root(privilege, &["install", "-D", "-m", "644", &src, &dest])
+}
+
+/// Whether the first column of the list printed by the manager contains `name`.
+fn list_contains(manager: PackageManager, args: &[&str], name: &str) -> Result<bool> {
+    if !manager.is_available() {
+        return Ok(false);
+    }
+
+    let bin = match manager {
+        PackageManager::Flatpak => "flatpak",
+        PackageManager::Scoop => "scoop.cmd",
+        _ => bail!("{manager} repositories can't be listed"),
+    };
+    let output = Command::new(bin)
+        .args(args)
+        .output()
+        .context(format!("failed to run {bin}"))?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .any(|line| line.split_whitespace().next() == Some(name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apt(key: &str) -> config::PackageRepo {
+        config::PackageRepo::Apt {
+            name: "docker".to_string(),
+            uri: "https://download.docker.com/linux/ubuntu".to_string(),
+            suite: "noble".to_string(),
+            components: vec!["stable".to_string()],
+            key: key.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apt_sources() {
+        let repo = PackageRepo::from(&apt("keys/docker.gpg"), Path::new("/config"));
+
+        let config::PackageRepo::Apt { key, .. } = &repo.source else {
+            panic!("unexpected repository {repo}");
+        };
+        assert_eq!(key, "/config/keys/docker.gpg");
+        assert_eq!(
+            repo.apt_sources(),
+            "Types: deb\n\
+             URIs: https://download.docker.com/linux/ubuntu\n\
+             Suites: noble\n\
+             Components: stable\n\
+             Signed-By: /etc/apt/keyrings/docker.gpg\n"
+        );
+    }
+
+    #[test]
+    fn test_repository_paths() {
+        assert_eq!(
+            apt_key_path("docker", "https://download.docker.com/linux/ubuntu/gpg"),
+            Path::new("/etc/apt/keyrings/docker.asc")
+        );
+        assert_eq!(
+            copr_repo_path("atim/lazygit"),
+            Path::new("/etc/yum.repos.d/_copr:copr.fedorainfracloud.org:atim:lazygit.repo")
+        );
+        assert_eq!(
+            copr_repo_path("@fedora/docs"),
+            Path::new("/etc/yum.repos.d/_copr:copr.fedorainfracloud.org:group_fedora:docs.repo")
+        );
+    }
+
+    #[test]
+    fn test_pacman_section() {
+        let repo = PackageRepo::from(
+            &config::PackageRepo::Pacman {
+                name: "chaotic-aur".to_string(),
+                server: "https://cdn-mirror.chaotic.cx/$repo/$arch".to_string(),
+                sig_level: Some("Optional TrustAll".to_string()),
+            },
+            Path::new("/config"),
+        );
+
+        assert_eq!(
+            repo.pacman_section(),
+            "\n[chaotic-aur]\nSigLevel = Optional TrustAll\nServer = https://cdn-mirror.chaotic.cx/$repo/$arch\n"
+        );
+        assert_eq!(repo.to_string(), "chaotic-aur (pacman)");
+    }
+}
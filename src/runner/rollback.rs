@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use tracing::{info, info_span};
+
+use crate::config;
+use crate::config::aliases::load_aliases;
+use crate::logging::EventType;
+use crate::runner::pkg::remove_packages;
+use crate::runner::script_checker::ScriptChecker;
+use crate::runner::{Step, run_step_script, with_retries};
+
+#[derive(Debug, Default)]
+pub struct StepUndo {
+    pub id: String,
+    pub source_file: String,
+    pub has_undo_script: bool,
+    pub package_manager: String,
+    pub packages_to_remove: Vec<String>,
+}
+
+impl StepUndo {
+    pub fn is_empty(&self) -> bool {
+        !self.has_undo_script && self.packages_to_remove.is_empty()
+    }
+}
+
+/// Undoes `steps` in the given order: runs the undo script, then removes the packages mepris installed
+/// for the step, `installed_packages` by step id. In dry-run mode nothing is changed and the undo plan
+/// is returned instead.
+pub fn rollback(
+    steps: &[config::Step],
+    source_file_path: &Path,
+    installed_packages: &BTreeMap<String, Vec<String>>,
+    dry_run: bool,
+    undone: &mut Vec<String>,
+    script_checker: &mut dyn ScriptChecker,
+    out: &mut impl Write,
+) -> Result<Option<Vec<StepUndo>>> {
+    let aliases = load_aliases(source_file_path.parent().unwrap())?;
+    let steps: Vec<Step> = steps.iter().map(|s| Step::from(s, &aliases)).collect();
+
+    let _span = info_span!("rollback").entered();
+    let total_steps = steps.len();
+    let mut plan = Vec::new();
+
+    for (i, step) in steps.iter().enumerate() {
+        let _span = info_span!(
+            "step",
+            step_id = step.id,
+            number = i + 1,
+            total = total_steps
+        )
+        .entered();
+
+        let recorded = installed_packages
+            .get(&step.id)
+            .map_or(&[][..], Vec::as_slice);
+        let undo = plan_undo(step, recorded)?;
+        if dry_run {
+            plan.push(undo);
+            continue;
+        }
+        if undo.is_empty() {
+            info!(event_type = %EventType::NothingToUndo.as_str());
+            undone.push(step.id.clone());
+            continue;
+        }
+
+        undo_step(step, &undo.packages_to_remove, script_checker, out)
+            .context(format!("failed to undo step '{}'", step.id))?;
+        undone.push(step.id.clone());
+    }
+
+    if dry_run {
+        return Ok(Some(plan));
+    }
+    info!(event_type = %EventType::RollbackCompleted.as_str());
+    Ok(None)
+}
+
+/// Only packages in `recorded`, which mepris installed, are removed.
+fn plan_undo(step: &Step, recorded: &[String]) -> Result<StepUndo> {
+    let mut packages_to_remove = Vec::new();
+    // packages of an unavailable manager can't be installed
+    if step.package_manager.can_remove() {
+        for pkg in step.packages.iter().filter(|p| recorded.contains(&p.name)) {
+            if step.package_manager.is_installed(&pkg.name)? {
+                packages_to_remove.push(pkg.name.clone());
+            }
+        }
+    }
+
+    Ok(StepUndo {
+        id: step.id.clone(),
+        source_file: step.source_file.clone(),
+        has_undo_script: step.undo_script.is_some(),
+        package_manager: step.package_manager.to_string(),
+        packages_to_remove,
+    })
+}
+
+fn undo_step(
+    step: &Step,
+    packages: &[String],
+    script_checker: &mut dyn ScriptChecker,
+    out: &mut impl Write,
+) -> Result<()> {
+    info!(event_type = %EventType::StepUndoStarted.as_str());
+
+    if step.undo_script.is_some() {
+        with_retries(step, "undo-script", || {
+            run_step_script(step, "undo-script", &step.undo_script, script_checker, out)
+        })?;
+    }
+    if !packages.is_empty() {
+        with_retries(step, "packages removal", || {
+            remove_packages(packages, &step.package_manager, &step.privilege)
+        })?;
+    }
+
+    info!(event_type = %EventType::StepUndoFinished.as_str());
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::EnvGuard;
+    use crate::config::StepSelectionReason::MatchedFilter;
+    use crate::runner::script_checker::DefaultScriptChecker;
+    use crate::system::shell::{Shell, mock_available_shells};
+    use serial_test::serial;
+    use std::collections::HashSet;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn step(dir: &Path, id: &str, undo: Option<&str>, packages: &[&str]) -> config::Step {
+        config::Step {
+            id: id.to_string(),
+            undo_script: undo.map(|code| config::Script {
+                shell: Some(Shell::Bash),
+                code: code.to_string(),
+                ..Default::default()
+            }),
+            packages: packages.iter().map(|p| p.to_string()).collect(),
+            source_file: dir.join("mepris.yaml").display().to_string(),
+            selection_reason: Some(MatchedFilter),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_rollback_runs_undo_script_and_removes_packages() {
+        mock_available_shells(HashSet::from_iter([Shell::Bash]));
+        let dir = tempdir().unwrap();
+        let log = dir.path().join("log");
+        let _guard = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "0");
+        let _guard2 = EnvGuard::new(
+            "MEPRIS_REMOVE_COMMAND",
+            &format!("sh -c 'echo removed \"$@\" >> {}' sh", log.display()),
+        );
+
+        let steps = vec![
+            step(
+                dir.path(),
+                "editor",
+                Some("echo undo editor >> log"),
+                &["helix", "git"],
+            ),
+            step(dir.path(), "empty", None, &[]),
+            step(dir.path(), "shell", Some("echo undo shell >> log"), &[]),
+        ];
+        // git was installed before mepris ran
+        let installed = BTreeMap::from([("editor".to_string(), vec!["helix".to_string()])]);
+
+        let mut undone = Vec::new();
+        rollback(
+            &steps,
+            &dir.path().join("mepris.yaml"),
+            &installed,
+            false,
+            &mut undone,
+            &mut DefaultScriptChecker::new(),
+            &mut std::io::sink(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "undo editor\nremoved helix\nundo shell\n"
+        );
+        assert_eq!(undone, vec!["editor", "empty", "shell"]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_rollback_dry_run() {
+        let dir = tempdir().unwrap();
+        let _guard = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "0");
+        let _guard2 = EnvGuard::new("MEPRIS_REMOVE_COMMAND", "false");
+
+        let plan = rollback(
+            &[step(dir.path(), "editor", Some("exit 1"), &["helix"])],
+            &dir.path().join("mepris.yaml"),
+            &BTreeMap::from([("editor".to_string(), vec!["helix".to_string()])]),
+            true,
+            &mut Vec::new(),
+            &mut DefaultScriptChecker::new(),
+            &mut std::io::sink(),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(plan.len(), 1);
+        assert!(plan[0].has_undo_script);
+        assert_eq!(plan[0].packages_to_remove, vec!["helix"]);
+    }
+}
@@ -1,12 +1,14 @@
 use crate::config;
 use crate::runner::script_checker::ScriptChecker;
 use crate::system::os_info::{OS_INFO, Platform};
+use crate::system::privilege::Privilege;
 use crate::system::shell::Shell;
 use anyhow::{Context, bail};
 use std::io::{Read, Write};
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
 use std::time::{Duration, Instant};
 use std::{env, thread};
 use tempfile::Builder;
@@ -15,9 +17,21 @@ use tempfile::TempPath;
 pub struct Script {
     pub shell: Shell,
     pub code: String,
+    pub timeout: Option<Duration>,
+    /// Escalation of privileged scripts, `None` if the script runs as the current user.
+    pub privilege: Option<Privilege>,
 }
 
 impl Script {
+    pub fn new(shell: Shell, code: impl Into<String>) -> Self {
+        Script {
+            shell,
+            code: code.into(),
+            timeout: None,
+            privilege: None,
+        }
+    }
+
     pub fn from(script: &config::Script, defaults: &Option<config::Defaults>) -> Self {
         let res_shell: Shell = if script.shell.is_some() {
             script.shell.as_ref().unwrap().clone()
@@ -28,6 +42,10 @@ impl Script {
         Script {
             shell: res_shell,
             code: script.code.clone(),
+            timeout: script.timeout,
+            privilege: script
+                .privileged
+                .then(|| resolve_privilege(OS_INFO.platform, defaults)),
         }
     }
 }
@@ -47,9 +65,25 @@ pub fn resolve_shell(platform: Platform, defaults: &Option<config::Defaults>) ->
     }
 }
 
+/// `linux_privilege` only applies to Linux: macOS always uses sudo, Windows has no escalation.
+pub fn resolve_privilege(platform: Platform, defaults: &Option<config::Defaults>) -> Privilege {
+    match platform {
+        Platform::Linux => defaults
+            .as_ref()
+            .and_then(|d| d.linux_privilege.clone())
+            .unwrap_or_default(),
+        Platform::MacOS => Privilege::Auto,
+        Platform::Windows => Privilege::None,
+    }
+}
+
+/// Exit code reported for timed out scripts, the same as `timeout(1)` uses.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
 pub enum ScriptStatus {
     Success,
     Failed(i32),
+    TimedOut(Duration),
 }
 
 impl ScriptStatus {
@@ -57,6 +91,7 @@ impl ScriptStatus {
         match self {
             ScriptStatus::Success => 0,
             ScriptStatus::Failed(code) => *code,
+            ScriptStatus::TimedOut(_) => TIMEOUT_EXIT_CODE,
         }
     }
 }
@@ -86,11 +121,12 @@ pub fn run_script(
     let time = Instant::now();
     let status = run_interactive_command(
         dir,
-        (cmd, &args),
+        (&cmd, &args),
+        script.timeout,
         env::var("MEPRIS_TEST_SCRIPT_OUTPUT").is_ok(),
         out,
     )?;
-    get_script_result(&status, &time)
+    get_script_result(status, script, &time)
 }
 
 pub fn run_noninteractive_script(
@@ -105,21 +141,35 @@ pub fn run_noninteractive_script(
     }
 
     let (cmd, args, _temp_file) = get_script_cmd(script);
-    let mut child = Command::new(cmd)
+    let mut command = Command::new(&cmd);
+    command
         .args(args)
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .current_dir(dir)
+        .current_dir(dir);
+    set_process_group(&mut command, script.timeout, false);
+
+    let mut child = command
         .spawn()
         .context(format!("failed to run {}", cmd))?;
 
     let time = Instant::now();
-    let status = child.wait()?;
-    get_script_result(&status, &time)
+    let deadline = script.timeout.map(|timeout| time + timeout);
+    let status = wait_until(&mut child, deadline)?;
+    get_script_result(status, script, &time)
 }
 
-fn get_script_cmd(script: &Script) -> (&str, Vec<String>, Option<TempPath>) {
+fn get_script_cmd(script: &Script) -> (String, Vec<String>, Option<TempPath>) {
+    let (cmd, args, temp_path) = get_shell_cmd(script);
+    let (cmd, args) = match &script.privilege {
+        Some(privilege) => privilege.wrap(cmd, args),
+        None => (cmd.to_string(), args),
+    };
+    (cmd, args, temp_path)
+}
+
+fn get_shell_cmd(script: &Script) -> (&str, Vec<String>, Option<TempPath>) {
     match script.shell {
         Shell::Bash => (
             Shell::Bash.get_command(),
@@ -164,10 +214,19 @@ fn requires_syntax_check_before_run(shell: Shell) -> bool {
     }
 }
 
+/// `status` is `None` if the script was killed after reaching its timeout.
 fn get_script_result(
-    status: &std::process::ExitStatus,
+    status: Option<ExitStatus>,
+    script: &Script,
     time: &Instant,
 ) -> anyhow::Result<ScriptResult> {
+    let Some(status) = status else {
+        return Ok(ScriptResult {
+            status: ScriptStatus::TimedOut(script.timeout.unwrap_or_default()),
+            time: time.elapsed(),
+        });
+    };
+
     if !status.success() {
         match status.code() {
             Some(code) => {
@@ -188,12 +247,14 @@ fn get_script_result(
 fn run_interactive_command(
     dir: &Path,
     (cmd, args): (&str, &[String]),
+    timeout: Option<Duration>,
     testable_output: bool,
     out: &mut dyn Write,
-) -> anyhow::Result<std::process::ExitStatus> {
+) -> anyhow::Result<Option<ExitStatus>> {
     let mut command = Command::new(cmd);
     command.args(args);
     command.stdin(Stdio::inherit());
+    let _terminal = set_process_group(&mut command, timeout, true);
 
     if testable_output {
         command.stdout(Stdio::piped()).stderr(Stdio::piped());
@@ -205,6 +266,7 @@ fn run_interactive_command(
         .current_dir(dir)
         .spawn()
         .context(format!("failed to run {}", cmd))?;
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
 
     if testable_output {
         let mut stdout = child.stdout.take().unwrap();
@@ -254,23 +316,139 @@ fn run_interactive_command(
 
         drop(tx);
 
-        for chunk in rx {
+        loop {
+            let chunk = match deadline {
+                Some(deadline) => {
+                    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(chunk) => chunk,
+                        Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                None => match rx.recv() {
+                    Ok(chunk) => chunk,
+                    Err(_) => break,
+                },
+            };
             let s = String::from_utf8_lossy(&chunk);
             write!(out, "{s}")?;
             out.flush()?;
         }
     }
 
-    let status = child.wait()?;
-    Ok(status)
+    wait_until(&mut child, deadline)
+}
+
+/// Scripts with a timeout run in their own process group, so the whole process tree
+/// can be killed when the timeout is reached.
+///
+/// A background process group is stopped when it reads from the terminal and doesn't get Ctrl-C,
+/// so a script that inherits a terminal as stdin becomes the foreground process group until
+/// the returned guard is dropped.
+fn set_process_group(
+    command: &mut Command,
+    timeout: Option<Duration>,
+    inherits_stdin: bool,
+) -> Option<TerminalGuard> {
+    #[cfg(unix)]
+    if timeout.is_some() {
+        use std::io::IsTerminal;
+        use std::os::unix::process::CommandExt;
+
+        if !inherits_stdin || !std::io::stdin().is_terminal() {
+            command.process_group(0);
+            return None;
+        }
+        // SAFETY: only async-signal-safe calls run between fork and exec
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setpgid(0, 0) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                set_foreground_group(libc::getpgrp());
+                Ok(())
+            });
+        }
+        return Some(TerminalGuard);
+    }
+    #[cfg(not(unix))]
+    let _ = (command, timeout, inherits_stdin);
+    None
+}
+
+/// Makes the process group of mepris the foreground process group again when dropped.
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        // SAFETY: plain syscall
+        set_foreground_group(unsafe { libc::getpgrp() });
+    }
+}
+
+/// Background process groups get SIGTTOU when they change the foreground group, so it is ignored meanwhile.
+#[cfg(unix)]
+fn set_foreground_group(pgid: libc::pid_t) {
+    // SAFETY: plain syscalls on stdin, the previous SIGTTOU disposition is restored
+    unsafe {
+        let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+        libc::signal(libc::SIGTTOU, previous);
+    }
+}
+
+/// Waits for the child to exit. Returns `None` if the deadline was reached,
+/// in which case the child and all its descendants are killed.
+fn wait_until(child: &mut Child, deadline: Option<Instant>) -> anyhow::Result<Option<ExitStatus>> {
+    let Some(deadline) = deadline else {
+        return Ok(Some(child.wait()?));
+    };
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            kill_process_tree(child)?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn kill_process_tree(child: &mut Child) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        let pgid = child.id() as libc::pid_t;
+        // SAFETY: plain syscall; the negative pid addresses the process group created for the script
+        if unsafe { libc::kill(-pgid, libc::SIGKILL) } == 0 {
+            return Ok(());
+        }
+    }
+    #[cfg(windows)]
+    {
+        let killed = Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &child.id().to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if killed {
+            return Ok(());
+        }
+    }
+
+    child.kill().context("failed to kill timed out script")
 }
 
 #[cfg(test)]
 mod tests {
     use crate::config::Defaults;
     use crate::EnvGuard;
-    use crate::runner::script::resolve_shell;
+    use crate::runner::script::{resolve_privilege, resolve_shell};
     use crate::system::os_info::Platform::{Linux, MacOS, Windows};
+    use crate::system::privilege::Privilege;
     use crate::system::shell::Shell::{Bash, PowerShell, PowerShellCore};
 
     #[test]
@@ -282,10 +460,10 @@ mod tests {
         mock_available_shells(HashSet::from_iter([Bash]));
         let _guard = EnvGuard::new("MEPRIS_TEST_SCRIPT_OUTPUT", "1");
 
-        let script = super::Script {
-            shell: Bash,
-            code: "false; false && echo should-not-reach; echo should-not-reach".to_string(),
-        };
+        let script = super::Script::new(
+            Bash,
+            "false; false && echo should-not-reach; echo should-not-reach",
+        );
         let mut buf = Vec::new();
 
         let result = super::run_script(&script, std::path::Path::new("/"), None, &mut buf);
@@ -295,6 +473,83 @@ mod tests {
         assert_eq!(result.unwrap().status.code(), 1);
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_timeout_kills_process_tree() {
+        use super::ScriptStatus;
+        use std::time::{Duration, Instant};
+
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let script = super::Script {
+            timeout: Some(Duration::from_millis(300)),
+            ..super::Script::new(
+                Bash,
+                format!("sleep 30 & echo $! > {}; wait", pid_file.display()),
+            )
+        };
+
+        let time = Instant::now();
+        let result = super::run_noninteractive_script(&script, dir.path(), None).unwrap();
+
+        assert!(time.elapsed() < Duration::from_secs(10));
+        assert!(matches!(result.status, ScriptStatus::TimedOut(_)));
+        assert_eq!(result.status.code(), super::TIMEOUT_EXIT_CODE);
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()));
+        // the orphaned child is either reaped already or left as a zombie
+        assert!(
+            stat.as_ref().is_err() || stat.as_ref().unwrap().contains(") Z "),
+            "process {} is still running: {:?}",
+            pid.trim(),
+            stat
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_timeout_with_captured_output() {
+        use super::ScriptStatus;
+        use std::time::Duration;
+
+        let _guard = EnvGuard::new("MEPRIS_TEST_SCRIPT_OUTPUT", "1");
+        let script = super::Script {
+            timeout: Some(Duration::from_millis(300)),
+            ..super::Script::new(Bash, "echo started; sleep 30; echo should-not-reach")
+        };
+        let mut buf = Vec::new();
+
+        let result =
+            super::run_script(&script, std::path::Path::new("/"), None, &mut buf).unwrap();
+        let output = String::from_utf8_lossy(&buf).to_string();
+
+        assert!(matches!(result.status, ScriptStatus::TimedOut(_)));
+        assert!(output.contains("started"), "unexpected output: {}", output);
+        assert!(!output.contains("should-not-reach"), "unexpected output: {}", output);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_timeout_with_inherited_stdin() {
+        use super::ScriptStatus;
+        use std::time::Duration;
+
+        let _guard = EnvGuard::new("MEPRIS_TEST_SCRIPT_OUTPUT", "1");
+        // a script in a background process group would be stopped by the read until the timeout
+        let script = super::Script {
+            timeout: Some(Duration::from_secs(10)),
+            ..super::Script::new(Bash, "read -t 1 line || true; echo done")
+        };
+        let mut buf = Vec::new();
+
+        let result = super::run_script(&script, std::path::Path::new("/"), None, &mut buf).unwrap();
+        let output = String::from_utf8_lossy(&buf).to_string();
+
+        assert!(matches!(result.status, ScriptStatus::Success));
+        assert!(output.contains("done"), "unexpected output: {}", output);
+    }
+
     #[test]
     fn test_resolve_shell_no_defaults() {
         let linux_shell = resolve_shell(Linux, &None);
@@ -314,6 +569,8 @@ mod tests {
             windows_shell: None,
             linux_shell: None,
             macos_shell: None,
+            refresh_index: None,
+            linux_privilege: None,
         };
 
         let linux_shell = resolve_shell(Linux, &Some(defaults.clone()));
@@ -333,6 +590,8 @@ mod tests {
             windows_shell: Some(Bash),
             linux_shell: Some(PowerShellCore),
             macos_shell: Some(PowerShell),
+            refresh_index: None,
+            linux_privilege: None,
         };
 
         let linux_shell = resolve_shell(Linux, &Some(defaults.clone()));
@@ -343,4 +602,40 @@ mod tests {
         assert_eq!(windows_shell, Bash);
         assert_eq!(macos_shell, PowerShell);
     }
+
+    #[test]
+    fn test_privileged_script_cmd() {
+        let script = super::Script {
+            privilege: Some(Privilege::Doas),
+            ..super::Script::new(Bash, "echo 1")
+        };
+
+        let (cmd, args, _) = super::get_script_cmd(&script);
+
+        assert_eq!(cmd, "doas");
+        assert_eq!(args, vec!["bash", "-e", "-c", "echo 1"]);
+    }
+
+    #[test]
+    fn test_resolve_privilege() {
+        let defaults = Defaults {
+            windows_package_manager: None,
+            windows_shell: None,
+            linux_shell: None,
+            macos_shell: None,
+            refresh_index: None,
+            linux_privilege: Some(Privilege::Run0),
+        };
+
+        assert_eq!(resolve_privilege(Linux, &None), Privilege::Auto);
+        assert_eq!(
+            resolve_privilege(Linux, &Some(defaults.clone())),
+            Privilege::Run0
+        );
+        assert_eq!(
+            resolve_privilege(MacOS, &Some(defaults.clone())),
+            Privilege::Auto
+        );
+        assert_eq!(resolve_privilege(Windows, &Some(defaults)), Privilege::None);
+    }
 }
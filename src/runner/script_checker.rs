@@ -88,10 +88,7 @@ fn hash_script(script: &Script) -> String {
 #[cfg(unix)]
 fn test_checked_script_saved() {
     let mut checker = DefaultScriptChecker::new();
-    let script = Script {
-        shell: Shell::Bash,
-        code: "echo \"bash\"".to_string(),
-    };
+    let script = Script::new(Shell::Bash, "echo \"bash\"");
     assert!(!checker.is_checked(&script));
     checker.check_script(&script, false).unwrap();
     assert!(checker.is_checked(&script));
@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use anyhow::Result;
+use indexmap::IndexMap;
+use tracing::{info, info_span, warn};
+
+use crate::config;
+use crate::config::aliases::load_aliases;
+use crate::logging::EventType;
+use crate::runner::pkg::{resolve_step_package_manager, upgrade_packages};
+use crate::runner::script::resolve_privilege;
+use crate::system::os_info::OS_INFO;
+use crate::system::pkg::PackageManager;
+use crate::system::pkg::version::parse_package_spec;
+use crate::system::privilege::Privilege;
+
+#[derive(Debug, Default)]
+pub struct PackageUpgrade {
+    pub manager: PackageManager,
+    /// Escalation configured for the first step using the manager.
+    pub privilege: Privilege,
+    pub installed: Vec<String>,
+    pub not_installed: Vec<String>,
+    /// Packages pinned to a version, which upgrading would break.
+    pub pinned: Vec<String>,
+}
+
+/// Packages declared by `steps`, grouped by package manager in order of first use.
+pub fn plan_upgrade(
+    steps: &[config::Step],
+    source_file_path: &Path,
+) -> Result<Vec<PackageUpgrade>> {
+    let aliases = load_aliases(source_file_path.parent().unwrap())?;
+
+    let mut declared: IndexMap<PackageManager, (Privilege, Vec<(String, bool)>)> = IndexMap::new();
+    for step in steps.iter().filter(|s| !s.packages.is_empty()) {
+        let manager = resolve_step_package_manager(step);
+        let (_, packages) = declared.entry(manager.clone()).or_insert_with(|| {
+            (
+                resolve_privilege(OS_INFO.platform, &step.defaults),
+                Vec::new(),
+            )
+        });
+        for pkg in &step.packages {
+            let (name, version) = parse_package_spec(pkg)?;
+            let name = aliases.resolve_name(name, &manager);
+            let pinned = version.is_some_and(|v| v.pinned().is_some());
+            if !packages.iter().any(|(n, _)| n == &name) {
+                packages.push((name, pinned));
+            }
+        }
+    }
+
+    let mut plan = Vec::new();
+    for (manager, (privilege, packages)) in declared {
+        // packages of an unavailable manager can't be installed
+        let available = std::env::var("MEPRIS_UPGRADE_COMMAND").is_ok() || manager.is_available();
+        let mut upgrade = PackageUpgrade {
+            manager: manager.clone(),
+            privilege,
+            ..Default::default()
+        };
+        for (pkg, pinned) in packages {
+            if pinned {
+                upgrade.pinned.push(pkg);
+            } else if available && manager.is_installed(&pkg)? {
+                upgrade.installed.push(pkg);
+            } else {
+                upgrade.not_installed.push(pkg);
+            }
+        }
+        plan.push(upgrade);
+    }
+    Ok(plan)
+}
+
+/// Upgrades installed packages of the plan, one call per package manager.
+pub fn upgrade(plan: &[PackageUpgrade]) -> Result<()> {
+    let _span = info_span!("upgrade").entered();
+
+    for upgrade in plan {
+        if !upgrade.not_installed.is_empty() {
+            warn!(
+                "skipping packages that are not installed: {} ({})",
+                upgrade.not_installed.join(", "),
+                upgrade.manager
+            );
+        }
+        if !upgrade.pinned.is_empty() {
+            info!(
+                "skipping packages pinned to a version: {} ({})",
+                upgrade.pinned.join(", "),
+                upgrade.manager
+            );
+        }
+        if !upgrade.installed.is_empty() {
+            upgrade_packages(&upgrade.installed, &upgrade.manager, &upgrade.privilege)?;
+        }
+    }
+
+    info!(event_type = %EventType::UpgradeCompleted.as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EnvGuard;
+    use crate::system::pkg::PackageSource;
+    use serial_test::serial;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn step(packages: &[&str], source: Option<PackageManager>, dir: &Path) -> config::Step {
+        config::Step {
+            packages: packages.iter().map(|p| p.to_string()).collect(),
+            package_source: source.map(PackageSource::Manager),
+            source_file: dir.join("mepris.yaml").display().to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_plan_upgrade_groups_packages_by_manager() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("pkg_aliases.yaml"), "fd:\n  apt: fd-find\n").unwrap();
+        let _guard = EnvGuard::new("MEPRIS_UPGRADE_COMMAND", "true");
+        let _guard2 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "0");
+
+        let steps = vec![
+            step(&["git", "fd"], Some(PackageManager::Apt), dir.path()),
+            step(&["ripgrep"], Some(PackageManager::Cargo), dir.path()),
+            step(&["git", "curl@8.5"], Some(PackageManager::Apt), dir.path()),
+        ];
+
+        let plan = plan_upgrade(&steps, &dir.path().join("mepris.yaml")).unwrap();
+
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].manager, PackageManager::Apt);
+        assert_eq!(plan[0].installed, vec!["git", "fd-find"]);
+        assert_eq!(plan[0].pinned, vec!["curl"]);
+        assert_eq!(plan[1].manager, PackageManager::Cargo);
+        assert_eq!(plan[1].installed, vec!["ripgrep"]);
+    }
+}
@@ -1,3 +1,4 @@
 pub mod os_info;
 pub mod pkg;
+pub mod privilege;
 pub mod shell;
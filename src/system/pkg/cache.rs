@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Not set when mepris is used as a library or in tests: package lists are then only kept in memory.
+static SETTINGS: Mutex<Option<CacheSettings>> = Mutex::new(None);
+
+#[derive(Debug, Clone)]
+pub struct CacheSettings {
+    pub path: PathBuf,
+    pub ttl: Duration,
+    /// Ignore cached lists and list the installed packages again.
+    pub refresh: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedList {
+    /// Seconds since the Unix epoch.
+    updated: u64,
+    packages: HashSet<String>,
+}
+
+/// Enables the on-disk cache of installed package lists in the data directory; a zero `ttl` disables it.
+pub fn configure(ttl: Duration, refresh: bool) {
+    if ttl.is_zero() {
+        return;
+    }
+    let Some(dirs) = ProjectDirs::from("", "", "mepris") else {
+        debug!("could not get project dirs, package lists are not cached on disk");
+        return;
+    };
+    set_settings(CacheSettings {
+        path: dirs.data_dir().join("pkg_cache.json"),
+        ttl,
+        refresh,
+    });
+}
+
+pub fn set_settings(settings: CacheSettings) {
+    *SETTINGS.lock().unwrap() = Some(settings);
+}
+
+/// Installed packages of the manager listed less than TTL ago.
+pub fn load(cache_id: &str) -> Option<HashSet<String>> {
+    let settings = SETTINGS.lock().unwrap().clone()?;
+    if settings.refresh {
+        return None;
+    }
+
+    let mut lists = match read_lists(&settings) {
+        Ok(lists) => lists,
+        Err(err) => {
+            debug!("ignoring package cache: {err:#}");
+            return None;
+        }
+    };
+    let list = lists.remove(cache_id)?;
+    let age = now().saturating_sub(list.updated);
+    if age >= settings.ttl.as_secs() {
+        debug!("package cache for {cache_id} is outdated");
+        return None;
+    }
+    Some(list.packages)
+}
+
+pub fn store(cache_id: &str, packages: &HashSet<String>) {
+    update(|lists| {
+        lists.insert(
+            cache_id.to_string(),
+            CachedList {
+                updated: now(),
+                packages: packages.clone(),
+            },
+        );
+    });
+}
+
+/// Drops the cached list after mepris changed the installed packages of the manager.
+pub fn invalidate(cache_id: &str) {
+    update(|lists| {
+        lists.remove(cache_id);
+    });
+}
+
+fn update(change: impl FnOnce(&mut HashMap<String, CachedList>)) {
+    let Some(settings) = SETTINGS.lock().unwrap().clone() else {
+        return;
+    };
+    // an unreadable cache is replaced
+    let mut lists = read_lists(&settings).unwrap_or_default();
+    change(&mut lists);
+    if let Err(err) = write_lists(&settings, &lists) {
+        warn!("failed to save package cache: {err:#}");
+    }
+}
+
+fn read_lists(settings: &CacheSettings) -> Result<HashMap<String, CachedList>> {
+    if !settings.path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = fs::read_to_string(&settings.path).context("Failed to read package cache")?;
+    serde_json::from_str(&content).context("Failed to parse package cache")
+}
+
+fn write_lists(settings: &CacheSettings, lists: &HashMap<String, CachedList>) -> Result<()> {
+    if let Some(dir) = settings.path.parent() {
+        fs::create_dir_all(dir).context("Failed to create data directory")?;
+    }
+    fs::write(&settings.path, serde_json::to_string(lists)?)
+        .context("Failed to write package cache")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+pub fn reset_settings() {
+    *SETTINGS.lock().unwrap() = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::pkg::PackageManager;
+    use serial_test::serial;
+    use tempfile::tempdir;
+
+    fn packages(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    #[serial]
+    fn test_store_load_invalidate() {
+        let dir = tempdir().unwrap();
+        set_settings(CacheSettings {
+            path: dir.path().join("pkg_cache.json"),
+            ttl: Duration::from_secs(600),
+            refresh: false,
+        });
+
+        assert_eq!(load("apt"), None);
+        store("apt", &packages(&["git", "curl"]));
+        store("cargo", &packages(&["ripgrep"]));
+        assert_eq!(load("apt"), Some(packages(&["git", "curl"])));
+
+        invalidate("apt");
+        assert_eq!(load("apt"), None);
+        assert_eq!(load("cargo"), Some(packages(&["ripgrep"])));
+        reset_settings();
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_ignores_outdated_and_refreshed_lists() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("pkg_cache.json");
+        fs::write(
+            &path,
+            format!(
+                r#"{{"apt": {{"updated": {}, "packages": ["git"]}}}}"#,
+                now() - 700
+            ),
+        )
+        .unwrap();
+
+        let settings = |ttl: u64, refresh: bool| CacheSettings {
+            path: path.clone(),
+            ttl: Duration::from_secs(ttl),
+            refresh,
+        };
+
+        set_settings(settings(600, false));
+        assert_eq!(load("apt"), None);
+        set_settings(settings(3600, false));
+        assert_eq!(load("apt"), Some(packages(&["git"])));
+        set_settings(settings(3600, true));
+        assert_eq!(load("apt"), None);
+        reset_settings();
+    }
+
+    #[test]
+    #[serial]
+    fn test_is_installed_uses_disk_cache() {
+        let dir = tempdir().unwrap();
+        set_settings(CacheSettings {
+            path: dir.path().join("pkg_cache.json"),
+            ttl: Duration::from_secs(600),
+            refresh: false,
+        });
+        // no package manager is called for a fresh cached list
+        store("paru", &packages(&["not-in-pacman"]));
+        store("pacman", &packages(&["yay-bin"]));
+
+        assert!(PackageManager::Yay.is_installed("yay-bin").unwrap());
+        assert!(!PackageManager::Yay.is_installed("not-in-pacman").unwrap());
+        reset_settings();
+    }
+}
@@ -1,8 +1,11 @@
 use crate::logging::EventType;
 use crate::system::os_info::{OS_INFO, Platform};
-use crate::system::pkg::parsers::parse_packages_list_func;
+use crate::system::pkg::parsers::{parse_installed_version, parse_packages_list_func};
+use crate::system::pkg::version::{VersionOp, VersionReq};
+use crate::system::privilege::Privilege;
 use anyhow::{Context, bail};
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::cmp::PartialEq;
 use std::collections::{HashMap, HashSet};
@@ -13,7 +16,9 @@ use tempfile::NamedTempFile;
 use tracing::{debug, debug_span};
 use which::which;
 
+pub mod cache;
 mod parsers;
+pub mod version;
 
 thread_local! {
     static PKG_CACHE: RefCell<HashMap<String, HashSet<String>>> = RefCell::new(HashMap::new());
@@ -59,7 +64,18 @@ impl Repository {
 }
 
 #[derive(
-    Debug, Deserialize, Clone, PartialEq, Eq, EnumIter, Display, Hash, EnumString, Default,
+    Debug,
+    Deserialize,
+    Serialize,
+    JsonSchema,
+    Clone,
+    PartialEq,
+    Eq,
+    EnumIter,
+    Display,
+    Hash,
+    EnumString,
+    Default,
 )]
 #[serde(rename_all = "lowercase")]
 #[strum(serialize_all = "lowercase")]
@@ -99,38 +115,67 @@ impl PackageManager {
         }
     }
 
-    fn requires_cache(&self) -> bool {
-        parse_packages_list_func(self).is_ok()
+    /// Whether packages of the manager can be removed; a faked remove command doesn't need it.
+    pub fn can_remove(&self) -> bool {
+        std::env::var("MEPRIS_REMOVE_COMMAND").is_ok() || self.is_available()
     }
 
-    pub fn install(&self, pkgs: &[String]) -> anyhow::Result<()> {
-        if let Ok(cmd) = std::env::var("MEPRIS_INSTALL_COMMAND") {
-            let parts = shell_words::split(&cmd)?;
-            let (program, args) = parts.split_first().unwrap();
-
-            let success = Command::new(program)
-                .args(args)
-                .args(pkgs)
-                .output()?
-                .status
-                .success();
-
-            if success {
-                return Ok(());
-            } else {
-                bail!("Failed to install {}", pkgs.join(", "));
-            }
+    /// AUR helpers install into the pacman database, so they share its list of installed packages.
+    fn cache_id(&self) -> String {
+        match self {
+            Self::Yay | Self::Paru => Self::Pacman.to_string(),
+            _ => self.to_string(),
         }
+    }
 
-        fn build_cmd(cmd: &str, args: &[&str], pkgs: &[String]) -> CommandSpec {
-            CommandSpec {
-                bin: cmd.into(),
-                args: args
-                    .iter()
-                    .map(ToString::to_string)
-                    .chain(pkgs.iter().cloned())
-                    .collect(),
-            }
+    /// Whether the manager can install an exact version of a package.
+    /// Homebrew only has versioned formulae like `python@3.12`, not exact versions.
+    pub fn can_pin_versions(&self) -> bool {
+        !matches!(
+            self,
+            Self::Pacman | Self::Yay | Self::Paru | Self::Flatpak | Self::Brew
+        )
+    }
+
+    /// Updates the list of available packages; does nothing for managers without a local index.
+    pub fn refresh(&self, privilege: &Privilege) -> anyhow::Result<()> {
+        if let Ok(cmd) = std::env::var("MEPRIS_REFRESH_COMMAND") {
+            return run_override_command(&cmd, &[self.to_string()], "refresh");
+        }
+        // the index of a faked package manager is not refreshed either
+        if std::env::var("MEPRIS_INSTALL_COMMAND").is_ok() {
+            return Ok(());
+        }
+
+        match self.refresh_command(privilege) {
+            Some(cmd) => run_package_commands(&[cmd], &[self.to_string()], "refresh"),
+            None => Ok(()),
+        }
+    }
+
+    pub fn has_index(&self) -> bool {
+        self.refresh_command(&Privilege::None).is_some()
+    }
+
+    fn refresh_command(&self, privilege: &Privilege) -> Option<CommandSpec> {
+        match self {
+            Self::Apt => Some(build_root_cmd(privilege, &["apt-get", "update"], &[])),
+            Self::Dnf => Some(build_root_cmd(privilege, &["dnf", "makecache"], &[])),
+            Self::Zypper => Some(build_root_cmd(privilege, &["zypper", "refresh"], &[])),
+            Self::Brew => Some(build_cmd("brew", &["update"], &[])),
+            Self::Scoop => Some(build_cmd("scoop.cmd", &["update"], &[])),
+            Self::Winget => Some(build_cmd("winget", &["source", "update"], &[])),
+            // installs fetch the latest metadata anyway
+            Self::Flatpak | Self::Choco | Self::Cargo | Self::Npm => None,
+            // `-Sy` alone leaves a partial upgrade, which pacman doesn't support, and `-Syu`
+            // would upgrade the whole system
+            Self::Pacman | Self::Yay | Self::Paru => None,
+        }
+    }
+
+    pub fn install(&self, pkgs: &[String], privilege: &Privilege) -> anyhow::Result<()> {
+        if let Ok(cmd) = std::env::var("MEPRIS_INSTALL_COMMAND") {
+            return run_override_command(&cmd, pkgs, "install");
         }
 
         let commands = match self {
@@ -166,16 +211,24 @@ impl PackageManager {
                 })
                 .collect(),
 
-            Self::Apt => vec![build_cmd("sudo", &["apt-get", "install", "-y"], pkgs)],
-            Self::Dnf => vec![build_cmd("sudo", &["dnf", "install", "-y"], pkgs)],
-            Self::Pacman => vec![build_cmd(
-                "sudo",
+            Self::Apt => vec![build_root_cmd(
+                privilege,
+                &["apt-get", "install", "-y"],
+                pkgs,
+            )],
+            Self::Dnf => vec![build_root_cmd(privilege, &["dnf", "install", "-y"], pkgs)],
+            Self::Pacman => vec![build_root_cmd(
+                privilege,
                 &["pacman", "-S", "--noconfirm", "--needed"],
                 pkgs,
             )],
             Self::Yay => vec![build_cmd("yay", &["-S", "--noconfirm", "--needed"], pkgs)],
             Self::Paru => vec![build_cmd("paru", &["-S", "--noconfirm", "--needed"], pkgs)],
-            Self::Zypper => vec![build_cmd("sudo", &["zypper", "install", "-y"], pkgs)],
+            Self::Zypper => vec![build_root_cmd(
+                privilege,
+                &["zypper", "install", "-y"],
+                pkgs,
+            )],
             Self::Brew => vec![build_cmd("brew", &["install"], pkgs)],
             Self::Scoop => vec![build_cmd("scoop.cmd", &["install"], pkgs)],
             Self::Choco => vec![build_cmd("choco", &["install", "-y"], pkgs)],
@@ -191,139 +244,437 @@ impl PackageManager {
             )],
         };
 
-        for cmd in &commands {
-            let status = Command::new(&cmd.bin)
-                .args(&cmd.args)
-                .stdin(Stdio::null())
-                .stdout(Stdio::inherit())
-                .stderr(Stdio::inherit())
-                .status()
-                .context(format!("Failed to install {}", pkgs.join(", ")))?;
-
-            if !status.success() {
-                bail!("Failed to install {}", pkgs.join(", "));
+        run_package_commands(&commands, pkgs, "install")?;
+
+        let cache_id = self.cache_id();
+        cache::invalidate(&cache_id);
+        PKG_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            if let Some(entry) = cache.get_mut(&cache_id) {
+                pkgs.iter().for_each(|pkg| {
+                    entry.insert(pkg.clone());
+                });
             }
+        });
+
+        Ok(())
+    }
+
+    /// Installs `pkg` with the manager's version syntax. Managers without one, and constraints
+    /// that don't select a single version where the manager needs it, install the latest version.
+    pub fn install_version(
+        &self,
+        pkg: &str,
+        req: &VersionReq,
+        privilege: &Privilege,
+    ) -> anyhow::Result<()> {
+        let spec = format!("{pkg}@{req}");
+        if let Ok(cmd) = std::env::var("MEPRIS_INSTALL_COMMAND") {
+            return run_override_command(&cmd, &[spec], "install");
         }
 
-        if self.requires_cache() {
-            let cache_id = self.to_string();
-            PKG_CACHE.with(|cache| {
-                let mut cache = cache.borrow_mut();
-                if let Some(entry) = cache.get_mut(&cache_id) {
-                    pkgs.iter().for_each(|pkg| {
-                        entry.insert(pkg.clone());
-                    });
-                }
-            });
+        let pinned = req.pinned();
+        let with_version_flag = |bin: &str, args: &[&str], version: Option<&str>| {
+            let mut cmd = build_cmd(bin, args, &[pkg.to_string()]);
+            if let Some(version) = version {
+                cmd.args
+                    .extend(["--version".to_string(), version.to_string()]);
+            }
+            vec![cmd]
+        };
+
+        let commands = match self {
+            Self::Apt => {
+                let pkg = match req.op {
+                    // the Debian revision is part of the version, e.g. `14.1.0-1`
+                    VersionOp::Prefix => format!("{pkg}={}*", req.version),
+                    VersionOp::Eq => format!("{pkg}={}", req.version),
+                    _ => pkg.to_string(),
+                };
+                vec![build_root_cmd(
+                    privilege,
+                    &["apt-get", "install", "-y", "--allow-downgrades"],
+                    &[pkg],
+                )]
+            }
+            Self::Dnf => {
+                let pkg = pinned.map_or(pkg.to_string(), |v| format!("{pkg}-{v}"));
+                vec![build_root_cmd(privilege, &["dnf", "install", "-y"], &[pkg])]
+            }
+            Self::Zypper => {
+                let pkg = match req.op {
+                    VersionOp::Prefix => format!("{pkg}={}", req.version),
+                    _ => format!("{pkg}{req}"),
+                };
+                vec![build_root_cmd(
+                    privilege,
+                    &["zypper", "install", "-y"],
+                    &[pkg],
+                )]
+            }
+            Self::Brew => {
+                let pkg = pinned.map_or(pkg.to_string(), |v| format!("{pkg}@{v}"));
+                vec![build_cmd("brew", &["install"], &[pkg])]
+            }
+            Self::Scoop => {
+                let pkg = pinned.map_or(pkg.to_string(), |v| format!("{pkg}@{v}"));
+                vec![build_cmd("scoop.cmd", &["install"], &[pkg])]
+            }
+            Self::Npm => vec![build_cmd(
+                if OS_INFO.platform == Platform::Windows {
+                    "npm.cmd"
+                } else {
+                    "npm"
+                },
+                &["i", "-g"],
+                &[format!("{pkg}@{}", npm_version_range(req))],
+            )],
+            Self::Cargo => with_version_flag("cargo", &["install"], Some(&cargo_version_req(req))),
+            Self::Choco => with_version_flag("choco", &["install", "-y"], pinned),
+            Self::Winget => with_version_flag(
+                "winget",
+                &[
+                    "install",
+                    "--source",
+                    "winget",
+                    "--silent",
+                    "--accept-source-agreements",
+                    "--accept-package-agreements",
+                    "--exact",
+                    "--id",
+                ],
+                pinned,
+            ),
+            Self::Pacman | Self::Yay | Self::Paru | Self::Flatpak => {
+                return self.install(&[pkg.to_string()], privilege);
+            }
+        };
+
+        run_package_commands(&commands, &[spec], "install")?;
+
+        let cache_id = self.cache_id();
+        cache::invalidate(&cache_id);
+        PKG_CACHE.with(|cache| {
+            if let Some(entry) = cache.borrow_mut().get_mut(&cache_id) {
+                entry.insert(pkg.to_string());
+            }
+        });
+        Ok(())
+    }
+
+    pub fn remove(&self, pkgs: &[String], privilege: &Privilege) -> anyhow::Result<()> {
+        if let Ok(cmd) = std::env::var("MEPRIS_REMOVE_COMMAND") {
+            run_override_command(&cmd, pkgs, "remove")?;
+            self.forget_removed(pkgs);
+            return Ok(());
         }
 
+        let commands = match self {
+            Self::Winget => pkgs
+                .iter()
+                .map(|pkg| CommandSpec {
+                    bin: "winget".into(),
+                    args: vec![
+                        "uninstall",
+                        "--exact",
+                        "--id",
+                        pkg,
+                        "--source",
+                        "winget",
+                        "--silent",
+                        "--accept-source-agreements",
+                    ]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                })
+                .collect(),
+
+            Self::Apt => vec![build_root_cmd(
+                privilege,
+                &["apt-get", "remove", "-y"],
+                pkgs,
+            )],
+            Self::Dnf => vec![build_root_cmd(privilege, &["dnf", "remove", "-y"], pkgs)],
+            Self::Pacman => vec![build_root_cmd(
+                privilege,
+                &["pacman", "-R", "--noconfirm"],
+                pkgs,
+            )],
+            Self::Yay => vec![build_cmd("yay", &["-R", "--noconfirm"], pkgs)],
+            Self::Paru => vec![build_cmd("paru", &["-R", "--noconfirm"], pkgs)],
+            Self::Zypper => vec![build_root_cmd(privilege, &["zypper", "remove", "-y"], pkgs)],
+            Self::Flatpak => vec![build_cmd("flatpak", &["uninstall", "-y"], pkgs)],
+            Self::Brew => vec![build_cmd("brew", &["uninstall"], pkgs)],
+            Self::Scoop => vec![build_cmd("scoop.cmd", &["uninstall"], pkgs)],
+            Self::Choco => vec![build_cmd("choco", &["uninstall", "-y"], pkgs)],
+            Self::Cargo => vec![build_cmd("cargo", &["uninstall"], pkgs)],
+            Self::Npm => vec![build_cmd(
+                if OS_INFO.platform == Platform::Windows {
+                    "npm.cmd"
+                } else {
+                    "npm"
+                },
+                &["uninstall", "-g"],
+                pkgs,
+            )],
+        };
+
+        run_package_commands(&commands, pkgs, "remove")?;
+        self.forget_removed(pkgs);
         Ok(())
     }
 
+    /// Drops removed packages from the cached lists, so they aren't reported as installed.
+    fn forget_removed(&self, pkgs: &[String]) {
+        let cache_id = self.cache_id();
+        cache::invalidate(&cache_id);
+        PKG_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            if let Some(entry) = cache.get_mut(&cache_id) {
+                pkgs.iter().for_each(|pkg| {
+                    entry.remove(pkg);
+                });
+            }
+        });
+    }
+
+    /// Upgrades already installed packages to their latest versions.
+    pub fn upgrade(&self, pkgs: &[String], privilege: &Privilege) -> anyhow::Result<()> {
+        if let Ok(cmd) = std::env::var("MEPRIS_UPGRADE_COMMAND") {
+            return run_override_command(&cmd, pkgs, "upgrade");
+        }
+
+        let commands = match self {
+            Self::Winget => pkgs
+                .iter()
+                .map(|pkg| CommandSpec {
+                    bin: "winget".into(),
+                    args: vec![
+                        "upgrade",
+                        "--exact",
+                        "--id",
+                        pkg,
+                        "--source",
+                        "winget",
+                        "--silent",
+                        "--accept-source-agreements",
+                        "--accept-package-agreements",
+                    ]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                })
+                .collect(),
+
+            Self::Apt => vec![build_root_cmd(
+                privilege,
+                &["apt-get", "install", "--only-upgrade", "-y"],
+                pkgs,
+            )],
+            Self::Dnf => vec![build_root_cmd(privilege, &["dnf", "upgrade", "-y"], pkgs)],
+            Self::Pacman => vec![build_root_cmd(
+                privilege,
+                &["pacman", "-S", "--noconfirm"],
+                pkgs,
+            )],
+            Self::Yay => vec![build_cmd("yay", &["-S", "--noconfirm"], pkgs)],
+            Self::Paru => vec![build_cmd("paru", &["-S", "--noconfirm"], pkgs)],
+            Self::Zypper => vec![build_root_cmd(privilege, &["zypper", "update", "-y"], pkgs)],
+            Self::Flatpak => vec![build_cmd("flatpak", &["update", "-y"], pkgs)],
+            Self::Brew => vec![build_cmd("brew", &["upgrade"], pkgs)],
+            Self::Scoop => vec![build_cmd("scoop.cmd", &["update"], pkgs)],
+            Self::Choco => vec![build_cmd("choco", &["upgrade", "-y"], pkgs)],
+            // installing an already installed crate updates it if a newer version exists
+            Self::Cargo => vec![build_cmd("cargo", &["install"], pkgs)],
+            Self::Npm => vec![build_cmd(
+                if OS_INFO.platform == Platform::Windows {
+                    "npm.cmd"
+                } else {
+                    "npm"
+                },
+                &["update", "-g"],
+                pkgs,
+            )],
+        };
+
+        run_package_commands(&commands, pkgs, "upgrade")
+    }
+
+    /// Version of the installed package, `None` if it isn't installed.
+    pub fn installed_version(&self, pkg: &str) -> anyhow::Result<Option<String>> {
+        if let Ok(version) = std::env::var("MEPRIS_INSTALLED_VERSION") {
+            return Ok(Some(version).filter(|v| !v.is_empty()));
+        }
+
+        let cmd = match self {
+            Self::Apt => build_cmd(
+                "dpkg-query",
+                &["-W", "--showformat=${db:Status-Abbrev}|${Version}"],
+                &[pkg.to_string()],
+            ),
+            Self::Dnf | Self::Zypper => {
+                build_cmd("rpm", &["-q", "--qf", "%{VERSION}"], &[pkg.to_string()])
+            }
+            Self::Pacman | Self::Yay | Self::Paru => {
+                build_cmd("pacman", &["-Q"], &[pkg.to_string()])
+            }
+            Self::Flatpak => build_cmd("flatpak", &["info"], &[pkg.to_string()]),
+            Self::Brew => build_cmd("brew", &["list", "--versions"], &[pkg.to_string()]),
+            Self::Scoop => build_cmd("scoop.cmd", &["list"], &[]),
+            Self::Choco => build_cmd("choco", &["list", "--limit-output", "--no-color"], &[]),
+            Self::Cargo => build_cmd("cargo", &["install", "--list"], &[]),
+            Self::Npm => build_cmd(
+                if OS_INFO.platform == Platform::Windows {
+                    "npm.cmd"
+                } else {
+                    "npm"
+                },
+                &["list", "--depth=0", "-g", "--json"],
+                &[],
+            ),
+            Self::Winget => build_cmd(
+                "winget",
+                &["export", "--source", "winget", "--include-versions", "-o"],
+                &[],
+            ),
+        };
+
+        let output = if self == &Self::Winget {
+            run_win_command_with_file_output(&cmd)?
+        } else {
+            let output = run_command(&cmd)?;
+            if !output.status.success() && !matches!(self, Self::Npm) {
+                // the query commands fail for packages that aren't installed
+                return Ok(None);
+            }
+            String::from_utf8_lossy(&output.stdout).to_string()
+        };
+
+        parse_installed_version(self, pkg, &output)
+    }
+
     pub fn is_installed(&self, pkg: &str) -> anyhow::Result<bool> {
         if let Ok(res) = std::env::var("MEPRIS_IS_INSTALLED_RESULT") {
             return Ok(res == "0");
         }
 
+        // all installed packages are listed at once and cached for the following checks
         let cmd = match self {
-            Self::Pacman | Self::Yay | Self::Paru => CommandSpec {
-                bin: "pacman".to_string(),
-                args: vec!["-Qq".to_string()],
-            },
-            Self::Apt => CommandSpec {
-                bin: "dpkg".to_string(),
-                args: vec!["-l".to_string(), pkg.to_string()],
-            },
-            Self::Dnf | Self::Zypper => CommandSpec {
-                bin: "rpm".to_string(),
-                args: vec!["-q".to_string(), pkg.to_string()],
-            },
-            Self::Flatpak => CommandSpec {
-                bin: "flatpak".to_string(),
-                args: vec!["info".to_string(), pkg.to_string()],
-            },
-            Self::Brew => CommandSpec {
-                bin: "brew".to_string(),
-                args: vec![
-                    "list".to_string(),
-                    "--versions".to_string(),
-                    pkg.to_string(),
-                ],
-            },
-            Self::Winget => CommandSpec {
-                bin: "winget".to_string(),
-                args: vec![
-                    "export".to_string(),
-                    "--source".to_string(),
-                    "winget".to_string(),
-                    "-o".to_string(),
-                ],
-            },
-            Self::Scoop => CommandSpec {
-                bin: "scoop.cmd".to_string(),
-                args: vec!["list".to_string()],
-            },
-            Self::Choco => CommandSpec {
-                bin: "choco".to_string(),
-                args: vec![
-                    "list".to_string(),
-                    "--limit-output".to_string(),
-                    "--no-color".to_string(),
+            Self::Pacman | Self::Yay | Self::Paru => build_cmd("pacman", &["-Qq"], &[]),
+            Self::Apt => build_cmd(
+                "dpkg-query",
+                &[
+                    "-W",
+                    "--showformat=${db:Status-Abbrev} ${Package} ${Architecture}\\n",
                 ],
-            },
-            Self::Cargo => CommandSpec {
-                bin: "cargo".to_string(),
-                args: vec!["install".to_string(), "--list".to_string()],
-            },
-            Self::Npm => CommandSpec {
-                bin: if OS_INFO.platform == Platform::Windows {
-                    "npm.cmd".to_string()
+                &[],
+            ),
+            Self::Dnf | Self::Zypper => build_cmd("rpm", &["-qa", "--qf", "%{NAME}\\n"], &[]),
+            Self::Flatpak => build_cmd("flatpak", &["list", "--columns=application"], &[]),
+            Self::Brew => build_cmd("brew", &["list", "-1"], &[]),
+            Self::Winget => build_cmd("winget", &["export", "--source", "winget", "-o"], &[]),
+            Self::Scoop => build_cmd("scoop.cmd", &["list"], &[]),
+            Self::Choco => build_cmd("choco", &["list", "--limit-output", "--no-color"], &[]),
+            Self::Cargo => build_cmd("cargo", &["install", "--list"], &[]),
+            Self::Npm => build_cmd(
+                if OS_INFO.platform == Platform::Windows {
+                    "npm.cmd"
                 } else {
-                    "npm".to_string()
+                    "npm"
                 },
-                args: vec![
-                    "list".to_string(),
-                    "--depth=0".to_string(),
-                    "-g".to_string(),
-                    "----parseable".to_string(),
-                ],
-            },
+                &["list", "--depth=0", "-g", "----parseable"],
+                &[],
+            ),
         };
 
-        if self.requires_cache() {
-            return run_cacheable_is_installed(self, &cmd, parse_packages_list_func(self)?, pkg);
-        }
-
-        let output = run_command(&cmd)?;
-        let out = String::from_utf8_lossy(&output.stdout);
+        let name = self.listed_name(pkg);
+        run_cacheable_is_installed(self, &cmd, parse_packages_list_func(self), name)
+    }
 
+    /// Name of the package in the list of installed packages: `brew list` prints formulae from
+    /// taps like `user/tap/foo` without the tap.
+    fn listed_name<'a>(&self, pkg: &'a str) -> &'a str {
         match self {
-            PackageManager::Dnf | PackageManager::Zypper | PackageManager::Flatpak => {
-                Ok(output.status.success())
-            }
+            Self::Brew => pkg.rsplit('/').next().unwrap_or(pkg),
+            _ => pkg,
+        }
+    }
+}
 
-            PackageManager::Apt => {
-                Ok(output.status.success() && out.lines().any(|line| line.starts_with("ii")))
-            }
+/// `cargo install --version` takes a semver requirement, where a bare partial version is invalid.
+fn cargo_version_req(req: &VersionReq) -> String {
+    match req.op {
+        VersionOp::Prefix if req.version.split('.').count() < 3 => format!("~{}", req.version),
+        VersionOp::Prefix => format!("={}", req.version),
+        _ => req.to_string(),
+    }
+}
 
-            PackageManager::Pacman | PackageManager::Yay | PackageManager::Paru => {
-                Ok(out.lines().any(|line| line == pkg))
-            }
+/// npm ranges already treat a bare partial version as a prefix, `20` is `20.x`.
+fn npm_version_range(req: &VersionReq) -> String {
+    match req.op {
+        VersionOp::Prefix => req.version.clone(),
+        _ => req.to_string(),
+    }
+}
 
-            PackageManager::Brew => Ok(out.contains(pkg)),
+fn build_cmd(cmd: &str, args: &[&str], pkgs: &[String]) -> CommandSpec {
+    CommandSpec {
+        bin: cmd.into(),
+        args: args
+            .iter()
+            .map(ToString::to_string)
+            .chain(pkgs.iter().cloned())
+            .collect(),
+    }
+}
 
-            PackageManager::Cargo => Ok(out
-                .lines()
-                .any(|line| line.starts_with(pkg) && line.contains(" v"))),
+/// Command of a package manager that has to run as root.
+fn build_root_cmd(privilege: &Privilege, args: &[&str], pkgs: &[String]) -> CommandSpec {
+    let cmd = build_cmd(args[0], &args[1..], pkgs);
+    let (bin, args) = privilege.wrap(&cmd.bin, cmd.args);
+    CommandSpec { bin, args }
+}
 
-            PackageManager::Npm
-            | PackageManager::Scoop
-            | PackageManager::Choco
-            | PackageManager::Winget => {
-                bail!("Unreachable code")
-            }
+fn run_package_commands(
+    commands: &[CommandSpec],
+    pkgs: &[String],
+    action: &str,
+) -> anyhow::Result<()> {
+    for cmd in commands {
+        let status = Command::new(&cmd.bin)
+            .args(&cmd.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .context(format!("Failed to {action} {}", pkgs.join(", ")))?;
+
+        if !status.success() {
+            bail!("Failed to {action} {}", pkgs.join(", "));
         }
     }
+    Ok(())
+}
+
+/// Runs `cmd` with the packages appended instead of the real package manager (used by tests).
+fn run_override_command(cmd: &str, pkgs: &[String], action: &str) -> anyhow::Result<()> {
+    let parts = shell_words::split(cmd)?;
+    let (program, args) = parts.split_first().unwrap();
+
+    let success = Command::new(program)
+        .args(args)
+        .args(pkgs)
+        .output()?
+        .status
+        .success();
+
+    if success {
+        Ok(())
+    } else {
+        bail!("Failed to {action} {}", pkgs.join(", "));
+    }
 }
 
 fn run_cacheable_is_installed(
@@ -332,28 +683,35 @@ fn run_cacheable_is_installed(
     parse: fn(output: String) -> anyhow::Result<HashSet<String>>,
     pkg: &str,
 ) -> anyhow::Result<bool> {
-    let res = PKG_CACHE.with(|cache| {
-        let mut cache = cache.borrow_mut();
-        let cache_id = manager.to_string();
+    let cache_id = manager.cache_id();
+    let cached = PKG_CACHE.with(|cache| cache.borrow().get(&cache_id).map(|p| p.contains(pkg)));
+    if let Some(res) = cached {
+        return Ok(res);
+    }
 
-        let packages = cache.entry(cache_id).or_insert_with(|| {
+    let packages = match cache::load(&cache_id) {
+        Some(packages) => packages,
+        None => {
             debug!("Package cache is empty for {}, populating...", manager);
             let _span = debug_span!("cache_population").entered();
 
             let output = if manager == &PackageManager::Winget {
-                run_win_command_with_file_output(cmd).unwrap()
+                run_win_command_with_file_output(cmd)?
             } else {
-                let res = run_command(cmd).unwrap();
+                let res = run_command(cmd)?;
                 String::from_utf8_lossy(&res.stdout).to_string()
             };
-            
-            debug!(event_type=%EventType::CachePopulationCompleted);
-            parse(output).unwrap()
-        });
+            let packages = parse(output)
+                .context(format!("Failed to parse installed packages of {manager}"))?;
 
-        packages.contains(pkg)
-    });
+            debug!(event_type=%EventType::CachePopulationCompleted);
+            cache::store(&cache_id, &packages);
+            packages
+        }
+    };
 
+    let res = packages.contains(pkg);
+    PKG_CACHE.with(|cache| cache.borrow_mut().insert(cache_id, packages));
     Ok(res)
 }
 
@@ -390,3 +748,55 @@ fn run_win_command_with_file_output(cmd: &CommandSpec) -> anyhow::Result<String>
     let file = fs::read_to_string(path).context("failed to read temp file")?;
     Ok(file)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::pkg::cache::CacheSettings;
+    use crate::utils::test::EnvGuard;
+    use serial_test::serial;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_pacman_family_is_never_refreshed() {
+        // `-Sy` is a partial upgrade and `-Syu` a full system upgrade, neither may run implicitly
+        for manager in [
+            PackageManager::Pacman,
+            PackageManager::Yay,
+            PackageManager::Paru,
+        ] {
+            assert!(!manager.has_index(), "{manager} has an index to refresh");
+            assert!(manager.refresh_command(&Privilege::None).is_none());
+        }
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(unix)]
+    fn test_removed_package_is_not_installed() {
+        let dir = tempdir().unwrap();
+        cache::set_settings(CacheSettings {
+            path: dir.path().join("pkg_cache.json"),
+            ttl: Duration::from_secs(600),
+            refresh: false,
+        });
+        let _remove = EnvGuard::new("MEPRIS_REMOVE_COMMAND", "true");
+        let manager = PackageManager::Cargo;
+        let cache_id = manager.cache_id();
+        cache::store(
+            &cache_id,
+            &HashSet::from(["ripgrep".to_string(), "fd-find".to_string()]),
+        );
+        assert!(manager.is_installed("ripgrep").unwrap());
+
+        manager
+            .remove(&["ripgrep".to_string()], &Privilege::None)
+            .unwrap();
+
+        assert!(!manager.is_installed("ripgrep").unwrap());
+        assert!(manager.is_installed("fd-find").unwrap());
+        assert_eq!(cache::load(&cache_id), None);
+        cache::reset_settings();
+    }
+}
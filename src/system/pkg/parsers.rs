@@ -1,20 +1,89 @@
 use crate::system::pkg::PackageManager;
-use anyhow::{Context, bail};
+use anyhow::Context;
 use serde_json::Value;
 use std::collections::HashSet;
 
 type ParserFn = fn(String) -> anyhow::Result<HashSet<String>>;
 
-pub fn parse_packages_list_func(manager: &PackageManager) -> anyhow::Result<ParserFn> {
+pub fn parse_packages_list_func(manager: &PackageManager) -> ParserFn {
     match manager {
-        PackageManager::Scoop => Ok(SCOOP_PARSE_PACKAGES_LIST),
-        PackageManager::Choco => Ok(CHOCO_PARSE_PACKAGES_LIST),
-        PackageManager::Winget => Ok(WINGET_PARSE_PACKAGES_LIST),
-        PackageManager::Npm => Ok(NPM_PARSE_PACKAGES_LIST),
-        _ => bail!("unsupported package manager"),
+        PackageManager::Apt => DPKG_PARSE_PACKAGES_LIST,
+        PackageManager::Dnf | PackageManager::Zypper => RPM_PARSE_PACKAGES_LIST,
+        PackageManager::Pacman | PackageManager::Yay | PackageManager::Paru => {
+            PACMAN_PARSE_PACKAGES_LIST
+        }
+        PackageManager::Flatpak => FLATPAK_PARSE_PACKAGES_LIST,
+        PackageManager::Brew => BREW_PARSE_PACKAGES_LIST,
+        PackageManager::Cargo => CARGO_PARSE_PACKAGES_LIST,
+        PackageManager::Scoop => SCOOP_PARSE_PACKAGES_LIST,
+        PackageManager::Choco => CHOCO_PARSE_PACKAGES_LIST,
+        PackageManager::Winget => WINGET_PARSE_PACKAGES_LIST,
+        PackageManager::Npm => NPM_PARSE_PACKAGES_LIST,
     }
 }
 
+// "<status> <name> <arch>" lines, only "ii" (install ok installed) packages are installed.
+// Packages are listed by name and as `name:arch`, to match multiarch packages like `libc6:i386`.
+const DPKG_PARSE_PACKAGES_LIST: fn(String) -> anyhow::Result<HashSet<String>> = |output| {
+    let mut ids = HashSet::new();
+    for line in output.lines() {
+        let Some(rest) = line.strip_prefix("ii") else {
+            continue;
+        };
+        let mut fields = rest.split_whitespace();
+        if let Some(name) = fields.next() {
+            if let Some(arch) = fields.next() {
+                ids.insert(format!("{name}:{arch}"));
+            }
+            ids.insert(name.to_string());
+        }
+    }
+    Ok(ids)
+};
+
+// one name per line, for `rpm -qa --qf '%{NAME}\n'`, `pacman -Qq` and `flatpak list --columns=application`
+const NAMES_PARSE_PACKAGES_LIST: fn(String) -> anyhow::Result<HashSet<String>> = |output| {
+    Ok(output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+};
+
+const RPM_PARSE_PACKAGES_LIST: fn(String) -> anyhow::Result<HashSet<String>> =
+    NAMES_PARSE_PACKAGES_LIST;
+
+const PACMAN_PARSE_PACKAGES_LIST: fn(String) -> anyhow::Result<HashSet<String>> =
+    NAMES_PARSE_PACKAGES_LIST;
+
+const FLATPAK_PARSE_PACKAGES_LIST: fn(String) -> anyhow::Result<HashSet<String>> =
+    NAMES_PARSE_PACKAGES_LIST;
+
+// formulae and casks, with "==> Formulae" and "==> Casks" headers on a terminal
+const BREW_PARSE_PACKAGES_LIST: fn(String) -> anyhow::Result<HashSet<String>> = |output| {
+    Ok(output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("==>"))
+        .map(String::from)
+        .collect())
+};
+
+// "<name> v<version>:" lines followed by indented binaries
+const CARGO_PARSE_PACKAGES_LIST: fn(String) -> anyhow::Result<HashSet<String>> = |output| {
+    let mut ids = HashSet::new();
+    for line in output.lines() {
+        if line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        if let Some((name, _)) = line.split_once(" v") {
+            ids.insert(name.to_string());
+        }
+    }
+    Ok(ids)
+};
+
 const WINGET_PARSE_PACKAGES_LIST: fn(String) -> anyhow::Result<HashSet<String>> = |output| {
     let mut ids = HashSet::new();
     let v: Value = serde_json::from_str(&output)
@@ -74,10 +143,154 @@ const NPM_PARSE_PACKAGES_LIST: fn(String) -> anyhow::Result<HashSet<String>> = |
     Ok(ids)
 };
 
+/// Version of `pkg` from the output of [`PackageManager::installed_version`]'s query,
+/// `None` if the package is not installed.
+pub fn parse_installed_version(
+    manager: &PackageManager,
+    pkg: &str,
+    output: &str,
+) -> anyhow::Result<Option<String>> {
+    let version = match manager {
+        PackageManager::Apt => output
+            .trim()
+            .split_once('|')
+            .filter(|(status, _)| status.starts_with("ii"))
+            .map(|(_, version)| version.to_string()),
+
+        PackageManager::Dnf | PackageManager::Zypper => {
+            Some(output.trim().to_string()).filter(|v| !v.is_empty())
+        }
+
+        PackageManager::Pacman | PackageManager::Yay | PackageManager::Paru => output
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .find(|(name, _)| *name == pkg)
+            .map(|(_, version)| version.trim().to_string()),
+
+        PackageManager::Flatpak => output
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("Version:"))
+            .map(|version| version.trim().to_string())
+            .next(),
+
+        // "<name> <version> [<older version>...]", the last version is the linked one
+        PackageManager::Brew => output
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .find(|parts| parts.len() > 1 && parts[0] == pkg)
+            .and_then(|parts| parts.last().map(|v| v.to_string())),
+
+        // "<name> v<version>:" or "<name> v<version> (<source>):"
+        PackageManager::Cargo => output
+            .lines()
+            .filter_map(|line| line.split_once(" v"))
+            .find(|(name, _)| *name == pkg)
+            .and_then(|(_, rest)| rest.split([':', ' ']).next().map(String::from)),
+
+        PackageManager::Scoop => output
+            .lines()
+            .skip(3)
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .find(|parts| parts.len() >= 2 && parts[0] == pkg)
+            .map(|parts| parts[1].to_string()),
+
+        PackageManager::Choco => output
+            .lines()
+            .filter_map(|line| line.trim().split_once('|'))
+            .find(|(name, _)| name.eq_ignore_ascii_case(pkg))
+            .map(|(_, version)| version.to_string()),
+
+        PackageManager::Npm => {
+            let v: Value = serde_json::from_str(output)
+                .context(format!("couldn't parse npm output: {}", output))?;
+            v.get("dependencies")
+                .and_then(|deps| deps.get(pkg))
+                .and_then(|dep| dep.get("version"))
+                .and_then(|version| version.as_str())
+                .map(String::from)
+        }
+
+        PackageManager::Winget => {
+            let v: Value = serde_json::from_str(output)
+                .context(format!("couldn't parse winget output: {}", output))?;
+            v.get("Sources")
+                .and_then(|s| s.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|source| source.get("Packages").and_then(|p| p.as_array()))
+                .flatten()
+                .find(|package| {
+                    package.get("PackageIdentifier").and_then(|id| id.as_str()) == Some(pkg)
+                })
+                .and_then(|package| package.get("Version"))
+                .and_then(|version| version.as_str())
+                .map(String::from)
+        }
+    };
+
+    Ok(version)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_dpkg_parse() {
+        let output = include_str!("../../../tests/fixtures/dpkg.txt");
+        let res = DPKG_PARSE_PACKAGES_LIST(output.to_string()).unwrap();
+        assert!(res.contains("git"));
+        assert!(res.contains("libc6"));
+        assert!(res.contains("libc6:i386"));
+        assert!(!res.contains("zlib1g:i386"));
+        assert!(!res.contains("nano"));
+    }
+
+    #[test]
+    fn test_rpm_parse() {
+        let output = include_str!("../../../tests/fixtures/rpm.txt");
+        let res = RPM_PARSE_PACKAGES_LIST(output.to_string()).unwrap();
+        assert!(res.contains("git-core"));
+        assert!(res.contains("gpg-pubkey"));
+    }
+
+    #[test]
+    fn test_pacman_parse() {
+        let output = include_str!("../../../tests/fixtures/pacman.txt");
+        let res = PACMAN_PARSE_PACKAGES_LIST(output.to_string()).unwrap();
+        assert!(res.contains("base-devel"));
+        assert!(res.contains("yay-bin"));
+        assert!(!res.contains(""));
+    }
+
+    #[test]
+    fn test_flatpak_parse() {
+        let output = include_str!("../../../tests/fixtures/flatpak.txt");
+        let res = FLATPAK_PARSE_PACKAGES_LIST(output.to_string()).unwrap();
+        assert!(res.contains("org.mozilla.firefox"));
+        assert!(res.contains("org.freedesktop.Platform"));
+    }
+
+    #[test]
+    fn test_brew_parse() {
+        let output = include_str!("../../../tests/fixtures/brew.txt");
+        let res = BREW_PARSE_PACKAGES_LIST(output.to_string()).unwrap();
+        assert!(res.contains("python@3.12"));
+        assert!(res.contains("visual-studio-code"));
+        assert!(!res.iter().any(|p| p.starts_with("==>")));
+        assert!(res.contains(PackageManager::Brew.listed_name("hashicorp/tap/terraform")));
+    }
+
+    #[test]
+    fn test_cargo_parse() {
+        let output = include_str!("../../../tests/fixtures/cargo.txt");
+        let res = CARGO_PARSE_PACKAGES_LIST(output.to_string()).unwrap();
+        assert_eq!(
+            res,
+            HashSet::from_iter(["cargo-edit", "ripgrep", "stylua"].map(String::from))
+        );
+    }
+
     #[test]
     fn test_winget_parse() {
         let output = include_str!("../../../tests/fixtures/winget.txt");
@@ -109,4 +322,77 @@ mod tests {
         assert!(res.contains("yaml-language-server"));
         assert!(res.contains("@angular/cli"));
     }
+
+    #[test]
+    fn test_parse_installed_version() {
+        let version = |manager: PackageManager, pkg: &str, output: &str| {
+            parse_installed_version(&manager, pkg, output).unwrap()
+        };
+
+        assert_eq!(
+            version(PackageManager::Apt, "git", "ii |1:2.43.0-1ubuntu7"),
+            Some("1:2.43.0-1ubuntu7".to_string())
+        );
+        assert_eq!(version(PackageManager::Apt, "git", "rc |1:2.43.0"), None);
+        assert_eq!(
+            version(PackageManager::Pacman, "ripgrep", "ripgrep 14.1.1-1\n"),
+            Some("14.1.1-1".to_string())
+        );
+        assert_eq!(
+            version(PackageManager::Brew, "node", "node 21.7.1 22.3.0\n"),
+            Some("22.3.0".to_string())
+        );
+        assert_eq!(
+            version(
+                PackageManager::Flatpak,
+                "org.mozilla.firefox",
+                "Firefox\n\n          ID: org.mozilla.firefox\n     Version: 128.0\n"
+            ),
+            Some("128.0".to_string())
+        );
+        assert_eq!(version(PackageManager::Dnf, "git", ""), None);
+    }
+
+    #[test]
+    fn test_parse_installed_version_fixtures() {
+        let version = |manager: PackageManager, pkg: &str, output: &str| {
+            parse_installed_version(&manager, pkg, output).unwrap()
+        };
+
+        let cargo = include_str!("../../../tests/fixtures/cargo.txt");
+        assert_eq!(
+            version(PackageManager::Cargo, "ripgrep", cargo),
+            Some("14.1.1".to_string())
+        );
+        assert_eq!(
+            version(PackageManager::Cargo, "stylua", cargo),
+            Some("0.20.0".to_string())
+        );
+        assert_eq!(version(PackageManager::Cargo, "rip", cargo), None);
+
+        let scoop = include_str!("../../../tests/fixtures/scoop.txt");
+        assert_eq!(
+            version(PackageManager::Scoop, "7zip", scoop),
+            Some("25.01".to_string())
+        );
+
+        let choco = include_str!("../../../tests/fixtures/choco.txt");
+        assert_eq!(
+            version(PackageManager::Choco, "dbeaver", choco),
+            Some("25.3.4".to_string())
+        );
+
+        let npm = include_str!("../../../tests/fixtures/npm_versions.json");
+        assert_eq!(
+            version(PackageManager::Npm, "@angular/cli", npm),
+            Some("17.3.8".to_string())
+        );
+        assert_eq!(version(PackageManager::Npm, "typescript", npm), None);
+
+        let winget = include_str!("../../../tests/fixtures/winget_versions.json");
+        assert_eq!(
+            version(PackageManager::Winget, "7zip.7zip", winget),
+            Some("24.09".to_string())
+        );
+    }
 }
@@ -0,0 +1,263 @@
+use anyhow::bail;
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOp {
+    /// Bare version: matches versions starting with the same components, `20` matches `20.11.1`.
+    Prefix,
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// Version constraint of a package, e.g. `14.1.0`, `=14.1.0` or `>=20`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    pub op: VersionOp,
+    pub version: String,
+}
+
+impl VersionReq {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let text = text.trim();
+        let (op, version) = [
+            (">=", VersionOp::Ge),
+            ("<=", VersionOp::Le),
+            (">", VersionOp::Gt),
+            ("<", VersionOp::Lt),
+            ("=", VersionOp::Eq),
+        ]
+        .iter()
+        .find_map(|(prefix, op)| text.strip_prefix(prefix).map(|rest| (*op, rest.trim())))
+        .unwrap_or((VersionOp::Prefix, text));
+
+        if version.is_empty() || version.contains(char::is_whitespace) {
+            bail!("invalid version constraint '{text}'");
+        }
+        Ok(VersionReq {
+            op,
+            version: version.to_string(),
+        })
+    }
+
+    pub fn matches(&self, installed: &str) -> bool {
+        let ord = compare_versions(installed, &self.version);
+        match self.op {
+            VersionOp::Prefix => {
+                let installed = tokens(installed);
+                let required = tokens(&self.version);
+                // a pre-release of the required version doesn't match it
+                installed.starts_with(&required)
+                    && installed
+                        .get(required.len())
+                        .is_none_or(|token| compare_extra(token) != Ordering::Less)
+            }
+            VersionOp::Eq => ord == Ordering::Equal,
+            VersionOp::Gt => ord == Ordering::Greater,
+            VersionOp::Ge => ord != Ordering::Less,
+            VersionOp::Lt => ord == Ordering::Less,
+            VersionOp::Le => ord != Ordering::Greater,
+        }
+    }
+
+    /// The version to install if the constraint selects a single version.
+    pub fn pinned(&self) -> Option<&str> {
+        match self.op {
+            VersionOp::Prefix | VersionOp::Eq => Some(&self.version),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            VersionOp::Prefix => "",
+            VersionOp::Eq => "=",
+            VersionOp::Gt => ">",
+            VersionOp::Ge => ">=",
+            VersionOp::Lt => "<",
+            VersionOp::Le => "<=",
+        };
+        write!(f, "{op}{}", self.version)
+    }
+}
+
+/// Splits `name@version` into the package name and its version constraint;
+/// a leading `@` belongs to the name (scoped npm packages like `@angular/cli@17`).
+pub fn parse_package_spec(spec: &str) -> anyhow::Result<(&str, Option<VersionReq>)> {
+    match spec.rfind('@').filter(|&idx| idx > 0) {
+        Some(idx) => Ok((&spec[..idx], Some(VersionReq::parse(&spec[idx + 1..])?))),
+        None => Ok((spec, None)),
+    }
+}
+
+/// Loose comparison of versions reported by different package managers:
+/// the epoch (`1:`) and a leading `v` are ignored, numbers are compared numerically
+/// and missing numeric components count as zero.
+/// As in dpkg and rpm, pre-releases sort before the release: `1.0~beta2 < 1.0rc1 < 1.0 < 1.0p1`.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let a = tokens(a);
+    let b = tokens(b);
+    for i in 0..a.len().max(b.len()) {
+        let ord = match (a.get(i), b.get(i)) {
+            (Some(a), Some(b)) => compare_tokens(a, b),
+            (Some(a), None) => compare_extra(a),
+            (None, Some(b)) => compare_extra(b).reverse(),
+            (None, None) => unreachable!(),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Words that mark a pre-release when they follow the version number.
+const PRE_RELEASES: [&str; 5] = ["alpha", "beta", "dev", "pre", "rc"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    /// `~`, sorts before everything, even the end of the version.
+    Tilde,
+    Alpha(&'a str),
+    /// Digits without leading zeros.
+    Num(&'a str),
+}
+
+fn tokens(version: &str) -> Vec<Token<'_>> {
+    let version = version.trim();
+    let version = match version.split_once(':') {
+        Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => version,
+    };
+    let version = version.trim_start_matches('v');
+
+    let mut tokens = Vec::new();
+    let mut rest = version;
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '~' {
+            tokens.push(Token::Tilde);
+            1
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let digits = rest[..len].trim_start_matches('0');
+            tokens.push(Token::Num(digits));
+            len
+        } else if c.is_ascii_alphabetic() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            tokens.push(Token::Alpha(&rest[..len]));
+            len
+        } else {
+            c.len_utf8()
+        };
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// Orders `~` before letters and letters before numbers, so `1.0rc1 < 1.0.1`.
+fn compare_tokens(a: &Token, b: &Token) -> Ordering {
+    match (a, b) {
+        (Token::Num(a), Token::Num(b)) => a.len().cmp(&b.len()).then_with(|| a.cmp(b)),
+        (Token::Alpha(a), Token::Alpha(b)) => a.cmp(b),
+        (Token::Tilde, Token::Tilde) => Ordering::Equal,
+        (Token::Tilde, _) | (Token::Alpha(_), Token::Num(_)) => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+/// Order of a version with the extra `token` against the same version without it.
+fn compare_extra(token: &Token) -> Ordering {
+    match token {
+        Token::Tilde => Ordering::Less,
+        Token::Alpha(word) if PRE_RELEASES.contains(&word.to_ascii_lowercase().as_str()) => {
+            Ordering::Less
+        }
+        Token::Num("") => Ordering::Equal,
+        _ => Ordering::Greater,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            VersionReq::parse(">= 20").unwrap(),
+            VersionReq {
+                op: VersionOp::Ge,
+                version: "20".to_string()
+            }
+        );
+        assert_eq!(VersionReq::parse("14.1.0").unwrap().op, VersionOp::Prefix);
+        assert_eq!(VersionReq::parse("=14.1.0").unwrap().op, VersionOp::Eq);
+        assert!(VersionReq::parse(">=").is_err());
+        assert!(VersionReq::parse("1 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_package_spec() {
+        assert_eq!(parse_package_spec("ripgrep").unwrap(), ("ripgrep", None));
+        let (name, req) = parse_package_spec("node@>=20").unwrap();
+        assert_eq!(name, "node");
+        assert_eq!(req.unwrap().to_string(), ">=20");
+        let (name, req) = parse_package_spec("@angular/cli@17").unwrap();
+        assert_eq!(name, "@angular/cli");
+        assert_eq!(req.unwrap().version, "17");
+        assert_eq!(
+            parse_package_spec("@angular/cli").unwrap(),
+            ("@angular/cli", None)
+        );
+        assert!(parse_package_spec("node@").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let req = |text: &str| VersionReq::parse(text).unwrap();
+
+        assert!(req("20").matches("20.11.1"));
+        assert!(!req("20").matches("200.1"));
+        assert!(!req("20.11.1").matches("20.11"));
+        assert!(req("14.1.0").matches("14.1.0-1"));
+        assert!(req("=14.1.0").matches("v14.1.0"));
+        assert!(!req("=14.1.0").matches("14.1.1"));
+        assert!(req(">=20").matches("20.11.1"));
+        assert!(req(">=20").matches("22.0.0"));
+        assert!(!req(">=20").matches("18.19.0"));
+        assert!(req("<2.0").matches("1.9.10"));
+        assert!(!req(">1.2.10").matches("1.2.9"));
+        assert!(req("<=1:2.3").matches("1:2.3.0"));
+        assert!(!req(">=2.0").matches("2.0rc1"));
+        assert!(req("<2.0").matches("2.0~beta1"));
+        assert!(!req("2.0").matches("2.0rc1"));
+        assert!(req("2").matches("2.0rc1"));
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("2:1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0rc1", "1.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0~beta2", "1.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0~beta2", "1.0rc1"), Ordering::Less);
+        assert_eq!(compare_versions("1.0-alpha", "1.0-beta"), Ordering::Less);
+        assert_eq!(compare_versions("1.0rc1", "1.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0rc1", "1.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0p1", "1.0"), Ordering::Greater);
+        assert_eq!(
+            compare_versions("1.2.3-1ubuntu1", "1.2.3"),
+            Ordering::Greater
+        );
+        assert_eq!(compare_versions("1.02", "1.2"), Ordering::Equal);
+    }
+}
@@ -0,0 +1,110 @@
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// How commands that need root are run.
+#[derive(Debug, Clone, Deserialize, JsonSchema, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Privilege {
+    Sudo,
+    Doas,
+    Run0,
+    None,
+    /// `sudo`, or no escalation when already running as root.
+    #[default]
+    Auto,
+}
+
+impl Privilege {
+    /// Program that runs a command as root, `None` if the command runs as is.
+    pub fn get_command(&self) -> Option<&'static str> {
+        match self {
+            Self::Sudo => Some("sudo"),
+            Self::Doas => Some("doas"),
+            Self::Run0 => Some("run0"),
+            Self::None => None,
+            Self::Auto => (!is_root()).then_some("sudo"),
+        }
+    }
+
+    /// Prefixes the command with the escalation program.
+    pub fn wrap(&self, bin: &str, args: Vec<String>) -> (String, Vec<String>) {
+        match self.get_command() {
+            Some(escalation) => (
+                escalation.to_string(),
+                std::iter::once(bin.to_string()).chain(args).collect(),
+            ),
+            None => (bin.to_string(), args),
+        }
+    }
+}
+
+/// Runs the command as root, failing if it doesn't succeed.
+pub fn run_root(privilege: &Privilege, args: &[&str]) -> Result<()> {
+    let args = args
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<String>>();
+    let (bin, args) = privilege.wrap(&args[0], args[1..].to_vec());
+    run(&bin, &args)
+}
+
+/// Runs the command without input, failing if it doesn't succeed.
+pub fn run(bin: &str, args: &[impl AsRef<str>]) -> Result<()> {
+    let args = args.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
+    let status = Command::new(bin)
+        .args(&args)
+        .stdin(Stdio::null())
+        .status()
+        .context(format!("failed to run {bin}"))?;
+    if !status.success() {
+        bail!("{bin} {} failed", args.join(" "));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and can't fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_root() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let args = vec!["install".to_string(), "-y".to_string()];
+
+        assert_eq!(
+            Privilege::Doas.wrap("apt-get", args.clone()),
+            (
+                "doas".to_string(),
+                vec![
+                    "apt-get".to_string(),
+                    "install".to_string(),
+                    "-y".to_string()
+                ]
+            )
+        );
+        assert_eq!(
+            Privilege::None.wrap("apt-get", args.clone()),
+            ("apt-get".to_string(), args)
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_auto_drops_escalation_for_root() {
+        let expected = if is_root() { None } else { Some("sudo") };
+        assert_eq!(Privilege::Auto.get_command(), expected);
+    }
+}
@@ -2,6 +2,7 @@ use strum::IntoEnumIterator;
 use which::which;
 
 use crate::system::os_info::Platform;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::collections::HashSet;
 use std::sync::Mutex;
@@ -10,7 +11,7 @@ use strum_macros::EnumIter;
 static AVAILABLE_SHELLS: Mutex<Option<HashSet<Shell>>> = Mutex::new(None);
 static MOCKED_SHELLS: Mutex<Option<HashSet<Shell>>> = Mutex::new(None);
 
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq, Hash, EnumIter)]
+#[derive(Debug, Clone, Deserialize, JsonSchema, PartialEq, Eq, Hash, EnumIter)]
 #[serde(rename_all = "lowercase")]
 pub enum Shell {
     Bash,
@@ -1,5 +1,6 @@
 use anyhow::Context;
 use anyhow::Result;
+use std::fs;
 use std::path::{Path, PathBuf};
 
 pub fn get_absolute_path(file: &str, base_dir: Option<&Path>) -> Result<PathBuf> {
@@ -21,3 +22,22 @@ pub fn get_absolute_path(file: &str, base_dir: Option<&Path>) -> Result<PathBuf>
         .canonicalize()
         .with_context(|| format!("Failed to canonicalize file path '{file}'"))
 }
+
+/// Resolves a path from the config: expands `~` and makes relative paths relative to `base_dir`.
+pub fn resolve_path(path: &str, base_dir: &Path) -> PathBuf {
+    let expanded = shellexpand::tilde(path);
+    base_dir.join(expanded.as_ref())
+}
+
+/// Sets permission bits of the file; does nothing on Windows.
+#[cfg(unix)]
+pub fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).context("failed to set mode")
+}
+
+#[cfg(windows)]
+pub fn set_mode(_: &Path, _: u32) -> Result<()> {
+    Ok(())
+}
@@ -304,3 +304,57 @@ steps:
     assert!(output.contains("file"));
     assert!(output.contains("child.yaml"));
 }
+
+#[test]
+#[cfg(unix)]
+fn test_list_steps_shows_template_fields() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let mut output = Vec::new();
+
+    fs::write(
+        &file_path,
+        r#"
+templates:
+  windows-tool:
+    os: "windows"
+    tags: ["tag-windows"]
+
+steps:
+  - id: "step-windows"
+    extends: windows-tool
+    tags: ["tag-tool"]
+
+  - id: "step-unix"
+    tags: ["tag-unix"]
+"#,
+    )
+    .expect("Failed to write file.yaml");
+
+    let res = handle(
+        ListStepsArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            tags_expr: Some("tag-windows".to_string()),
+            plain: true,
+            all: true,
+        },
+        &mut output,
+    );
+    let output = String::from_utf8_lossy(&output);
+    assert!(res.is_ok());
+    assert_eq!(output.trim(), "step-windows");
+
+    let mut output = Vec::new();
+    let res = handle(
+        ListStepsArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            tags_expr: None,
+            plain: false,
+            all: false,
+        },
+        &mut output,
+    );
+    let output = String::from_utf8_lossy(&output);
+    assert!(res.is_ok());
+    assert!(!output.contains("step-windows"));
+}
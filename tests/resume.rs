@@ -56,6 +56,7 @@ fn test_resume_uses_absolute_path() {
             dry_run: false,
             show_skipped: false,
             debug: false,
+            keep_going: false,
         },
         &mut output,
     );
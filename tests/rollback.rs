@@ -0,0 +1,233 @@
+use mepris::{
+    EnvGuard,
+    cli::{ResumeArgs, RollbackArgs, RunArgs},
+    commands::{resume, rollback, run},
+};
+use serial_test::serial;
+use std::{fs, io};
+use tempfile::tempdir;
+
+fn rollback_args(steps: &[&str]) -> RollbackArgs {
+    RollbackArgs {
+        file: None,
+        steps: steps.iter().map(|s| s.to_string()).collect(),
+        dry_run: false,
+        debug: false,
+    }
+}
+
+#[test]
+#[serial]
+fn test_rollback_undoes_last_run_in_reverse_order() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let state_file_path = dir.path().join("state.json");
+
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "installed"
+            check: "true"
+            undo: echo "undo installed"
+          - id: "runtime"
+            provides: ["runtime"]
+            script: echo "run runtime"
+            undo: echo "undo runtime"
+          - id: "app"
+            requires:
+              - id: "runtime"
+            script: echo "run app"
+            undo: echo "undo app"
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+
+    let _guard = EnvGuard::new("MEPRIS_STATE_PATH", state_file_path.to_str().unwrap());
+    let _guard2 = EnvGuard::new("MEPRIS_TEST_SCRIPT_OUTPUT", "1");
+    run::handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            ..Default::default()
+        },
+        &mut io::sink(),
+    )
+    .expect("mepris run failed");
+
+    let mut output = Vec::new();
+    let res = rollback::handle(rollback_args(&[]), &mut output);
+    let output = String::from_utf8_lossy(&output);
+
+    assert!(res.is_ok(), "mepris rollback failed: {}", res.unwrap_err());
+    // the already completed step wasn't run, so it's not undone
+    assert!(!output.contains("undo installed"), "{output}");
+    let app = output.find("undo app").expect(&output);
+    let runtime = output.find("undo runtime").expect(&output);
+    assert!(app < runtime, "{output}");
+
+    let mut output = Vec::new();
+    rollback::handle(rollback_args(&[]), &mut output).expect("second rollback failed");
+    let output = String::from_utf8_lossy(&output);
+
+    assert!(output.contains("Nothing to roll back"), "{output}");
+}
+
+#[test]
+#[serial]
+fn test_failed_rollback_keeps_undone_steps_out_of_state() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let state_file_path = dir.path().join("state.json");
+
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "runtime"
+            provides: ["runtime"]
+            script: echo "run runtime"
+            undo: exit 1
+          - id: "app"
+            requires:
+              - id: "runtime"
+            script: echo "run app"
+            undo: echo "undo app"
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+
+    let _guard = EnvGuard::new("MEPRIS_STATE_PATH", state_file_path.to_str().unwrap());
+    let _guard2 = EnvGuard::new("MEPRIS_TEST_SCRIPT_OUTPUT", "1");
+    run::handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            ..Default::default()
+        },
+        &mut io::sink(),
+    )
+    .expect("mepris run failed");
+
+    let mut output = Vec::new();
+    let res = rollback::handle(rollback_args(&[]), &mut output);
+    let output = String::from_utf8_lossy(&output);
+    assert!(res.is_err(), "undo of runtime should fail: {output}");
+    assert!(output.contains("undo app"), "{output}");
+
+    // only the step whose undo failed is left to roll back
+    let mut output = Vec::new();
+    let res = rollback::handle(rollback_args(&[]), &mut output);
+    let output = String::from_utf8_lossy(&output);
+    assert!(res.is_err(), "undo of runtime should fail again: {output}");
+    assert!(!output.contains("undo app"), "{output}");
+}
+
+#[test]
+#[serial]
+fn test_rollback_after_resume_undoes_steps_of_both_runs() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let state_file_path = dir.path().join("state.json");
+    let marker = dir.path().join("marker");
+
+    fs::write(
+        &file_path,
+        format!(
+            r#"
+        steps:
+          - id: "first"
+            script: echo "run first"
+            undo: echo "undo first"
+          - id: "second"
+            script: test -f {}
+            undo: echo "undo second"
+        "#,
+            marker.display()
+        ),
+    )
+    .expect("Failed to write file.yaml");
+
+    let _guard = EnvGuard::new("MEPRIS_STATE_PATH", state_file_path.to_str().unwrap());
+    let _guard2 = EnvGuard::new("MEPRIS_TEST_SCRIPT_OUTPUT", "1");
+    let res = run::handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            ..Default::default()
+        },
+        &mut io::sink(),
+    );
+    assert!(res.is_err());
+
+    fs::write(&marker, "").expect("Failed to write marker");
+    resume::handle(
+        ResumeArgs {
+            interactive: false,
+            dry_run: false,
+            show_skipped: false,
+            debug: false,
+            keep_going: false,
+        },
+        &mut io::sink(),
+    )
+    .expect("mepris resume failed");
+
+    let mut output = Vec::new();
+    let res = rollback::handle(rollback_args(&[]), &mut output);
+    let output = String::from_utf8_lossy(&output);
+
+    assert!(res.is_ok(), "mepris rollback failed: {}", res.unwrap_err());
+    assert!(output.contains("undo second"), "{output}");
+    assert!(output.contains("undo first"), "{output}");
+}
+
+#[test]
+#[serial]
+#[cfg(unix)]
+fn test_rollback_removes_only_packages_installed_by_mepris() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let state_file_path = dir.path().join("state.json");
+    let log_path = dir.path().join("log");
+
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "editor"
+            package_source: apt
+            packages: ["helix"]
+          - id: "git"
+            package_source: apt
+            packages: ["git"]
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+
+    let _guard = EnvGuard::new("MEPRIS_STATE_PATH", state_file_path.to_str().unwrap());
+    let _guard2 = EnvGuard::new("MEPRIS_INSTALL_COMMAND", "true");
+    let _guard3 = EnvGuard::new(
+        "MEPRIS_REMOVE_COMMAND",
+        &format!("sh -c 'echo removed \"$@\" >> {}' sh", log_path.display()),
+    );
+    {
+        let _guard = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+        run::handle(
+            RunArgs {
+                file: file_path.to_str().unwrap().to_string(),
+                steps: vec!["editor".to_string()],
+                ..Default::default()
+            },
+            &mut io::sink(),
+        )
+        .expect("mepris run failed");
+    }
+
+    // git is installed, but not by mepris
+    let _guard4 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "0");
+    let res = rollback::handle(rollback_args(&["editor", "git"]), &mut io::sink());
+
+    assert!(res.is_ok(), "mepris rollback failed: {}", res.unwrap_err());
+    assert_eq!(
+        fs::read_to_string(&log_path).expect("Failed to read log"),
+        "removed helix\n"
+    );
+}
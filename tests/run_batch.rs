@@ -0,0 +1,122 @@
+use mepris::{EnvGuard, cli::RunArgs, commands::run::handle};
+use serial_test::serial;
+use std::fs;
+use std::io::sink;
+use tempfile::tempdir;
+
+#[test]
+#[cfg(unix)]
+#[serial]
+fn test_run_batches_packages_of_independent_steps() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let log_path = dir.path().join("log");
+    let _guard = EnvGuard::new(
+        "MEPRIS_INSTALL_COMMAND",
+        &format!("sh -c 'echo \"$@\" >> {}' sh", log_path.display()),
+    );
+    let _guard2 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+    let _guard3 = EnvGuard::new(
+        "MEPRIS_STATE_PATH",
+        dir.path().join("state.json").to_str().unwrap(),
+    );
+
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "git"
+            package_source: apt
+            packages: ["git"]
+          - id: "docker"
+            package_source: apt
+            packages: ["docker.io", "git"]
+            provides: ["docker"]
+            script: "true"
+          - id: "compose"
+            requires: ["docker"]
+            package_source: apt
+            packages: ["docker-compose"]
+          - id: "rust-tools"
+            package_source: cargo
+            packages: ["ripgrep"]
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+
+    let res = handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            batch_packages: true,
+            ..Default::default()
+        },
+        &mut sink(),
+    );
+
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    // "compose" has to wait for the script of "docker"
+    assert_eq!(
+        fs::read_to_string(&log_path).expect("Failed to read log"),
+        "git docker.io\ndocker-compose\nripgrep\n"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+#[serial]
+fn test_run_reports_failed_batch_per_step() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let log_path = dir.path().join("log");
+    let state_path = dir.path().join("state.json");
+    let _guard = EnvGuard::new(
+        "MEPRIS_INSTALL_COMMAND",
+        &format!(
+            "sh -c 'case \"$*\" in *missing*) exit 1;; esac; echo \"$@\" >> {}' sh",
+            log_path.display()
+        ),
+    );
+    let _guard2 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+    let _guard3 = EnvGuard::new("MEPRIS_STATE_PATH", state_path.to_str().unwrap());
+
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "git"
+            package_source: apt
+            packages: ["git"]
+          - id: "broken"
+            package_source: apt
+            packages: ["missing-package"]
+          - id: "curl"
+            package_source: apt
+            packages: ["curl"]
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+
+    let res = handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            batch_packages: true,
+            keep_going: true,
+            ..Default::default()
+        },
+        &mut sink(),
+    );
+
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "1 step(s) failed, 0 step(s) blocked"
+    );
+    assert_eq!(
+        fs::read_to_string(&log_path).expect("Failed to read log"),
+        "git\ncurl\n"
+    );
+    let state: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&state_path).expect("Failed to read state"))
+            .expect("Failed to parse state");
+    assert_eq!(state["last_step_id"], "broken");
+    assert_eq!(state["executed_steps"], serde_json::json!(["git", "curl"]));
+}
@@ -0,0 +1,93 @@
+use mepris::{EnvGuard, cli::RunArgs, commands::run::handle};
+use serial_test::serial;
+use std::fs;
+use std::io::sink;
+use tempfile::tempdir;
+
+#[test]
+#[cfg(unix)]
+#[serial]
+fn test_run_writes_lock_and_installs_locked_versions() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let log_path = dir.path().join("log");
+    let _guard = EnvGuard::new(
+        "MEPRIS_INSTALL_COMMAND",
+        &format!("sh -c 'echo \"$@\" >> {}' sh", log_path.display()),
+    );
+    let _guard2 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+    let _guard3 = EnvGuard::new("MEPRIS_INSTALLED_VERSION", "14.1.1");
+
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "rust-tools"
+            package_source: cargo
+            packages: ["ripgrep"]
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+
+    let args = || RunArgs {
+        file: file_path.to_str().unwrap().to_string(),
+        ..Default::default()
+    };
+
+    let res = handle(args(), &mut sink());
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    let lock = fs::read_to_string(dir.path().join("mepris.lock")).expect("Failed to read lock");
+    assert!(lock.contains("rust-tools:"), "{lock}");
+    assert!(lock.contains("manager: cargo"), "{lock}");
+    assert!(lock.contains("name: ripgrep"), "{lock}");
+    assert!(lock.contains("version: 14.1.1"), "{lock}");
+
+    // an older version is installed on another machine
+    let _guard3 = EnvGuard::new("MEPRIS_INSTALLED_VERSION", "13.0.0");
+    let res = handle(
+        RunArgs {
+            locked: true,
+            ..args()
+        },
+        &mut sink(),
+    );
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    assert_eq!(
+        fs::read_to_string(&log_path).expect("Failed to read log"),
+        "ripgrep\nripgrep@=14.1.1\n"
+    );
+}
+
+#[test]
+#[serial]
+fn test_run_locked_without_lock_file() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "rust-tools"
+            package_source: cargo
+            packages: ["ripgrep"]
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+
+    let res = handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            dry_run: true,
+            locked: true,
+            ..Default::default()
+        },
+        &mut sink(),
+    );
+
+    assert!(res.is_err());
+    assert!(
+        res.unwrap_err()
+            .to_string()
+            .contains("Failed to read lock file"),
+    );
+}
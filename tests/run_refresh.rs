@@ -0,0 +1,128 @@
+use mepris::{EnvGuard, cli::RunArgs, commands::run::handle};
+use serial_test::serial;
+use std::fs;
+use std::io::sink;
+use std::path::Path;
+use tempfile::tempdir;
+
+const CONFIG: &str = r#"
+        steps:
+          - id: "git"
+            package_source: apt
+            packages: ["git"]
+          - id: "curl"
+            package_source: apt
+            packages: ["curl"]
+          - id: "rust-tools"
+            package_source: cargo
+            packages: ["ripgrep"]
+        "#;
+
+/// Runs the config with logged installs and index refreshes, returns the log.
+fn run_logged(config: &str, no_refresh: bool) -> String {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let log_path = dir.path().join("log");
+    let _guard = EnvGuard::new("MEPRIS_INSTALL_COMMAND", &log_command("install", &log_path));
+    let _guard2 = EnvGuard::new("MEPRIS_REFRESH_COMMAND", &log_command("refresh", &log_path));
+    let _guard3 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+    let _guard4 = EnvGuard::new(
+        "MEPRIS_STATE_PATH",
+        dir.path().join("state.json").to_str().unwrap(),
+    );
+
+    fs::write(&file_path, config).expect("Failed to write file.yaml");
+
+    let res = handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            no_refresh,
+            ..Default::default()
+        },
+        &mut sink(),
+    );
+
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    fs::read_to_string(&log_path).expect("Failed to read log")
+}
+
+fn log_command(action: &str, log_path: &Path) -> String {
+    format!("sh -c 'echo {action} \"$@\" >> {}' sh", log_path.display())
+}
+
+#[test]
+#[cfg(unix)]
+#[serial]
+fn test_run_refreshes_index_once_per_manager() {
+    assert_eq!(
+        run_logged(CONFIG, false),
+        "refresh apt\ninstall git\ninstall curl\ninstall ripgrep\n"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+#[serial]
+fn test_run_without_refresh() {
+    assert_eq!(
+        run_logged(CONFIG, true),
+        "install git\ninstall curl\ninstall ripgrep\n"
+    );
+
+    let config = format!("\n        defaults:\n          refresh_index: false{CONFIG}");
+    assert_eq!(
+        run_logged(&config, false),
+        "install git\ninstall curl\ninstall ripgrep\n"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+#[serial]
+fn test_run_never_refreshes_pacman_family() {
+    let config = r#"
+        steps:
+          - id: "git"
+            package_source: pacman
+            packages: ["git"]
+          - id: "curl"
+            package_source: pacman
+            packages: ["curl"]
+        "#;
+
+    // `pacman -Sy` is a partial upgrade and `-Syu` a full system upgrade, neither is implicit
+    assert_eq!(run_logged(config, false), "install git\ninstall curl\n");
+}
+
+#[test]
+#[serial]
+fn test_dry_run_shows_index_refresh() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let _guard = EnvGuard::new("MEPRIS_INSTALL_COMMAND", "false");
+    let _guard2 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+
+    fs::write(&file_path, CONFIG).expect("Failed to write file.yaml");
+
+    let mut output = Vec::new();
+    let res = handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            dry_run: true,
+            ..Default::default()
+        },
+        &mut output,
+    );
+    let output = String::from_utf8_lossy(&output);
+
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    assert_eq!(
+        output.matches("Would refresh package index").count(),
+        1,
+        "{output}"
+    );
+    assert!(
+        output.contains("Would refresh package index (apt)"),
+        "{output}"
+    );
+}
@@ -0,0 +1,93 @@
+use mepris::{EnvGuard, cli::RunArgs, commands::run::handle};
+use serial_test::serial;
+use std::fs;
+use std::io::sink;
+use tempfile::tempdir;
+
+const CONFIG: &str = r#"
+        steps:
+          - id: "compose"
+            requires: ["docker"]
+            package_source: apt
+            packages: ["docker-compose-plugin"]
+          - id: "docker"
+            package_source: apt
+            repositories:
+              - type: apt
+                name: docker
+                uri: https://download.docker.com/linux/ubuntu
+                suite: noble
+                components: [stable]
+                key: keys/docker.asc
+            packages: ["docker-ce"]
+        "#;
+
+#[test]
+#[cfg(unix)]
+#[serial]
+fn test_run_adds_repositories_before_packages() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let log_path = dir.path().join("log");
+    let _guard = EnvGuard::new(
+        "MEPRIS_ADD_REPOSITORY_COMMAND",
+        &format!("sh -c 'echo add \"$@\" >> {}' sh", log_path.display()),
+    );
+    let _guard2 = EnvGuard::new(
+        "MEPRIS_INSTALL_COMMAND",
+        &format!("sh -c 'echo install \"$@\" >> {}' sh", log_path.display()),
+    );
+    let _guard3 = EnvGuard::new("MEPRIS_REPOSITORY_ADDED_RESULT", "1");
+    let _guard4 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+    let _guard5 = EnvGuard::new(
+        "MEPRIS_STATE_PATH",
+        dir.path().join("state.json").to_str().unwrap(),
+    );
+
+    fs::write(&file_path, CONFIG).expect("Failed to write file.yaml");
+
+    let res = handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            ..Default::default()
+        },
+        &mut sink(),
+    );
+
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    assert_eq!(
+        fs::read_to_string(&log_path).expect("Failed to read log"),
+        "add docker\ninstall docker-ce\ninstall docker-compose-plugin\n"
+    );
+}
+
+#[test]
+#[serial]
+fn test_dry_run_shows_pending_repositories() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let _guard = EnvGuard::new("MEPRIS_INSTALL_COMMAND", "false");
+    let _guard2 = EnvGuard::new("MEPRIS_REPOSITORY_ADDED_RESULT", "1");
+    let _guard3 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "0");
+
+    fs::write(&file_path, CONFIG).expect("Failed to write file.yaml");
+
+    let mut output = Vec::new();
+    let res = handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            steps: vec!["docker".to_string()],
+            dry_run: true,
+            ..Default::default()
+        },
+        &mut output,
+    );
+    let output = String::from_utf8_lossy(&output);
+
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    assert!(output.contains("Would run step docker"), "{output}");
+    assert!(
+        output.contains("Would add repository docker (apt)"),
+        "{output}"
+    );
+}
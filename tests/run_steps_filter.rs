@@ -64,3 +64,49 @@ fn test_run_steps_filter_preserve_order() {
     let step1_pos = output.find("step1").unwrap();
     assert!(step2_pos < step1_pos, "Expected step2 before step1");
 }
+
+#[test]
+#[cfg(unix)]
+fn test_run_conditional_include_skipped_by_os() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let mut output = Vec::new();
+
+    fs::write(
+        &file_path,
+        r#"
+        includes:
+          - path: windows.yaml
+            os: windows
+        steps:
+          - id: "step1"
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+
+    fs::write(
+        dir.path().join("windows.yaml"),
+        r#"
+        steps:
+          - id: "win-step1"
+          - id: "win-step2"
+        "#,
+    )
+    .expect("Failed to write windows.yaml");
+
+    let res = handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            dry_run: true,
+            show_skipped: true,
+            ..Default::default()
+        },
+        &mut output,
+    );
+    let output = String::from_utf8_lossy(&output);
+    assert!(res.is_ok());
+    assert!(
+        output.contains("Skipped steps due to OS mismatch: win-step1, win-step2"),
+        "unexpected output: {output}"
+    );
+}
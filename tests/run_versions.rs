@@ -0,0 +1,85 @@
+use mepris::{EnvGuard, cli::RunArgs, commands::run::handle};
+use serial_test::serial;
+use std::fs;
+use std::io::sink;
+use tempfile::tempdir;
+
+#[test]
+#[cfg(unix)]
+#[serial]
+fn test_run_installs_outdated_versioned_packages() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let log_path = dir.path().join("log");
+    let _guard = EnvGuard::new(
+        "MEPRIS_INSTALL_COMMAND",
+        &format!("sh -c 'echo \"$@\" >> {}' sh", log_path.display()),
+    );
+    let _guard2 = EnvGuard::new("MEPRIS_INSTALLED_VERSION", "18.19.0");
+    let _guard3 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "tools"
+            package_source: apt
+            packages:
+              - git
+              - fd@>=9
+              - name: nodejs
+                version: "20"
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+    fs::write(dir.path().join("pkg_aliases.yaml"), "fd:\n  apt: fd-find\n")
+        .expect("Failed to write pkg_aliases.yaml");
+
+    let res = handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            ..Default::default()
+        },
+        &mut sink(),
+    );
+
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    assert_eq!(
+        fs::read_to_string(&log_path).expect("Failed to read log"),
+        "git\nfd-find@>=9\nnodejs@20\n"
+    );
+}
+
+#[test]
+#[serial]
+fn test_dry_run_shows_version_constraints() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let _guard = EnvGuard::new("MEPRIS_INSTALL_COMMAND", "false");
+    let _guard2 = EnvGuard::new("MEPRIS_INSTALLED_VERSION", "13.0.0");
+
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "tools"
+            package_source: cargo
+            packages: ["ripgrep@14.1.0"]
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+
+    let mut output = Vec::new();
+    let res = handle(
+        RunArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            dry_run: true,
+            ..Default::default()
+        },
+        &mut output,
+    );
+    let output = String::from_utf8_lossy(&output);
+
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    assert!(output.contains("ripgrep@14.1.0"), "{output}");
+}
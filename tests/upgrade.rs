@@ -0,0 +1,98 @@
+use mepris::{EnvGuard, cli::UpgradeArgs, commands::upgrade::handle, run_with_tracing};
+use serial_test::serial;
+use std::fs;
+use std::io::sink;
+use tempfile::tempdir;
+
+#[test]
+#[cfg(unix)]
+#[serial]
+fn test_upgrade_selected_steps_with_aliases() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let _guard = EnvGuard::new("MEPRIS_UPGRADE_COMMAND", "echo upgrading");
+    let _guard2 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "0");
+
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "cli"
+            tags: ["cli"]
+            package_source: apt
+            packages: ["git", "fd"]
+          - id: "rust-tools"
+            tags: ["cli"]
+            package_source: cargo
+            packages: ["ripgrep"]
+          - id: "desktop"
+            package_source: flatpak
+            packages: ["org.mozilla.firefox"]
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+    fs::write(dir.path().join("pkg_aliases.yaml"), "fd:\n  apt: fd-find\n")
+        .expect("Failed to write pkg_aliases.yaml");
+
+    let mut res: anyhow::Result<()> = Ok(());
+    let trace_output = run_with_tracing(false, || {
+        res = handle(
+            UpgradeArgs {
+                file: file_path.to_str().unwrap().to_string(),
+                tags_expr: Some("cli".to_string()),
+                ..Default::default()
+            },
+            &mut sink(),
+        );
+    })
+    .as_string();
+
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    assert!(
+        trace_output.contains("Upgrading packages (apt): git, fd-find"),
+        "{trace_output}"
+    );
+    assert!(
+        trace_output.contains("Upgrading packages (cargo): ripgrep"),
+        "{trace_output}"
+    );
+    assert!(!trace_output.contains("firefox"), "{trace_output}");
+}
+
+#[test]
+#[serial]
+fn test_upgrade_dry_run_skips_not_installed_packages() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("file.yaml");
+    let _guard = EnvGuard::new("MEPRIS_UPGRADE_COMMAND", "false");
+    let _guard2 = EnvGuard::new("MEPRIS_IS_INSTALLED_RESULT", "1");
+
+    fs::write(
+        &file_path,
+        r#"
+        steps:
+          - id: "rust-tools"
+            package_source: cargo
+            packages: ["ripgrep"]
+        "#,
+    )
+    .expect("Failed to write file.yaml");
+
+    let mut output = Vec::new();
+    let res = handle(
+        UpgradeArgs {
+            file: file_path.to_str().unwrap().to_string(),
+            dry_run: true,
+            ..Default::default()
+        },
+        &mut output,
+    );
+    let output = String::from_utf8_lossy(&output);
+
+    assert!(res.is_ok(), "error: {}", res.unwrap_err());
+    assert!(
+        output.contains("Not installed, skipped: ripgrep (cargo)"),
+        "{output}"
+    );
+    assert!(output.contains("No packages would be upgraded"), "{output}");
+}